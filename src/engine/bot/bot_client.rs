@@ -0,0 +1,185 @@
+use glam::{IVec2, Vec2};
+
+use crate::engine::{bot::constants::{BOT_EDIT_HEIGHT, BOT_SPAWN_SPACING}, common::{BlockChange, ClientPacket, HandshakePacket, ServerPacket, ServerStatus}, latency::LatencyTracker, network::Connection, server::common::{world_to_chunk_pos_2d, BlockType, LayerType}};
+
+// What a bot does every tick. Intervals are in bot ticks.
+#[derive(Clone, Copy)]
+pub struct BotScript {
+    pub walk_speed: f32,
+    pub walk_range: f32,
+    pub view_distance: i32,
+    pub position_interval: u64,
+    pub chunk_request_interval: u64,
+    pub edit_interval: u64,
+    pub ping_interval: u64,
+}
+
+impl Default for BotScript {
+    fn default() -> Self {
+        Self {
+            walk_speed: 0.5,
+            walk_range: 96.0,
+            view_distance: 2,
+            position_interval: 5,
+            chunk_request_interval: 20,
+            edit_interval: 10,
+            ping_interval: 20,
+        }
+    }
+}
+
+// A client without a window or renderer, driven by a BotScript
+pub struct BotClient {
+    player_nickname: String,
+    connection: Connection,
+    script: BotScript,
+    pub latency: LatencyTracker,
+    home_x: f32,
+    position: Vec2,
+    direction: f32,
+    ticks: u64,
+    placed_block: Option<IVec2>,
+    pub chunks_received: u32,
+    pub block_changes_received: u32,
+    pub server_status: Option<ServerStatus>,
+    pub disconnect_reason: Option<String>,
+}
+
+impl BotClient {
+    pub fn connect(index: usize, connection: Connection, script: BotScript) -> BotClient {
+        let player_nickname = format!("bot-{index}");
+        let home_x = index as f32 * BOT_SPAWN_SPACING;
+
+        connection.send(ClientPacket::Handshake(HandshakePacket {
+            player_uuid: fastrand::u64(..),
+            player_nickname: player_nickname.clone(),
+            // Bots are attached by the server itself and never asked for a token
+            identity_token: None,
+        }), true);
+
+        BotClient {
+            player_nickname,
+            connection,
+            script,
+            latency: LatencyTracker::new(),
+            home_x,
+            position: Vec2::new(home_x, BOT_EDIT_HEIGHT as f32),
+            direction: 1.0,
+            ticks: 0,
+            placed_block: None,
+            chunks_received: 0,
+            block_changes_received: 0,
+            server_status: None,
+            disconnect_reason: None,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.handle_packets();
+        if self.disconnect_reason.is_some() {
+            return;
+        }
+
+        self.walk();
+
+        if self.ticks.is_multiple_of(self.script.position_interval) {
+            self.send(ClientPacket::PlayerPosition((self.position.x, self.position.y)));
+        }
+
+        if self.ticks.is_multiple_of(self.script.chunk_request_interval) {
+            self.request_chunks();
+        }
+
+        if self.ticks.is_multiple_of(self.script.edit_interval) {
+            self.edit_block();
+        }
+
+        if self.ticks.is_multiple_of(self.script.ping_interval) {
+            let ping = self.latency.next_ping();
+            self.send(ClientPacket::Ping(ping));
+        }
+
+        self.ticks += 1;
+    }
+
+    pub fn disconnect(&self) {
+        self.send(ClientPacket::Disconnect);
+    }
+
+    fn handle_packets(&mut self) {
+        while let Some(packet) = self.connection.try_recv::<ServerPacket>() {
+            match packet {
+                ServerPacket::Ping(ping) => self.send(ClientPacket::Pong(ping)),
+                ServerPacket::Pong(pong) => self.latency.on_pong(&pong),
+                ServerPacket::Status(status) => self.server_status = Some(status),
+                ServerPacket::Chunk(_) => self.chunks_received += 1,
+                ServerPacket::BlockChange(_) => self.block_changes_received += 1,
+                ServerPacket::Disconnect(reason) => self.disconnect_reason = Some(reason),
+                _ => {}
+            }
+        }
+    }
+
+    // Paces back and forth around the spawn point
+    fn walk(&mut self) {
+        self.position.x += self.script.walk_speed * self.direction;
+
+        if (self.position.x - self.home_x).abs() >= self.script.walk_range {
+            self.direction = -self.direction;
+        }
+    }
+
+    fn request_chunks(&self) {
+        let center = world_to_chunk_pos_2d(self.position.floor().as_ivec2());
+        let view_distance = self.script.view_distance;
+
+        let mut chunks = Vec::new();
+        for x in -view_distance..=view_distance {
+            for y in -view_distance..=view_distance {
+                chunks.push((center.x + x, center.y + y));
+            }
+        }
+
+        self.send(ClientPacket::RequestChunks(chunks));
+    }
+
+    // Alternates between placing a block where the bot stands and removing it again
+    fn edit_block(&mut self) {
+        let (world_pos, block_change) = match self.placed_block.take() {
+            Some(world_pos) => (world_pos, BlockChange {
+                layer: LayerType::Foreground,
+                block_type: BlockType::Air,
+                block_id: 0,
+                texture_index: 0,
+            }),
+            None => {
+                let world_pos = self.position.floor().as_ivec2();
+                self.placed_block = Some(world_pos);
+                (world_pos, BlockChange {
+                    layer: LayerType::Foreground,
+                    block_type: BlockType::Tile,
+                    block_id: 0,
+                    texture_index: 0,
+                })
+            }
+        };
+
+        self.send(ClientPacket::BlockEdit(((world_pos.x as i64, world_pos.y as i64), block_change)));
+    }
+
+    fn send(&self, packet: ClientPacket) {
+        self.connection.send(packet, true);
+    }
+
+    pub fn get_nickname(&self) -> &str {
+        &self.player_nickname
+    }
+
+    pub fn get_bytes_sent(&self) -> u64 {
+        self.connection.get_bytes_sent()
+    }
+
+    pub fn get_bytes_received(&self) -> u64 {
+        self.connection.get_bytes_received()
+    }
+}
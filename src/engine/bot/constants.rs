@@ -0,0 +1,9 @@
+use crate::engine::command_registry::PermissionLevel;
+
+// Bots are simulated at a lower rate than the server ticks
+pub const BOT_TICK_RATE: u64 = 20;
+// Spawn positions are spread out so bots do not all request the same chunks
+pub const BOT_SPAWN_SPACING: f32 = 64.0;
+pub const BOT_EDIT_HEIGHT: i32 = 40;
+// Bots place and break blocks, so this has to reach BLOCK_EDIT_PERMISSION_LEVEL
+pub const BOT_PERMISSION_LEVEL: PermissionLevel = PermissionLevel::Moderator;
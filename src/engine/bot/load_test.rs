@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use crate::engine::{bot::{bot_client::{BotClient, BotScript}, constants::{BOT_PERMISSION_LEVEL, BOT_TICK_RATE}}, network::Connection, server::server::Server};
+
+// Connects the bots to the server right away and simulates them on their
+// own thread, so the server tick loop only pays for handling their packets
+pub fn spawn_load_test(server: &mut Server, bot_count: usize, duration: Duration) {
+    let mut connections = Vec::with_capacity(bot_count);
+    for _ in 0..bot_count {
+        let (bot_connection, server_connection) = Connection::pair();
+        server.accept_local_connection(server_connection, BOT_PERMISSION_LEVEL);
+        connections.push(bot_connection);
+    }
+
+    std::thread::spawn(move || {
+        println!("Load test started with {} bots for {:?}", bot_count, duration);
+        run_bots(connections, duration);
+    });
+}
+
+fn run_bots(connections: Vec<Connection>, duration: Duration) {
+    let script = BotScript::default();
+    let mut bots: Vec<BotClient> = connections.into_iter()
+        .enumerate()
+        .map(|(index, connection)| BotClient::connect(index, connection, script))
+        .collect();
+
+    let tick_duration = Duration::from_micros(1_000_000 / BOT_TICK_RATE);
+    let start_time = Instant::now();
+    let mut next_tick = start_time;
+    let mut tps_samples: Vec<f32> = Vec::new();
+
+    while start_time.elapsed() < duration {
+        for bot in &mut bots {
+            bot.tick();
+        }
+
+        // Every bot sees the same status packets, one is enough to sample
+        if let Some(status) = bots.first().and_then(|bot| bot.server_status) {
+            tps_samples.push(status.ticks_per_second);
+        }
+
+        next_tick += tick_duration;
+        let now = Instant::now();
+        if next_tick > now {
+            std::thread::sleep(next_tick - now);
+        } else {
+            next_tick = now + tick_duration;
+        }
+    }
+
+    for bot in &bots {
+        bot.disconnect();
+    }
+
+    print_report(&bots, &tps_samples, start_time.elapsed());
+}
+
+fn print_report(bots: &[BotClient], tps_samples: &[f32], elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+
+    println!("Load test finished after {:.1}s with {} bots", seconds, bots.len());
+
+    if tps_samples.is_empty() {
+        println!("  Server TPS: no status received");
+    } else {
+        let average = tps_samples.iter().sum::<f32>() / tps_samples.len() as f32;
+        let minimum = tps_samples.iter().copied().fold(f32::MAX, f32::min);
+        println!("  Server TPS: {:.2} average, {:.2} minimum", average, minimum);
+    }
+
+    let mut total_received = 0;
+    let mut total_sent = 0;
+    for bot in bots {
+        let received = bot.get_bytes_received();
+        let sent = bot.get_bytes_sent();
+        total_received += received;
+        total_sent += sent;
+
+        println!("  {:<8} down {:>10.2} KB/s, up {:>8.2} KB/s, {} chunks, {} block changes, {}",
+            bot.get_nickname(),
+            received as f64 / 1000.0 / seconds,
+            sent as f64 / 1000.0 / seconds,
+            bot.chunks_received,
+            bot.block_changes_received,
+            bot.latency.describe());
+
+        if let Some(reason) = &bot.disconnect_reason {
+            println!("           disconnected: {}", reason);
+        }
+    }
+
+    println!("  Total down {:.2} KB/s, up {:.2} KB/s", total_received as f64 / 1000.0 / seconds, total_sent as f64 / 1000.0 / seconds);
+}
@@ -0,0 +1,3 @@
+pub mod bot_client;
+pub mod load_test;
+pub mod constants;
@@ -0,0 +1,66 @@
+use winit::{event::KeyEvent, keyboard::{Key, NamedKey}};
+
+// Line being typed into the in-game chat. While open it swallows all
+// keyboard input, Enter submits the line and Escape throws it away.
+pub struct ChatInput {
+    open: bool,
+    buffer: String,
+}
+
+pub enum ChatInputResult {
+    Typing,
+    Submitted(String),
+    Cancelled,
+}
+
+impl ChatInput {
+    pub fn new() -> ChatInput {
+        ChatInput {
+            open: false,
+            buffer: String::new(),
+        }
+    }
+
+    pub fn open(&mut self, prefix: &str) {
+        self.open = true;
+        self.buffer = prefix.to_string();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn get_buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) -> ChatInputResult {
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => {
+                self.open = false;
+                return ChatInputResult::Submitted(std::mem::take(&mut self.buffer));
+            },
+            Key::Named(NamedKey::Escape) => {
+                self.open = false;
+                self.buffer.clear();
+                return ChatInputResult::Cancelled;
+            },
+            Key::Named(NamedKey::Backspace) => {
+                self.buffer.pop();
+            },
+            _ => {
+                if let Some(text) = &event.text {
+                    self.buffer.extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+
+        ChatInputResult::Typing
+    }
+}
+
+impl Default for ChatInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,39 +1,64 @@
-use crate::engine::{client::{client_chunk::ClientChunk, constants::ZOOM_SPEED, state::State}, command_registry::{self, DebugCommandWithArgs}, common::{ChunkMesh, PacketHeader, ServerPacket}, time::Time};
+use crate::engine::{client::{chat::{ChatInput, ChatInputResult}, client_chunk::ClientChunk, identity::ClientIdentity, constants::{PING_INTERVAL, WINDOW_TITLE, ZOOM_SPEED}, state::State}, command_registry::{self, CommandSource, ConsoleOutput, DebugCommandWithArgs}, common::{ChunkMesh, ClientPacket, HandshakePacket, ServerPacket, ServerStatus, WeatherState, WorldTime}, latency::LatencyTracker, network::Connection, server::{common::{world_to_chunk_pos, world_to_chunk_pos_2d, world_to_local_pos, world_to_local_pos_2d}, constants::{CHUNK_SIZE, TICK_RATE}}, time::Time};
 use glam::IVec2;
 use winit::{application::ApplicationHandler, dpi::PhysicalSize, event::{KeyEvent, MouseScrollDelta, WindowEvent}, event_loop::ActiveEventLoop, keyboard::{KeyCode, PhysicalKey}, window::{Window, WindowId}};
-use std::{collections::HashMap, sync::{mpsc::Receiver, Arc}};
+use std::{collections::HashMap, sync::{mpsc::Receiver, Arc}, time::Instant};
 
 pub struct Client {
     state: Option<State>,
     pub time: Time,
     console_listener: Receiver<DebugCommandWithArgs>,
-    server_listener: Receiver<Vec<u8>>,
+    server_connection: Connection,
+    connected: bool,
+    pub latency: LatencyTracker,
+    last_ping_sent: Instant,
+    pub server_status: Option<ServerStatus>,
+    // Last clock the server sent and when it arrived
+    world_time: Option<(WorldTime, Instant)>,
+    // Last weather the server sent for the dimension and when it arrived
+    weather: Option<(WeatherState, Instant)>,
     pub client_config: ClientConfig,
-    player_uuid: u64,
+    identity: ClientIdentity,
     player_nickname: String,
     loaded_chunks: HashMap<IVec2, ClientChunk>,
     zoom_factor: f32,
+    chat_input: ChatInput,
 }
 
 impl Client {
-    pub fn new(console_listener: Receiver<DebugCommandWithArgs>, server_listener: Receiver<Vec<u8>>) -> Self {
-        Self {
+    pub fn new(console_listener: Receiver<DebugCommandWithArgs>, server_connection: Connection) -> Self {
+        let client = Self {
             state: None,
             time: Time::new(),
             console_listener: console_listener,
-            server_listener: server_listener,
+            server_connection,
+            connected: true,
+            latency: LatencyTracker::new(),
+            last_ping_sent: Instant::now(),
+            server_status: None,
+            world_time: None,
+            weather: None,
             client_config: ClientConfig::default(),
-            player_uuid: fastrand::u64(..),
+            identity: ClientIdentity::load(),
             player_nickname: "playerboy".to_string(),
             loaded_chunks: HashMap::new(),
             zoom_factor: 1.0,
-        }
+            chat_input: ChatInput::new(),
+        };
+
+        client.send_packet(ClientPacket::Handshake(HandshakePacket {
+            player_uuid: client.identity.uuid,
+            player_nickname: client.player_nickname.clone(),
+            identity_token: client.identity.token,
+        }));
+
+        client
     }
 
     fn redraw(&mut self) {
         self.time.update();
         self.on_handle_command();
         self.on_handle_server_packet();
+        self.on_keepalive();
         self.on_update_frame();
         self.on_render();
     }
@@ -45,6 +70,7 @@ impl Client {
     }
 
     fn close(&self, event_loop: &ActiveEventLoop) {
+        self.send_packet(ClientPacket::Disconnect);
         event_loop.exit();
     }
 }
@@ -52,7 +78,7 @@ impl Client {
 impl ApplicationHandler for Client {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window_attributes = Window::default_attributes()
-                .with_title("swagrarria")
+                .with_title(WINDOW_TITLE)
                 .with_inner_size(winit::dpi::LogicalSize::new(128.0, 128.0));
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
         let state = pollster::block_on(State::new(window.clone()));
@@ -67,6 +93,15 @@ impl ApplicationHandler for Client {
                 WindowEvent::CloseRequested => self.close(event_loop),
                 WindowEvent::RedrawRequested => self.redraw(),
                 WindowEvent::Resized(size) => self.resize(size),
+                WindowEvent::KeyboardInput {
+                    event: key_event @ KeyEvent {
+                        state: winit::event::ElementState::Pressed,
+                        ..
+                    },
+                    ..
+                } if self.chat_input.is_open() => {
+                    self.on_chat_key(key_event);
+                },
                 WindowEvent::KeyboardInput {
                     event:
                         KeyEvent {
@@ -111,10 +146,22 @@ impl Client {
         KeyCode::KeyP => {
             let fps = self.time.average_fps();
             println!("Average FPS: {:.2}", fps);
+            println!("Latency: {}", self.latency.describe());
+            if let Some(status) = self.server_status {
+                println!("Server TPS: {:.2}, {} player(s) online", status.ticks_per_second, status.player_count);
+            }
         }
         KeyCode::KeyO => {
             self.time.reset_average_fps();
         }
+        KeyCode::KeyT | KeyCode::Enter => {
+            self.chat_input.open("");
+            self.update_chat_title();
+        }
+        KeyCode::Slash => {
+            self.chat_input.open("/");
+            self.update_chat_title();
+        }
         _ => {}
         }
     }
@@ -133,63 +180,132 @@ impl Client {
         self.zoom_factor *= zoom_change_factor;
     }
 
-    fn on_handle_command(&mut self) {
-        while let Ok(cmd) = self.console_listener.try_recv() {
-            command_registry::handle_client_command(self, &cmd);
+    fn on_chat_key(&mut self, event: &KeyEvent) {
+        if let ChatInputResult::Submitted(line) = self.chat_input.handle_key(event) {
+            self.send_chat(line);
         }
+        self.update_chat_title();
     }
 
-    fn on_handle_server_packet(&mut self) {
-        while let Ok(raw_packet) = self.server_listener.try_recv() {
-            // decode to packet header
-            let (packet, _bytes_consumed): (PacketHeader, usize) = bincode::decode_from_slice(&raw_packet, bincode::config::standard()).unwrap();
-
-            let is_compressed = packet.is_compressed;
-            let original_size = packet.original_size;
-
-            let packet: ServerPacket = if is_compressed {
-                let decompressed_packet = lz4_flex::decompress(&packet.data, original_size).unwrap();
-
-                let (packet, _bytes) = bincode::decode_from_slice(&decompressed_packet, bincode::config::standard()).unwrap();
-
-                packet
+    // There is no text rendering yet, so the line being typed goes in the title bar
+    fn update_chat_title(&self) {
+        if let Some(state) = &self.state {
+            if self.chat_input.is_open() {
+                state.get_window().set_title(&format!("> {}", self.chat_input.get_buffer()));
             } else {
-                let (packet, _bytes) = bincode::decode_from_slice(&packet.data, bincode::config::standard()).unwrap();
+                state.get_window().set_title(WINDOW_TITLE);
+            }
+        }
+    }
 
-                packet
-            };
+    pub fn send_chat(&self, message: String) {
+        if message.trim().is_empty() {
+            return;
+        }
+        self.send_packet(ClientPacket::Chat(message));
+    }
+
+    fn on_handle_command(&mut self) {
+        while let Ok(cmd) = self.console_listener.try_recv() {
+            let result = command_registry::handle_client_command(self, &cmd, CommandSource::console(), &mut ConsoleOutput);
+            result.write_to(&mut ConsoleOutput);
+        }
+    }
 
+    fn on_handle_server_packet(&mut self) {
+        while let Some(packet) = self.server_connection.try_recv::<ServerPacket>() {
             match packet {
                 ServerPacket::Chunk(packet) => {
                     let coord = IVec2::new(packet.0.0, packet.0.1);
                     let mesh = ChunkMesh::from(&*packet.1);
-                    if self.loaded_chunks.contains_key(&coord) {
-                    } else {
-                        self.loaded_chunks.insert(coord, ClientChunk::create(coord, mesh, self.state.as_ref().expect("666 demon evil client error").get_device()));
+                    let state = self.state.as_ref().expect("666 demon evil client error");
+                    match self.loaded_chunks.get_mut(&coord) {
+                        Some(chunk) => chunk.update_mesh(mesh, state.get_queue()),
+                        None => {
+                            self.loaded_chunks.insert(coord, ClientChunk::create(coord, mesh, state.get_device()));
+                        },
                     }
                 },
-                ServerPacket::BlockChange(block_change) => {
-                    println!("Got block change at {}x {}y in layer {:?} with blocktype {:?} and block_id {}", block_change.0.0, block_change.0.1, block_change.1.layer, block_change.1.block_type, block_change.1.block_id)
+                ServerPacket::ChunkLight(packet) => {
+                    let coord = IVec2::new(packet.0.0, packet.0.1);
+                    if let (Some(chunk), Some(state)) = (self.loaded_chunks.get_mut(&coord), self.state.as_ref()) {
+                        chunk.update_light(&packet.1, state.get_queue());
+                    }
+                },
+                ServerPacket::ChunkLiquid(packet) => {
+                    let coord = IVec2::new(packet.0.0, packet.0.1);
+                    if let (Some(chunk), Some(state)) = (self.loaded_chunks.get_mut(&coord), self.state.as_ref()) {
+                        chunk.update_liquid(&packet.1, state.get_queue());
+                    }
+                },
+                ServerPacket::BlockChange(((x, y), block_change)) => {
+                    // Changes in chunks that are not loaded arrive with the chunk later
+                    let world_pos = IVec2::new(x as i32, y as i32);
+                    if let (Some(chunk), Some(state)) = (self.loaded_chunks.get_mut(&world_to_chunk_pos_2d(world_pos)), self.state.as_ref()) {
+                        chunk.set_block(world_to_local_pos_2d(world_pos), &block_change, state.get_queue());
+                    }
+                },
+                ServerPacket::TileEntity((position, tile_entity)) => {
+                    match tile_entity {
+                        Some(tile_entity) => println!("Tile entity at {}x {}y: {:?}", position.0, position.1, tile_entity),
+                        None => println!("No tile entity at {}x {}y", position.0, position.1),
+                    }
                 },
                 ServerPacket::Message(message) => {
-                    println!("Got message {}!", message)
+                    println!("[Server] {}", message)
+                },
+                ServerPacket::Chat(chat) => {
+                    println!("<{}> {}", chat.sender_nickname, chat.message)
                 },
                 ServerPacket::ReloadChunks => {
                     self.loaded_chunks.clear();
                 },
-                ServerPacket::Ping => {
-                    // todo Don't know what to put here yet
-                }
+                ServerPacket::Ping(ping) => {
+                    self.send_packet(ClientPacket::Pong(ping));
+                },
+                ServerPacket::Pong(pong) => {
+                    self.latency.on_pong(&pong);
+                },
+                ServerPacket::Status(status) => {
+                    self.server_status = Some(status);
+                },
+                ServerPacket::Time(world_time) => {
+                    self.world_time = Some((world_time, Instant::now()));
+                },
+                ServerPacket::Weather(weather) => {
+                    self.weather = Some((weather, Instant::now()));
+                },
+                ServerPacket::Identity(token) => {
+                    self.identity.token = Some(token);
+                    self.identity.save();
+                },
+                ServerPacket::Disconnect(reason) => {
+                    println!("Disconnected from server: {}", reason);
+                    self.connected = false;
+                    self.loaded_chunks.clear();
+                },
             }
         }
     }
+
+    fn on_keepalive(&mut self) {
+        if self.connected && self.last_ping_sent.elapsed() >= PING_INTERVAL {
+            self.last_ping_sent = Instant::now();
+            let ping = self.latency.next_ping();
+            self.send_packet(ClientPacket::Ping(ping));
+        }
+    }
+
+    pub fn send_packet(&self, packet: ClientPacket) {
+        self.server_connection.send(packet, true);
+    }
     
     pub fn get_uuid(&self) -> u64 {
-        return self.player_uuid;
+        return self.identity.uuid;
     }
 
     pub fn get_uuid_string(&self) -> String {
-        let hex_string = format!("{:016x}", self.player_uuid);
+        let hex_string = format!("{:016x}", self.identity.uuid);
 
         let dashed_hex: String = hex_string
             .chars()
@@ -217,6 +333,35 @@ impl Client {
     pub fn get_zoom_factor(&self) -> f32 {
         self.zoom_factor
     }
+
+    // The clock keeps running between time packets, None until the first one arrives
+    pub fn get_world_time(&self) -> Option<WorldTime> {
+        let (world_time, received) = self.world_time?;
+        let ticks_since = (received.elapsed().as_secs_f64() * TICK_RATE as f64) as u64;
+        Some(WorldTime { time: world_time.time + ticks_since, ..world_time })
+    }
+
+    // Highest solid block of the column between the two heights, in loaded chunks only
+    pub fn get_surface_height(&self, x: i32, bottom: i32, top: i32) -> Option<i32> {
+        let local_x = world_to_local_pos(x) as usize;
+        let chunk_x = world_to_chunk_pos(x);
+
+        for chunk_y in (world_to_chunk_pos(bottom)..=world_to_chunk_pos(top)).rev() {
+            let Some(chunk) = self.loaded_chunks.get(&IVec2::new(chunk_x, chunk_y)) else {
+                continue;
+            };
+            if let Some(local_y) = chunk.get_highest_solid(local_x) {
+                return Some(chunk_y * CHUNK_SIZE as i32 + local_y as i32);
+            }
+        }
+        None
+    }
+
+    // The fronts keep drifting until the next update arrives
+    pub fn get_weather(&self) -> Option<WeatherState> {
+        let (weather, received) = self.weather.as_ref()?;
+        Some(weather.advanced(received.elapsed().as_secs_f32() * TICK_RATE as f32))
+    }
 }
 
 pub struct ClientConfig {
@@ -1,6 +1,6 @@
 use glam::IVec2;
 
-use crate::engine::{common::{Block, ChunkMesh}, server::constants::CHUNK_SIZE};
+use crate::engine::{common::{Block, BlockChange, ChunkMesh, PacketChunkLight, PacketChunkLiquid}, server::{common::BlockType, constants::CHUNK_SIZE}};
 use wgpu::{util::DeviceExt, RenderPass};
 
 pub struct ClientChunk {
@@ -15,7 +15,7 @@ impl ClientChunk {
             &wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{}x {}y Chunk Buffer", position.x, position.y)),
                 contents: bytemuck::bytes_of(&mesh),
-                usage: wgpu::BufferUsages::VERTEX,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             }
         );
 
@@ -26,6 +26,38 @@ impl ClientChunk {
         }
     }
 
+    // Used when the server sends a chunk that is already loaded
+    pub fn update_mesh(&mut self, mesh: ChunkMesh, queue: &wgpu::Queue) {
+        self.mesh = mesh;
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.mesh));
+    }
+
+    // Only the changed block is written to the buffer
+    pub fn set_block(&mut self, local_pos: IVec2, change: &BlockChange, queue: &wgpu::Queue) {
+        let index = (local_pos.y * CHUNK_SIZE as i32 + local_pos.x) as usize;
+        let offset = self.mesh.set_block(index, change);
+        let block = &bytemuck::bytes_of(&self.mesh)[offset..offset + std::mem::size_of::<Block>()];
+        queue.write_buffer(&self.buffer, offset as wgpu::BufferAddress, block);
+    }
+
+    pub fn update_light(&mut self, light: &PacketChunkLight, queue: &wgpu::Queue) {
+        self.mesh.set_light(light);
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.mesh));
+    }
+
+    pub fn update_liquid(&mut self, liquid: &PacketChunkLiquid, queue: &wgpu::Queue) {
+        self.mesh.set_liquid_levels(liquid);
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.mesh));
+    }
+
+    // Local height of the highest foreground block that stops rain, liquids included
+    pub fn get_highest_solid(&self, local_x: usize) -> Option<usize> {
+        (0..CHUNK_SIZE as usize).rev().find(|y| {
+            let block_type = self.mesh.foreground[y * CHUNK_SIZE as usize + local_x].block_type;
+            block_type == BlockType::Tile as u8 || block_type == BlockType::TileEntity as u8
+        })
+    }
+
     pub fn prepare_for_draw(&self, render_pass: &mut RenderPass) {
         let pos: [i32; 2] = [self.position.x * CHUNK_SIZE as i32, self.position.y * CHUNK_SIZE as i32];
         render_pass.set_push_constants(
@@ -36,8 +68,8 @@ impl ClientChunk {
     }
 
     pub fn get_desc() -> wgpu::VertexBufferLayout<'static> {
-        // 0 = position, 1 = blockid, 2 = blocktype, 3 = textureindex
-        const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Uint32, 1 => Uint8x2, 2 => Uint8, 3 => Uint8];
+        // 0 = blockid, 1 = position, 2 = blocktype, 3 = textureindex, 4 = sky and block light, 5 = liquid level
+        const ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![0 => Uint32, 1 => Uint8x2, 2 => Uint8, 3 => Uint8, 4 => Uint8x2, 5 => Uint8];
 
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Block>() as wgpu::BufferAddress,
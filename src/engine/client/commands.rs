@@ -1,4 +1,4 @@
-use crate::engine::command_registry::{error_not_enough_arguments, error_wrong_type, CommandDependency, CommandEnvironment, DebugCommand};
+use crate::engine::{command_args::{ArgSpec, ArgType}, common::ClientPacket, command_registry::{error_wrong_environment, CommandDependency, CommandEnvironment, CommandResult, DebugCommand, PermissionLevel}};
 
 pub fn create_client_commands() -> Vec<DebugCommand> {
     let mut commands = Vec::new();
@@ -7,10 +7,16 @@ pub fn create_client_commands() -> Vec<DebugCommand> {
         name: "framerate",
         aliases: &["fps"],
         description: "Prints the average framerate. Sampled since program start, or last resetfps execution.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Client(client) = dependency {
-                println!("Average FPS: {:.2}", client.time.average_fps());
-            }
+        args: &[],
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, _args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let fps = client.time.average_fps();
+            CommandResult::success_with(format!("Average FPS: {:.2}", fps))
+                .with_data(serde_json::json!({ "fps": fps }))
         },
         command_environment: CommandEnvironment::Client,
     });
@@ -19,19 +25,16 @@ pub fn create_client_commands() -> Vec<DebugCommand> {
         name: "setfps",
         aliases: &["setfps", "setframerate"],
         description: "Sets the target framerate.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Client(client) = dependency {
-                if let Some(arg) = _args.first() {
-                    if let Ok(new_cap) = arg.parse::<u32>() {
-                        client.client_config.frame_cap = new_cap;
-                        println!("Frame cap set to {new_cap}");
-                    } else {
-                        error_wrong_type();
-                    }
-                } else {
-                    error_not_enough_arguments();
-                }
-            }
+        args: const { &[ArgSpec::required("fps", ArgType::Unsigned)] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let new_cap = args.get_u32("fps").unwrap_or_default();
+            client.client_config.frame_cap = new_cap;
+            CommandResult::success_with(format!("Frame cap set to {new_cap}"))
         },
         command_environment: CommandEnvironment::Client,
     });
@@ -40,11 +43,15 @@ pub fn create_client_commands() -> Vec<DebugCommand> {
         name: "resetfps",
         aliases: &["rfps"],
         description: "Resets the average framerate sample frames.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Client(client) = dependency {
-                client.time.reset_average_fps();
-                println!("Reset average framerate sample frames")
-            }
+        args: &[],
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, _args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            client.time.reset_average_fps();
+            CommandResult::success_with("Reset average framerate sample frames")
         },
         command_environment: CommandEnvironment::Client,
     });
@@ -52,16 +59,106 @@ pub fn create_client_commands() -> Vec<DebugCommand> {
     commands.push(DebugCommand {
         name: "togglevsync",
         aliases: &["tvsync"],
-        description: "Toggles VSync.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Client(client) = dependency {
-                client.client_config.vsync = !client.client_config.vsync;
-                let bool = client.client_config.vsync;
-                println!("VSync toggled to {bool}");
+        description: "Toggles VSync, or sets it if a state is given.",
+        args: const { &[ArgSpec::optional("enabled", ArgType::Boolean)] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            client.client_config.vsync = args.get_bool("enabled").unwrap_or(!client.client_config.vsync);
+            let bool = client.client_config.vsync;
+            CommandResult::success_with(format!("VSync toggled to {bool}"))
+        },
+        command_environment: CommandEnvironment::Client,
+    });
+
+    commands.push(DebugCommand {
+        name: "say",
+        aliases: &["chat"],
+        description: "Sends a chat message. Messages starting with / run as commands on the server.",
+        args: const { &[ArgSpec::required("message", ArgType::Text)] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            client.send_chat(args.get_str("message").unwrap_or_default().to_string());
+            CommandResult::success()
+        },
+        command_environment: CommandEnvironment::Client,
+    });
+
+    commands.push(DebugCommand {
+        name: "inspect",
+        aliases: &[],
+        description: "Asks the server for the tile entity at a position, it is printed once the answer arrives.",
+        args: const { &[
+            ArgSpec::required("x", ArgType::Integer),
+            ArgSpec::required("y", ArgType::Integer),
+        ] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let position = (args.get_i32("x").unwrap_or_default() as i64, args.get_i32("y").unwrap_or_default() as i64);
+            client.send_packet(ClientPacket::RequestTileEntity(position));
+            CommandResult::success()
+        },
+        command_environment: CommandEnvironment::Client,
+    });
+
+    commands.push(DebugCommand {
+        name: "forecast",
+        aliases: &[],
+        description: "Prints the weather fronts the server last sent, and what falls on a column if one is given.",
+        args: const { &[ArgSpec::optional("x", ArgType::Integer)] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(weather) = client.get_weather() else {
+                return CommandResult::failure("The server has not sent any weather");
+            };
+
+            for front in &weather.fronts {
+                output.write_line(&format!("Front {:.0} blocks wide at {:.0}x, moving {:.2} blocks per tick at {:.0}% intensity",
+                    front.width, front.x, front.velocity, front.intensity * 100.0));
             }
+
+            let Some(x) = args.get_i32("x") else {
+                return CommandResult::success_with(format!("{} front(s)", weather.fronts.len()));
+            };
+
+            match weather.get_precipitation(x) {
+                Some((precipitation, intensity)) => CommandResult::success_with(format!("{precipitation:?} at {x}x, {:.0}% intensity", intensity * 100.0)),
+                None => CommandResult::success_with(format!("Dry at {x}x")),
+            }
+        },
+        command_environment: CommandEnvironment::Client,
+    });
+
+    commands.push(DebugCommand {
+        name: "latency",
+        aliases: &["ping", "rtt"],
+        description: "Prints the round trip time and jitter to the server.",
+        args: &[],
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, _args, _output| {
+            let CommandDependency::Client(client) = dependency else {
+                return error_wrong_environment();
+            };
+
+            CommandResult::success_with(format!("Latency: {}", client.latency.describe()))
         },
         command_environment: CommandEnvironment::Client,
     });
 
     return commands;
-}
\ No newline at end of file
+}
@@ -1 +1,21 @@
-pub const ZOOM_SPEED: f32 = 0.1;
\ No newline at end of file
+use std::time::Duration;
+
+pub const WINDOW_TITLE: &str = "swagrarria";
+pub const ZOOM_SPEED: f32 = 0.1;
+
+// Sky colours the clear colour blends between as the sun moves, in linear RGB
+pub const DAY_SKY_COLOR: [f64; 3] = [0.6, 0.7, 0.94];
+pub const DUSK_SKY_COLOR: [f64; 3] = [0.85, 0.45, 0.3];
+pub const NIGHT_SKY_COLOR: [f64; 3] = [0.02, 0.03, 0.08];
+// How high the sun has to be for the sky to be fully day or night coloured, from -1 to 1
+pub const DUSK_SUN_HEIGHT: f32 = 0.3;
+
+// Most columns of rain or snow drawn in one frame
+pub const WEATHER_MAX_COLUMNS: usize = 4096;
+// Seconds after which the falling drops start over, keeps the shader time precise
+pub const WEATHER_ANIMATION_PERIOD: f32 = 3600.0;
+
+// How often the client measures its latency to the server
+pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+// Keeps the player UUID and the token the server handed out for it between runs
+pub const CLIENT_IDENTITY_FILE: &str = "client_identity.json";
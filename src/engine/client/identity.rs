@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::client::constants::CLIENT_IDENTITY_FILE;
+
+// The UUID this client joins with, and the token the server handed out for it
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ClientIdentity {
+    pub uuid: u64,
+    pub token: Option<u64>,
+}
+
+impl ClientIdentity {
+    // Picks a new UUID if there is no file yet or it cannot be read
+    pub fn load() -> ClientIdentity {
+        let loaded = std::fs::read_to_string(CLIENT_IDENTITY_FILE).ok()
+            .and_then(|contents| serde_json::from_str::<ClientIdentity>(&contents).ok());
+
+        match loaded {
+            Some(identity) => identity,
+            None => {
+                let identity = ClientIdentity { uuid: fastrand::u64(..), token: None };
+                identity.save();
+                identity
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self).map_err(std::io::Error::other)
+            .and_then(|contents| std::fs::write(CLIENT_IDENTITY_FILE, contents));
+        if let Err(error) = result {
+            println!("Failed to save {CLIENT_IDENTITY_FILE}: {error}");
+        }
+    }
+}
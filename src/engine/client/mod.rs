@@ -2,4 +2,7 @@ pub mod commands;
 pub mod client;
 pub mod state;
 pub mod client_chunk;
+pub mod chat;
+pub mod identity;
+pub mod weather_overlay;
 pub mod constants;
\ No newline at end of file
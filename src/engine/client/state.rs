@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use winit::window::Window;
 
-use crate::engine::{client::{client::Client, client_chunk::ClientChunk}, server::constants::CHUNK_BLOCK_COUNT};
+use crate::engine::{client::{client::Client, client_chunk::ClientChunk, weather_overlay::WeatherOverlay, constants::{DAY_SKY_COLOR, DUSK_SKY_COLOR, DUSK_SUN_HEIGHT, NIGHT_SKY_COLOR}}, common::WorldTime, server::constants::CHUNK_BLOCK_COUNT};
 
 pub struct State {
     surface: wgpu::Surface<'static>,
@@ -10,6 +10,7 @@ pub struct State {
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     render_pipeline: wgpu::RenderPipeline,
+    weather_overlay: WeatherOverlay,
     size: winit::dpi::PhysicalSize<u32>,
     surface_format: wgpu::TextureFormat,
     window: Arc<Window>,
@@ -38,7 +39,7 @@ impl State {
                 label: None,
                 required_features: wgpu::Features::PUSH_CONSTANTS,
                 required_limits: wgpu::Limits {
-                    max_push_constant_size: 20,
+                    max_push_constant_size: 24,
                     ..Default::default()
                 },
                 memory_hints: Default::default(),
@@ -76,7 +77,7 @@ impl State {
                 push_constant_ranges: &[
                     wgpu::PushConstantRange {
                         stages: wgpu::ShaderStages::VERTEX,
-                        range: 0..20,
+                        range: 0..24,
                     }
                 ],
             });
@@ -119,6 +120,8 @@ impl State {
             cache: None,
         });
 
+        let weather_overlay = WeatherOverlay::new(&device, config.format);
+
         surface.configure(&device, &config);
 
         let state = State {
@@ -127,6 +130,7 @@ impl State {
             queue,
             config,
             render_pipeline,
+            weather_overlay,
             size,
             surface,
             surface_format,
@@ -157,6 +161,10 @@ impl State {
         });
 
         let mut encoder = self.device.create_command_encoder(&Default::default());
+        let world_time = client.get_world_time();
+        let window_size = self.get_window().inner_size();
+        let window_size = [window_size.width as f32, window_size.height as f32];
+        let daylight = world_time.map_or(1.0, |world_time| world_time.get_daylight());
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -166,7 +174,7 @@ impl State {
                     resolve_target: None,
                     depth_slice: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color{ r: 0.6, g: 0.7, b: 0.94, a: 1.0 }),
+                        load: wgpu::LoadOp::Clear(sky_color(world_time)),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -176,8 +184,6 @@ impl State {
             });
 
             render_pass.set_pipeline(&self.render_pipeline);
-            let window_size = self.get_window().inner_size();
-            let window_size = [window_size.width as f32, window_size.height as f32];
             // Push window size
             render_pass.set_push_constants(
             wgpu::ShaderStages::VERTEX,
@@ -189,12 +195,20 @@ impl State {
             wgpu::ShaderStages::VERTEX,
             16,
             bytemuck::bytes_of(&client.get_zoom_factor()));
+
+            // Push daylight, scales the sky light of every block
+            render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX,
+            20,
+            bytemuck::bytes_of(&daylight));
             for chunk in client.get_chunks() {
                 chunk.prepare_for_draw(&mut render_pass);
                 render_pass.draw(0..6, 0..(CHUNK_BLOCK_COUNT as u32 * 3));
             }
         }
 
+        self.weather_overlay.draw(&mut encoder, &texture_view, &self.queue, client, window_size, daylight);
+
         self.queue.submit([encoder.finish()]);
         self.window.pre_present_notify();
         surface_texture.present();
@@ -211,4 +225,22 @@ impl State {
     pub fn get_queue(&self) -> &wgpu::Queue {
         &self.queue
     }
+}
+
+// Day colour until the server sent the time, then a blend through the dusk
+// colour whenever the sun is close to the horizon
+fn sky_color(world_time: Option<WorldTime>) -> wgpu::Color {
+    let Some(world_time) = world_time else {
+        return to_color(DAY_SKY_COLOR);
+    };
+
+    let sun_height = world_time.get_sun_height();
+    let (target, amount) = if sun_height >= 0.0 { (DAY_SKY_COLOR, sun_height) } else { (NIGHT_SKY_COLOR, -sun_height) };
+    let amount = (amount / DUSK_SUN_HEIGHT).min(1.0) as f64;
+
+    to_color(std::array::from_fn(|i| DUSK_SKY_COLOR[i] + (target[i] - DUSK_SKY_COLOR[i]) * amount))
+}
+
+fn to_color(rgb: [f64; 3]) -> wgpu::Color {
+    wgpu::Color { r: rgb[0], g: rgb[1], b: rgb[2], a: 1.0 }
 }
\ No newline at end of file
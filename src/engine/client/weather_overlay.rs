@@ -0,0 +1,177 @@
+use std::time::Instant;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+
+use crate::engine::client::{client::Client, constants::{WEATHER_ANIMATION_PERIOD, WEATHER_MAX_COLUMNS}};
+
+/*
+/   Rain and snow are drawn over the world in a pass of their own. Every
+/   column on screen where something falls becomes one quad, reaching from
+/   the highest solid block of the column to the top of the screen, and the
+/   shader scatters the drops inside it. Columns whose ground lies in a
+/   chunk that is not loaded yet get weather all the way down.
+*/
+pub struct WeatherOverlay {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    started: Instant,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Zeroable, Pod)]
+struct WeatherColumn {
+    x: i32,
+    // In blocks
+    bottom: f32,
+    top: f32,
+    // 0 for rain, 1 for snow
+    precipitation: u32,
+    intensity: f32,
+}
+
+// Has to match the PushConstants in weather.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct WeatherPushConstants {
+    window_size: [f32; 2],
+    zoom_factor: f32,
+    time: f32,
+    daylight: f32,
+}
+
+impl WeatherOverlay {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> WeatherOverlay {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/weather.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Weather Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[
+                wgpu::PushConstantRange {
+                    stages: wgpu::ShaderStages::VERTEX,
+                    range: 0..std::mem::size_of::<WeatherPushConstants>() as u32,
+                }
+            ],
+        });
+
+        // 0 = column, 1 = bottom, 2 = top, 3 = precipitation, 4 = intensity
+        const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![0 => Sint32, 1 => Float32, 2 => Float32, 3 => Uint32, 4 => Float32];
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Weather Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<WeatherColumn>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &ATTRIBS,
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Weather Buffer"),
+            size: (WEATHER_MAX_COLUMNS * std::mem::size_of::<WeatherColumn>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        WeatherOverlay {
+            pipeline,
+            buffer,
+            started: Instant::now(),
+        }
+    }
+
+    // Draws on top of whatever is in the view already
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, queue: &wgpu::Queue, client: &Client, window_size: [f32; 2], daylight: f32) {
+        let columns = visible_columns(client, window_size);
+        if columns.is_empty() {
+            return;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&columns));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Weather Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        let push_constants = WeatherPushConstants {
+            window_size,
+            zoom_factor: client.get_zoom_factor(),
+            time: self.started.elapsed().as_secs_f32() % WEATHER_ANIMATION_PERIOD,
+            daylight,
+        };
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::bytes_of(&push_constants));
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..6, 0..columns.len() as u32);
+    }
+}
+
+// The screen is centred on the world origin, see shader.wgsl
+fn visible_columns(client: &Client, window_size: [f32; 2]) -> Vec<WeatherColumn> {
+    let Some(weather) = client.get_weather() else {
+        return Vec::new();
+    };
+
+    let tile_pixel_size = 16.0;
+    let half_size = Vec2::from(window_size) / (tile_pixel_size * client.get_zoom_factor());
+    let (screen_bottom, screen_top) = (-half_size.y, half_size.y);
+
+    let mut columns = Vec::new();
+    for x in (-half_size.x).floor() as i32..=half_size.x.ceil() as i32 {
+        let Some((precipitation, intensity)) = weather.get_precipitation(x) else {
+            continue;
+        };
+
+        let bottom = match client.get_surface_height(x, screen_bottom.floor() as i32, screen_top.ceil() as i32) {
+            Some(y) => y as f32 + 1.0,
+            None => screen_bottom,
+        };
+        if bottom >= screen_top {
+            continue;
+        }
+
+        columns.push(WeatherColumn { x, bottom, top: screen_top, precipitation: precipitation as u32, intensity });
+        if columns.len() == WEATHER_MAX_COLUMNS {
+            break;
+        }
+    }
+    columns
+}
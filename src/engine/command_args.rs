@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+
+use crate::{engine::{command_registry::{CommandResult, CommandSource, DebugCommand}, server::common::{BlockPattern, BlockType}}, get_global_command_registry};
+
+#[derive(Hash, Clone, Copy, PartialEq, Debug)]
+pub enum ArgType {
+    Integer,
+    Unsigned,
+    Boolean,
+    // A single token, case is preserved
+    Word,
+    // Everything up to the end of the line, only valid as the last argument
+    Text,
+    // Checked against the loaded dimensions when the command runs on the server
+    Dimension,
+    // Name or alias of a registered command
+    Command,
+    Block,
+    // One of a fixed set of lowercase words
+    Choice(&'static [&'static str]),
+}
+
+impl ArgType {
+    pub fn describe(&self) -> String {
+        match self {
+            ArgType::Integer => "integer".to_string(),
+            ArgType::Unsigned => "positive integer".to_string(),
+            ArgType::Boolean => "true or false".to_string(),
+            ArgType::Word => "word".to_string(),
+            ArgType::Text => "text".to_string(),
+            ArgType::Dimension => "dimension name".to_string(),
+            ArgType::Command => "command name".to_string(),
+            ArgType::Block => format!("block ({}), optionally with :id", BlockType::NAMES.join(", ")),
+            ArgType::Choice(options) => format!("one of {}", options.join(", ")),
+        }
+    }
+
+    // Values offered by tab completion, empty where anything goes
+    pub fn completions(&self, dimension_names: &[String]) -> Vec<String> {
+        match self {
+            ArgType::Boolean => vec!["true".to_string(), "false".to_string()],
+            ArgType::Dimension => dimension_names.to_vec(),
+            ArgType::Command => {
+                let mut names: Vec<String> = get_global_command_registry().keys().map(|name| name.to_string()).collect();
+                names.sort();
+                names
+            },
+            ArgType::Block => BlockType::NAMES.iter().map(|name| name.to_string()).collect(),
+            ArgType::Choice(options) => options.iter().map(|option| option.to_string()).collect(),
+            ArgType::Integer | ArgType::Unsigned | ArgType::Word | ArgType::Text => Vec::new(),
+        }
+    }
+}
+
+#[derive(Hash, Clone, Copy, Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub arg_type: ArgType,
+    pub required: bool,
+    // Parsed like user input when the argument is left out
+    pub default: Option<&'static str>,
+}
+
+impl ArgSpec {
+    pub const fn required(name: &'static str, arg_type: ArgType) -> ArgSpec {
+        ArgSpec { name, arg_type, required: true, default: None }
+    }
+
+    pub const fn optional(name: &'static str, arg_type: ArgType) -> ArgSpec {
+        ArgSpec { name, arg_type, required: false, default: None }
+    }
+
+    pub const fn with_default(name: &'static str, arg_type: ArgType, default: &'static str) -> ArgSpec {
+        ArgSpec { name, arg_type, required: false, default: Some(default) }
+    }
+
+    pub fn usage(&self) -> String {
+        match (self.required, self.default) {
+            (true, _) => format!("<{}>", self.name),
+            (false, Some(default)) => format!("[{}={}]", self.name, default),
+            (false, None) => format!("[{}]", self.name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgValue {
+    Integer(i32),
+    Unsigned(u32),
+    Boolean(bool),
+    Text(String),
+    Block(BlockPattern),
+}
+
+// The arguments of one command invocation, looked up by the names in its ArgSpecs
+pub struct CommandArgs {
+    values: HashMap<&'static str, ArgValue>,
+    source: CommandSource,
+}
+
+impl CommandArgs {
+    // Who ran the command
+    pub fn get_source(&self) -> CommandSource {
+        self.source
+    }
+
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        match self.values.get(name)? {
+            ArgValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_u32(&self, name: &str) -> Option<u32> {
+        match self.values.get(name)? {
+            ArgValue::Unsigned(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.values.get(name)? {
+            ArgValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.values.get(name)? {
+            ArgValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_block(&self, name: &str) -> Option<BlockPattern> {
+        match self.values.get(name)? {
+            ArgValue::Block(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+pub fn usage(command: &DebugCommand) -> String {
+    let mut usage = command.name.to_string();
+    for arg in command.args {
+        usage.push(' ');
+        usage.push_str(&arg.usage());
+    }
+    usage
+}
+
+// Splits a line on whitespace. Double quotes group words together and
+// a backslash escapes the next character.
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut in_quotes = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let Some(escaped) = chars.next() else {
+                    return Err("Line ends with an unfinished escape".to_string());
+                };
+                current.push(escaped);
+                in_token = true;
+            },
+            '"' => {
+                in_quotes = !in_quotes;
+                in_token = true;
+            },
+            c if c.is_whitespace() && !in_quotes => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            },
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_quotes {
+        return Err("Line has an unclosed quote".to_string());
+    }
+
+    if in_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+// Dimension names are only known on the server, pass None to skip checking them
+pub fn parse_args(command: &DebugCommand, tokens: &[String], dimension_names: Option<&[String]>, source: CommandSource) -> Result<CommandArgs, CommandResult> {
+    let mut values = HashMap::new();
+    let mut token_index = 0;
+
+    for spec in command.args {
+        let input: Option<String> = if spec.arg_type == ArgType::Text {
+            let rest = &tokens[token_index.min(tokens.len())..];
+            token_index = tokens.len();
+            if rest.is_empty() { None } else { Some(rest.join(" ")) }
+        } else {
+            let token = tokens.get(token_index).cloned();
+            token_index += 1;
+            token
+        };
+
+        let input = match (input, spec.default) {
+            (Some(input), _) => input,
+            (None, Some(default)) => default.to_string(),
+            (None, None) if spec.required => {
+                return Err(CommandResult::failure(format!("Missing argument <{}> ({}). Usage: {}", spec.name, spec.arg_type.describe(), usage(command))));
+            },
+            (None, None) => continue,
+        };
+
+        let value = parse_value(spec, &input, dimension_names)?;
+        values.insert(spec.name, value);
+    }
+
+    if token_index < tokens.len() {
+        return Err(CommandResult::failure(format!("Too many arguments, \"{}\" was not expected. Usage: {}", tokens[token_index], usage(command))));
+    }
+
+    Ok(CommandArgs { values, source })
+}
+
+fn parse_value(spec: &ArgSpec, input: &str, dimension_names: Option<&[String]>) -> Result<ArgValue, CommandResult> {
+    let wrong_type = || CommandResult::failure(format!("Argument <{}> expects {}, got \"{}\"", spec.name, spec.arg_type.describe(), input));
+
+    match spec.arg_type {
+        ArgType::Integer => input.parse::<i32>().map(ArgValue::Integer).map_err(|_| wrong_type()),
+        ArgType::Unsigned => input.parse::<u32>().map(ArgValue::Unsigned).map_err(|_| wrong_type()),
+        ArgType::Boolean => match input.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(ArgValue::Boolean(true)),
+            "false" | "no" | "off" | "0" => Ok(ArgValue::Boolean(false)),
+            _ => Err(wrong_type()),
+        },
+        ArgType::Word | ArgType::Text => Ok(ArgValue::Text(input.to_string())),
+        ArgType::Dimension => match dimension_names {
+            Some(names) if !names.iter().any(|name| name == input) => {
+                Err(CommandResult::failure(format!("Unknown dimension \"{}\" for <{}>, expected one of: {}", input, spec.name, names.join(", "))))
+            },
+            _ => Ok(ArgValue::Text(input.to_string())),
+        },
+        ArgType::Command => {
+            let name = input.to_lowercase();
+            if get_global_command_registry().contains_key(name.as_str()) {
+                Ok(ArgValue::Text(name))
+            } else {
+                Err(CommandResult::failure(format!("Unknown command \"{}\" for <{}>. Type 'help' for a list.", input, spec.name)))
+            }
+        },
+        ArgType::Block => BlockPattern::parse(input).map(ArgValue::Block).ok_or_else(wrong_type),
+        ArgType::Choice(options) => {
+            let input = input.to_lowercase();
+            if options.contains(&input.as_str()) {
+                Ok(ArgValue::Text(input))
+            } else {
+                Err(wrong_type())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::command_registry::{CommandEnvironment, PermissionLevel};
+
+    use super::*;
+
+    const COMMAND: DebugCommand = DebugCommand {
+        name: "test",
+        aliases: &[],
+        description: "",
+        args: const { &[
+            ArgSpec::required("count", ArgType::Integer),
+            ArgSpec::with_default("mode", ArgType::Choice(&["fast", "slow"]), "slow"),
+            ArgSpec::optional("block", ArgType::Block),
+            ArgSpec::optional("rest", ArgType::Text),
+        ] },
+        permission_level: PermissionLevel::Player,
+        execute: |_dependency, _args, _output| CommandResult::success(),
+        command_environment: CommandEnvironment::Main,
+    };
+
+    fn parse(line: &str) -> Result<CommandArgs, CommandResult> {
+        parse_args(&COMMAND, &tokenize(line).unwrap(), None, CommandSource::console())
+    }
+
+    #[test]
+    fn tokenize_groups_quotes_and_escapes() {
+        assert_eq!(tokenize("  say \"hello  world\" a\\ b \\\"x").unwrap(), ["say", "hello  world", "a b", "\"x"]);
+        assert_eq!(tokenize("\"\"").unwrap(), [""]);
+        assert!(tokenize("say \"open").is_err());
+        assert!(tokenize("say \\").is_err());
+    }
+
+    #[test]
+    fn parse_args_fills_defaults_and_text() {
+        let args = parse("5 FAST tile:3 one two").unwrap();
+        assert_eq!(args.get_i32("count"), Some(5));
+        assert_eq!(args.get_str("mode"), Some("fast"));
+        assert_eq!(args.get_block("block"), Some(BlockPattern { block_type: BlockType::Tile, block_id: Some(3) }));
+        assert_eq!(args.get_str("rest"), Some("one two"));
+
+        let args = parse("-2").unwrap();
+        assert_eq!(args.get_i32("count"), Some(-2));
+        assert_eq!(args.get_str("mode"), Some("slow"));
+        assert_eq!(args.get_block("block"), None);
+        assert_eq!(args.get_str("rest"), None);
+    }
+
+    #[test]
+    fn parse_args_rejects_bad_input() {
+        assert!(parse("").is_err());
+        assert!(parse("five").is_err());
+        assert!(parse("5 medium").is_err());
+        assert!(parse("5 fast stone").is_err());
+    }
+}
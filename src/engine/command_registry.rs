@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use crate::engine::{client::{client::Client, commands::create_client_commands}, commands::create_main_commands, server::{commands::create_server_commands, server::Server}};
+use serde::{Deserialize, Serialize};
+use crate::{engine::{command_args::{parse_args, tokenize, ArgSpec, CommandArgs}, client::{client::Client, commands::create_client_commands}, commands::create_main_commands, server::{commands::create_server_commands, server::Server}}, get_global_command_registry};
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy)]
 pub enum CommandEnvironment {
@@ -8,6 +9,69 @@ pub enum CommandEnvironment {
     Main,
 }
 
+// Who is allowed to run a command. Each level may run everything the
+// levels below it can.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionLevel {
+    Player,
+    Moderator,
+    Operator,
+    // Only the terminal the process was started from
+    Console,
+}
+
+impl PermissionLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PermissionLevel::Player => "player",
+            PermissionLevel::Moderator => "moderator",
+            PermissionLevel::Operator => "operator",
+            PermissionLevel::Console => "console",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PermissionLevel> {
+        match name.to_lowercase().as_str() {
+            "player" => Some(PermissionLevel::Player),
+            "moderator" => Some(PermissionLevel::Moderator),
+            "operator" => Some(PermissionLevel::Operator),
+            "console" => Some(PermissionLevel::Console),
+            _ => None,
+        }
+    }
+}
+
+// Who a command runs on behalf of. Per player state such as the edit
+// history is keyed by the actor.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum CommandActor {
+    Console,
+    Remote,
+    Player(u64),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSource {
+    pub actor: CommandActor,
+    pub level: PermissionLevel,
+}
+
+impl CommandSource {
+    pub fn console() -> CommandSource {
+        CommandSource { actor: CommandActor::Console, level: PermissionLevel::Console }
+    }
+
+    // Knowing the password makes you an operator, the console level stays local
+    pub fn remote() -> CommandSource {
+        CommandSource { actor: CommandActor::Remote, level: PermissionLevel::Operator }
+    }
+
+    pub fn player(uuid: u64, level: PermissionLevel) -> CommandSource {
+        CommandSource { actor: CommandActor::Player(uuid), level }
+    }
+}
+
 pub enum CommandDependency<'a>{
     Client(&'a mut Client),
     Server(&'a mut Server),
@@ -19,7 +83,9 @@ pub struct DebugCommand {
     pub name: &'static str,
     pub aliases: &'static [&'static str],
     pub description: &'static str,
-    pub execute: fn(&mut CommandDependency, &Vec<String>),
+    pub args: &'static [ArgSpec],
+    pub permission_level: PermissionLevel,
+    pub execute: fn(&mut CommandDependency, &CommandArgs, &mut dyn OutputSink) -> CommandResult,
     pub command_environment: CommandEnvironment,
 }
 
@@ -29,6 +95,80 @@ pub struct DebugCommandWithArgs {
     pub command_args: Vec<String>,
 }
 
+// Where a command writes its output while it runs. The console prints
+// it right away, chat and remote callers collect it and send it on.
+pub trait OutputSink {
+    fn write_line(&mut self, line: &str);
+}
+
+pub struct ConsoleOutput;
+
+impl OutputSink for ConsoleOutput {
+    fn write_line(&mut self, line: &str) {
+        println!("{line}");
+    }
+}
+
+impl OutputSink for Vec<String> {
+    fn write_line(&mut self, line: &str) {
+        self.push(line.to_string());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandStatus {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    pub status: CommandStatus,
+    pub lines: Vec<String>,
+    pub data: Option<serde_json::Value>,
+}
+
+impl CommandResult {
+    pub fn success() -> CommandResult {
+        CommandResult {
+            status: CommandStatus::Success,
+            lines: Vec::new(),
+            data: None,
+        }
+    }
+
+    pub fn success_with(line: impl Into<String>) -> CommandResult {
+        CommandResult {
+            status: CommandStatus::Success,
+            lines: vec![line.into()],
+            data: None,
+        }
+    }
+
+    pub fn failure(line: impl Into<String>) -> CommandResult {
+        CommandResult {
+            status: CommandStatus::Failure,
+            lines: vec![line.into()],
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: serde_json::Value) -> CommandResult {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == CommandStatus::Success
+    }
+
+    pub fn write_to(&self, output: &mut dyn OutputSink) {
+        for line in &self.lines {
+            output.write_line(line);
+        }
+    }
+}
+
 pub struct CommandRegistry {
     pub global_registry: HashMap<&'static str, DebugCommand>,
 }
@@ -55,6 +195,8 @@ pub fn build_registry<'a>(environment: CommandEnvironment) -> HashMap<&'static s
                 name: cmd.name,
                 aliases: cmd.aliases,
                 description: cmd.description,
+                args: cmd.args,
+                permission_level: cmd.permission_level,
                 execute: cmd.execute,
                 command_environment: cmd.command_environment,
             });
@@ -65,33 +207,115 @@ pub fn build_registry<'a>(environment: CommandEnvironment) -> HashMap<&'static s
     return mapped;
 }
 
-pub fn handle_client_command(client: &mut Client, command: &DebugCommandWithArgs) {
+// Splits a line into a command and its raw arguments. Only the command
+// name is case insensitive, arguments are checked when the command runs
+pub fn parse_command_line(line: &str) -> Result<DebugCommandWithArgs, String> {
+    let mut tokens = tokenize(line)?;
+    if tokens.is_empty() {
+        return Err("Empty command.".to_string());
+    }
+
+    let cmd_name = tokens.remove(0).to_lowercase();
+    let Some(command) = get_global_command_registry().get(cmd_name.as_str()).copied() else {
+        return Err(format!("Unknown command \"{cmd_name}\". Type 'help' for a list."));
+    };
+
+    Ok(DebugCommandWithArgs {
+        debug_command: command,
+        command_args: tokens,
+    })
+}
+
+pub fn handle_client_command(client: &mut Client, command: &DebugCommandWithArgs, source: CommandSource, output: &mut dyn OutputSink) -> CommandResult {
     let cmd: DebugCommand = command.debug_command;
-    (cmd.execute)(&mut CommandDependency::Client(client), &command.command_args);
+    if let Err(error) = check_permission(&cmd, source.level) {
+        return error;
+    }
+    let args = match parse_args(&cmd, &command.command_args, None, source) {
+        Ok(args) => args,
+        Err(error) => return error,
+    };
+    (cmd.execute)(&mut CommandDependency::Client(client), &args, output)
 }
 
-pub fn handle_server_command(server: &mut Server, command: &DebugCommandWithArgs) {
+pub fn handle_server_command(server: &mut Server, command: &DebugCommandWithArgs, source: CommandSource, output: &mut dyn OutputSink) -> CommandResult {
     let cmd: DebugCommand = command.debug_command;
-    (cmd.execute)(&mut CommandDependency::Server(server), &command.command_args);
+    if let Err(error) = check_permission(&cmd, source.level) {
+        return error;
+    }
+    let dimension_names: Vec<String> = server.get_dimension_keys().cloned().collect();
+    let args = match parse_args(&cmd, &command.command_args, Some(&dimension_names), source) {
+        Ok(args) => args,
+        Err(error) => return error,
+    };
+    (cmd.execute)(&mut CommandDependency::Server(server), &args, output)
 }
 
-pub fn handle_main_command(command: &DebugCommandWithArgs) {
+pub fn handle_main_command(command: &DebugCommandWithArgs, source: CommandSource, output: &mut dyn OutputSink) -> CommandResult {
     let cmd: DebugCommand = command.debug_command;
-    (cmd.execute)(&mut CommandDependency::Main, &command.command_args);
+    if let Err(error) = check_permission(&cmd, source.level) {
+        return error;
+    }
+    let args = match parse_args(&cmd, &command.command_args, None, source) {
+        Ok(args) => args,
+        Err(error) => return error,
+    };
+    (cmd.execute)(&mut CommandDependency::Main, &args, output)
 }
 
-pub fn error_wrong_type() {
-    println!("Failed to execute command - your input was the wrong type.")
+// Checked before the arguments are parsed, so callers without access
+// learn nothing about how the command is used
+pub fn check_permission(command: &DebugCommand, level: PermissionLevel) -> Result<(), CommandResult> {
+    if level >= command.permission_level {
+        return Ok(());
+    }
+
+    Err(CommandResult::failure(format!("You do not have permission to run \"{}\", it requires {} level.", command.name, command.permission_level.name())))
 }
 
-pub fn error_not_enough_arguments() {
-    println!("Failed to execute command - not enough arguments.")
+// Runs a command with a collecting sink and folds everything it wrote
+// into the result, in the order it was written
+pub fn capture_output(execute: impl FnOnce(&mut dyn OutputSink) -> CommandResult) -> CommandResult {
+    let mut lines: Vec<String> = Vec::new();
+    let mut result = execute(&mut lines);
+    lines.append(&mut result.lines);
+    result.lines = lines;
+    result
 }
 
-pub fn error_command_not_found() {
-    println!("Failed to execute command - the command you were looking for could not be found.")
+pub fn error_player_not_found(name: &str) -> CommandResult {
+    CommandResult::failure(format!("Failed to execute command - no player found under the name or UUID \"{name}\"."))
 }
 
-pub fn error_dimension_not_found() {
-    println!("Failed to execute command - the dimension you were looking for could not be found.")
-}
\ No newline at end of file
+pub fn error_dimension_not_found() -> CommandResult {
+    CommandResult::failure("Failed to execute command - the dimension you were looking for could not be found.")
+}
+
+// Commands only receive the dependency matching their environment
+pub fn error_wrong_environment() -> CommandResult {
+    CommandResult::failure("Failed to execute command - it was dispatched to the wrong environment.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn killprocess_is_refused_before_it_runs() {
+        let command = parse_command_line("killprocess").unwrap();
+        let result = capture_output(|output| handle_main_command(&command, CommandSource::player(1, PermissionLevel::Operator), output));
+        assert!(!result.is_success());
+        assert!(result.lines[0].contains("requires console level"));
+    }
+
+    #[test]
+    fn captured_output_comes_before_the_result_lines() {
+        let result = capture_output(|output| {
+            output.write_line("written while running");
+            CommandResult::success_with("done").with_data(serde_json::json!(3))
+        });
+        assert!(result.is_success());
+        assert_eq!(result.lines, vec!["written while running", "done"]);
+        assert_eq!(result.data, Some(serde_json::json!(3)));
+    }
+}
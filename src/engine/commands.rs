@@ -1,6 +1,6 @@
 use sysinfo::System;
 
-use crate::{engine::command_registry::{error_command_not_found, error_not_enough_arguments, CommandDependency, CommandEnvironment, DebugCommand}, get_global_command_registry};
+use crate::{engine::{command_args::{usage, ArgSpec, ArgType}, command_registry::{CommandEnvironment, CommandResult, DebugCommand, OutputSink, PermissionLevel}}, get_global_command_registry};
 
 pub fn create_main_commands() -> Vec<DebugCommand> {
     let mut commands = Vec::new();
@@ -8,11 +8,15 @@ pub fn create_main_commands() -> Vec<DebugCommand> {
     commands.push(DebugCommand {
         name: "help",
         aliases: &["h", "m"],
-        description: "Prints the help menu.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Main = dependency {
-                print_debug_menu();
+        description: "Prints the help menu, or the usage of a single command.",
+        args: const { &[ArgSpec::optional("command", ArgType::Command)] },
+        permission_level: PermissionLevel::Player,
+        execute: |_dependency, args, output| {
+            match args.get_str("command").and_then(|name| get_global_command_registry().get(name)) {
+                Some(command) => print_command_help(command, output),
+                None => print_debug_menu(output),
             }
+            CommandResult::success()
         },
         command_environment: CommandEnvironment::Main,
     });
@@ -21,8 +25,10 @@ pub fn create_main_commands() -> Vec<DebugCommand> {
         name: "memory",
         aliases: &["mem"],
         description: "Prints program memory usage.",
-        execute: |_dependency, _args| {
-            print_memory_usage();
+        args: &[],
+        permission_level: PermissionLevel::Moderator,
+        execute: |_dependency, _args, output| {
+            print_memory_usage(output)
         },
         command_environment: CommandEnvironment::Main,
     });
@@ -31,18 +37,11 @@ pub fn create_main_commands() -> Vec<DebugCommand> {
         name: "alias",
         aliases: &["a"],
         description: ("Prints the aliases of a given command."),
-        execute: |dependency, _args| {
-            if let CommandDependency::Main = dependency {
-                if let Some(arg) = _args.first() {
-                    if get_global_command_registry().contains_key(arg.as_str()) {
-                        print_aliases(arg);
-                    } else {
-                        error_command_not_found();
-                    }
-                } else {
-                    error_not_enough_arguments();
-                }
-            }
+        args: const { &[ArgSpec::required("command", ArgType::Command)] },
+        permission_level: PermissionLevel::Player,
+        execute: |_dependency, args, output| {
+            print_aliases(args.get_str("command").unwrap_or_default(), output);
+            CommandResult::success()
         },
         command_environment: CommandEnvironment::Main,
     });
@@ -50,8 +49,10 @@ pub fn create_main_commands() -> Vec<DebugCommand> {
     commands.push(DebugCommand {
         name: "killprocess",
         aliases: &["kill"],
-        description: "Kills the process. Warning - just close the window normally if you can.",
-        execute: |_client, _args| {
+        description: "Kills the process without saving, changes since the last autosave are lost. Close the window or stop the server normally if you can.",
+        args: &[],
+        permission_level: PermissionLevel::Console,
+        execute: |_client, _args, _output| {
             std::process::exit(0);
         },
         command_environment: CommandEnvironment::Main,
@@ -60,17 +61,30 @@ pub fn create_main_commands() -> Vec<DebugCommand> {
     return commands;
 }
 
-fn print_debug_menu() {
-    println!("Available commands:");
+fn print_debug_menu(output: &mut dyn OutputSink) {
+    output.write_line("Available commands:");
     let mut seen = std::collections::HashSet::new();
     for (_, cmd) in get_global_command_registry() {
         if seen.insert(cmd.description) {
-            println!("- {:<12} {}", cmd.name, cmd.description);
+            output.write_line(&format!("- {:<12} {}", cmd.name, cmd.description));
         }
     }
+    output.write_line("Type 'help <command>' for its arguments.");
 }
 
-fn print_memory_usage() {
+fn print_command_help(command: &DebugCommand, output: &mut dyn OutputSink) {
+    output.write_line(&format!("Usage: {}", usage(command)));
+    output.write_line(&format!("  {}", command.description));
+    output.write_line(&format!("  Requires: {}", command.permission_level.name()));
+    if !command.aliases.is_empty() {
+        output.write_line(&format!("  Aliases: {}", command.aliases.join(", ")));
+    }
+    for arg in command.args {
+        output.write_line(&format!("  {:<24} {}", arg.usage(), arg.arg_type.describe()));
+    }
+}
+
+fn print_memory_usage(output: &mut dyn OutputSink) -> CommandResult {
     let mut sys = System::new_all();
     sys.refresh_all();
 
@@ -78,21 +92,28 @@ fn print_memory_usage() {
         let mem_mb = process.memory() as f64 / 1_000_000.0;
         let virtual_mem_kb = process.virtual_memory() as f64 / 1_000_000.0;
 
-        println!("Process Memory Usage:");
-        println!("  Resident Set Size (RSS): {} MB (approx. physical RAM used)", mem_mb);
-        println!("  Virtual Memory Size (VSZ): {} MB (total virtual memory mapped)", virtual_mem_kb);
+        output.write_line("Process Memory Usage:");
+        output.write_line(&format!("  Resident Set Size (RSS): {} MB (approx. physical RAM used)", mem_mb));
+        output.write_line(&format!("  Virtual Memory Size (VSZ): {} MB (total virtual memory mapped)", virtual_mem_kb));
+
+        CommandResult::success().with_data(serde_json::json!({
+            "resident_mb": mem_mb,
+            "virtual_mb": virtual_mem_kb,
+        }))
     } else {
-        println!("Could not retrieve process memory information.");
+        CommandResult::failure("Could not retrieve process memory information.")
     }
 }
 
-fn print_aliases(target_name: &str) {
+fn print_aliases(target_name: &str, output: &mut dyn OutputSink) {
     let target_command = get_global_command_registry().get(target_name).unwrap();
     let aliases = target_command.aliases;
 
-    println!("Available aliases for {target_name}:");
+    output.write_line(&format!("Available aliases for {target_name}:"));
+    let mut line = String::new();
     for string in aliases {
-        print!("{string}, ")
+        line.push_str(&format!("{string}, "));
     }
-    println!("{}", target_command.name);
-}
\ No newline at end of file
+    line.push_str(target_command.name);
+    output.write_line(&line);
+}
@@ -1,11 +1,12 @@
-use std::{array::from_fn, path::PathBuf};
+use std::{array::from_fn, path::{Component, Path, PathBuf}};
 
 use bincode::{Encode, Decode};
 use bytemuck::{Pod, Zeroable};
 use serde::{Deserialize, Serialize};
 
-use crate::engine::server::{chunk::Chunk, common::{BlockType, LayerType}, constants::{CHUNK_BLOCK_COUNT, CHUNK_SIZE}};
+use crate::engine::server::{chunk::Chunk, common::{BlockType, LayerType}, constants::{CHUNK_BLOCK_COUNT, CHUNK_SIZE, MIN_DAYLIGHT}, tile_entity::TileEntity};
 
+#[derive(Clone, Copy)]
 pub struct ChunkRelativePos {
     pub x: u8,
     pub y: u8,
@@ -34,6 +35,42 @@ pub struct ChunkMesh {
     pub foreground: [Block; CHUNK_BLOCK_COUNT as usize],
 }
 
+impl ChunkMesh {
+    pub fn set_light(&mut self, light: &PacketChunkLight) {
+        for layer in [&mut self.background, &mut self.middleground, &mut self.foreground] {
+            for (i, block) in layer.iter_mut().enumerate() {
+                block.sky_light = light.sky_light[i];
+                block.block_light = light.block_light[i];
+            }
+        }
+    }
+
+    // Only foreground blocks can be liquid
+    pub fn set_liquid_levels(&mut self, liquid: &PacketChunkLiquid) {
+        for (i, block) in self.foreground.iter_mut().enumerate() {
+            block.liquid_level = liquid.liquid_levels[i];
+        }
+    }
+
+    // Applies a change to one cell and returns the byte offset of its block in the mesh
+    pub fn set_block(&mut self, index: usize, change: &BlockChange) -> usize {
+        let (layer, layer_index) = match change.layer {
+            LayerType::Background => (&mut self.background, 0),
+            LayerType::Middleground => (&mut self.middleground, 1),
+            LayerType::Foreground => (&mut self.foreground, 2),
+        };
+
+        let block = &mut layer[index];
+        block.block_id = change.block_id;
+        block.block_type = change.block_type as u8;
+        block.texture_index = change.texture_index;
+        // A new block starts out full, like it does on the server
+        block.liquid_level = 0;
+
+        (layer_index * CHUNK_BLOCK_COUNT as usize + index) * std::mem::size_of::<Block>()
+    }
+}
+
 impl From<&PacketChunk> for ChunkMesh {
     fn from(packet: &PacketChunk) -> Self {
         ChunkMesh {
@@ -41,16 +78,25 @@ impl From<&PacketChunk> for ChunkMesh {
                 packet.background_blockid,
                 packet.background_blocktype,
                 packet.background_textureindex,
+                &packet.sky_light,
+                &packet.block_light,
+                &[0; CHUNK_BLOCK_COUNT as usize],
             ),
             middleground: convert_layer_to_aos_mesh(
                 packet.middleground_blockid,
                 packet.middleground_blocktype,
                 packet.middleground_textureindex,
+                &packet.sky_light,
+                &packet.block_light,
+                &[0; CHUNK_BLOCK_COUNT as usize],
             ),
             foreground: convert_layer_to_aos_mesh(
                 packet.foreground_blockid,
                 packet.foreground_blocktype,
                 packet.foreground_textureindex,
+                &packet.sky_light,
+                &packet.block_light,
+                &packet.liquid_levels,
             ),
         }
     }
@@ -60,6 +106,9 @@ fn convert_layer_to_aos_mesh(
     block_ids: [u32; CHUNK_BLOCK_COUNT as usize],
     block_types: [u8; CHUNK_BLOCK_COUNT as usize],
     texture_indices: [u8; CHUNK_BLOCK_COUNT as usize],
+    sky_light: &[u8; CHUNK_BLOCK_COUNT as usize],
+    block_light: &[u8; CHUNK_BLOCK_COUNT as usize],
+    liquid_levels: &[u8; CHUNK_BLOCK_COUNT as usize],
 ) -> [Block; CHUNK_BLOCK_COUNT as usize] {
     core::array::from_fn(|i| {
         Block {
@@ -69,6 +118,10 @@ fn convert_layer_to_aos_mesh(
             block_id: block_ids[i] as u32,
             block_type: block_types[i],
             texture_index: texture_indices[i],
+            sky_light: sky_light[i],
+            block_light: block_light[i],
+            liquid_level: liquid_levels[i],
+            _padding: 0,
         }
     })
 }
@@ -94,6 +147,45 @@ pub struct PacketChunk {
     pub background_blocktype: [u8; CHUNK_BLOCK_COUNT as usize],
     #[serde(with = "serde_arrays")]
     pub background_textureindex: [u8; CHUNK_BLOCK_COUNT as usize],
+    #[serde(with = "serde_arrays")]
+    pub sky_light: [u8; CHUNK_BLOCK_COUNT as usize],
+    #[serde(with = "serde_arrays")]
+    pub block_light: [u8; CHUNK_BLOCK_COUNT as usize],
+    #[serde(with = "serde_arrays")]
+    pub liquid_levels: [u8; CHUNK_BLOCK_COUNT as usize],
+}
+
+// Sent when the light of a chunk the client already has changes
+#[derive(Clone, Copy, Serialize, Deserialize, Encode, Decode, Debug)]
+pub struct PacketChunkLight {
+    #[serde(with = "serde_arrays")]
+    pub sky_light: [u8; CHUNK_BLOCK_COUNT as usize],
+    #[serde(with = "serde_arrays")]
+    pub block_light: [u8; CHUNK_BLOCK_COUNT as usize],
+}
+
+impl From<&Chunk> for PacketChunkLight {
+    fn from(chunk: &Chunk) -> Self {
+        PacketChunkLight {
+            sky_light: *chunk.get_sky_light(),
+            block_light: *chunk.get_block_light(),
+        }
+    }
+}
+
+// Sent when liquid in a chunk the client already has rises or sinks
+#[derive(Clone, Copy, Serialize, Deserialize, Encode, Decode, Debug)]
+pub struct PacketChunkLiquid {
+    #[serde(with = "serde_arrays")]
+    pub liquid_levels: [u8; CHUNK_BLOCK_COUNT as usize],
+}
+
+impl From<&Chunk> for PacketChunkLiquid {
+    fn from(chunk: &Chunk) -> Self {
+        PacketChunkLiquid {
+            liquid_levels: *chunk.get_liquid_levels(),
+        }
+    }
 }
 
 impl From<&Chunk> for PacketChunk {
@@ -110,6 +202,10 @@ impl From<&Chunk> for PacketChunk {
             background_blockid: from_fn(|i| chunk.background.block_id[i]),
             background_blocktype: from_fn(|i| chunk.background.block_type[i] as u8),
             background_textureindex: from_fn(|i| chunk.background.texture_index[i]),
+
+            sky_light: *chunk.get_sky_light(),
+            block_light: *chunk.get_block_light(),
+            liquid_levels: *chunk.get_liquid_levels(),
         }
     }
 }
@@ -122,22 +218,183 @@ pub struct Block {
     pub y: u8,
     pub block_type: u8,
     pub texture_index: u8,
+    // Both from 0 to MAX_LIGHT_LEVEL, the same for every layer of a cell
+    pub sky_light: u8,
+    pub block_light: u8,
+    // Fill level of a liquid, 0 for a full cell or anything that is not liquid
+    pub liquid_level: u8,
+    // Keeps the size a multiple of four, Pod does not allow implicit padding
+    pub _padding: u8,
 }
 
-#[derive(Serialize, Deserialize, Encode, Decode, Debug)]
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy)]
 pub struct BlockChange {
     pub layer: LayerType,
     pub block_type: BlockType,
-    pub block_id: u16,
+    pub block_id: u32,
+    // Which cell of a sprite it is, see sprite.rs
+    pub texture_index: u8,
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy)]
+pub struct PingPacket {
+    pub sequence: u32,
+    // Microseconds since the unix epoch, as seen by whoever sent the ping
+    pub timestamp: u64,
+}
+
+// The clock of a dimension. Time counts ticks since the dimension was created.
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy)]
+pub struct WorldTime {
+    pub time: u64,
+    pub day_length: u64,
+}
+
+impl WorldTime {
+    pub fn get_day(&self) -> u64 {
+        self.time / self.day_length.max(1)
+    }
+
+    pub fn get_time_of_day(&self) -> u64 {
+        self.time % self.day_length.max(1)
+    }
+
+    // 0 at sunrise, 0.25 at noon, 0.5 at sunset and 0.75 at midnight
+    pub fn get_day_fraction(&self) -> f32 {
+        self.get_time_of_day() as f32 / self.day_length.max(1) as f32
+    }
+
+    // 1 at noon, 0 at sunrise and sunset and -1 at midnight
+    pub fn get_sun_height(&self) -> f32 {
+        (self.get_day_fraction() * std::f32::consts::TAU).sin()
+    }
+
+    // Share of the sky light that reaches the world, it fades out a little
+    // after sunset and back in a little before sunrise
+    pub fn get_daylight(&self) -> f32 {
+        (0.5 + self.get_sun_height() * 1.5).clamp(MIN_DAYLIGHT, 1.0)
+    }
+}
+
+// A band of weather drifting sideways across a dimension, see weather.rs
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy)]
+pub struct WeatherFront {
+    // Centre of the band, in blocks
+    pub x: f32,
+    pub width: f32,
+    // In blocks per tick, negative to the left
+    pub velocity: f32,
+    // From 0 to 1
+    pub intensity: f32,
+    // Ticks until it clears
+    pub remaining: u64,
+}
+
+impl WeatherFront {
+    pub fn covers(&self, x: f32) -> bool {
+        (x - self.x).abs() <= self.width / 2.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy, PartialEq)]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+// Columns from start to end, both included, where something falls
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy)]
+pub struct PrecipitationSpan {
+    pub start: i32,
+    pub end: i32,
+    pub precipitation: Precipitation,
+}
+
+// What falls where depends on the climate on the ground, which clients do
+// not have, so the server works it out for the columns the fronts will pass
+// over before the next update
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Default)]
+pub struct WeatherState {
+    pub fronts: Vec<WeatherFront>,
+    pub spans: Vec<PrecipitationSpan>,
+}
+
+impl WeatherState {
+    // What falls on the column and how hard, None if it is dry there
+    pub fn get_precipitation(&self, x: i32) -> Option<(Precipitation, f32)> {
+        let intensity = self.fronts.iter()
+            .filter(|front| front.covers(x as f32 + 0.5))
+            .map(|front| front.intensity)
+            .reduce(f32::max)?;
+        let span = self.spans.iter().find(|span| (span.start..=span.end).contains(&x))?;
+        Some((span.precipitation, intensity))
+    }
+
+    // Moves the fronts on by the ticks, spans stay where they are
+    pub fn advanced(&self, ticks: f32) -> WeatherState {
+        let fronts = self.fronts.iter()
+            .map(|front| WeatherFront { x: front.x + front.velocity * ticks, ..*front })
+            .collect();
+        WeatherState { fronts, spans: self.spans.clone() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug)]
+pub struct HandshakePacket {
+    pub player_uuid: u64,
+    pub player_nickname: String,
+    // The token the server handed out for this UUID, None on the first join
+    pub identity_token: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone)]
+pub struct ChatMessage {
+    pub sender_uuid: u64,
+    pub sender_nickname: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy)]
+pub struct ServerStatus {
+    pub ticks_per_second: f32,
+    pub player_count: u32,
 }
 
 #[derive(Serialize, Deserialize, Encode, Decode, Debug)]
 pub enum ServerPacket {
-    Ping,
+    Ping(PingPacket),
+    Pong(PingPacket),
+    Disconnect(String),
+    Status(ServerStatus),
     ReloadChunks,
     Message(String),
+    Chat(ChatMessage),
     BlockChange(((i64, i64), BlockChange)),
     Chunk(((i32, i32), Box<PacketChunk>)),
+    ChunkLight(((i32, i32), Box<PacketChunkLight>)),
+    ChunkLiquid(((i32, i32), Box<PacketChunkLiquid>)),
+    // Sent on joining and every second after, clients keep the clock running in between
+    Time(WorldTime),
+    // Sent along with the time and whenever a front forms or clears
+    Weather(WeatherState),
+    // Answer to RequestTileEntity, None if there is none at the position
+    TileEntity(((i64, i64), Option<TileEntity>)),
+    // Sent on the first join with a UUID, the client has to send it back on every later join
+    Identity(u64),
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug)]
+pub enum ClientPacket {
+    Handshake(HandshakePacket),
+    Ping(PingPacket),
+    Pong(PingPacket),
+    // Plain chat line, or a command if it starts with a slash
+    Chat(String),
+    Disconnect,
+    PlayerPosition((f32, f32)),
+    RequestChunks(Vec<(i32, i32)>),
+    BlockEdit(((i64, i64), BlockChange)),
+    RequestTileEntity((i64, i64)),
 }
 
 #[derive(Serialize, Deserialize, Encode, Decode, Debug)]
@@ -150,4 +407,51 @@ pub struct PacketHeader {
 pub fn get_data_path() -> PathBuf {
     let manifest_dir = env!("CARGO_MANIFEST_DIR");
     PathBuf::from(manifest_dir).join("src/engine/server/data/native")
-}
\ No newline at end of file
+}
+
+// Turns a name typed by a user into a path inside the directory, so
+// commands cannot reach files elsewhere. The extension may be left out.
+pub fn resolve_file_name(directory: &Path, name: &str, extension: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(format!("File names must stay inside the {} directory, got \"{name}\"", directory.display()));
+    }
+
+    let mut path = directory.join(relative);
+    if path.extension().is_none() {
+        path.set_extension(extension);
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_time_splits_into_days() {
+        let time = WorldTime { time: 2500, day_length: 1000 };
+        assert_eq!(time.get_day(), 2);
+        assert_eq!(time.get_time_of_day(), 500);
+        assert_eq!(time.get_day_fraction(), 0.5);
+    }
+
+    #[test]
+    fn daylight_follows_the_sun() {
+        let at = |time: u64| WorldTime { time, day_length: 1000 };
+        assert!((at(250).get_sun_height() - 1.0).abs() < 1e-5);
+        assert!((at(750).get_sun_height() + 1.0).abs() < 1e-5);
+        assert_eq!(at(250).get_daylight(), 1.0);
+        assert_eq!(at(750).get_daylight(), MIN_DAYLIGHT);
+        // Still half light right at sunrise
+        assert!((at(0).get_daylight() - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zero_day_length_does_not_divide_by_zero() {
+        let time = WorldTime { time: 7, day_length: 0 };
+        assert_eq!(time.get_day(), 7);
+        assert_eq!(time.get_time_of_day(), 0);
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::RwLock;
+
+use rustyline::{completion::{Completer, Pair}, config::Configurer, error::ReadlineError, highlight::Highlighter, hint::Hinter, history::DefaultHistory, validate::Validator, CompletionType, Config, Context, Editor, Helper};
+
+use crate::{engine::command_args::ArgType, get_global_command_registry};
+
+const HISTORY_FILE: &str = "console_history.txt";
+const MAX_HISTORY_ENTRIES: usize = 1000;
+
+// The console thread cannot reach the server, so the server publishes its
+// dimension names here whenever they change
+static DIMENSION_NAMES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+pub fn set_dimension_names(names: Vec<String>) {
+    *DIMENSION_NAMES.write().unwrap() = names;
+}
+
+/*
+/   Line editor for the terminal. Up and down walk through the history,
+/   which is kept between runs, and Tab completes command names and
+/   aliases, then each argument from the values its ArgSpec allows.
+*/
+
+pub struct Console {
+    editor: Editor<CommandCompleter, DefaultHistory>,
+}
+
+impl Console {
+    pub fn new() -> rustyline::Result<Console> {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .auto_add_history(true)
+            .build();
+
+        let mut editor = Editor::with_config(config)?;
+        editor.set_max_history_size(MAX_HISTORY_ENTRIES)?;
+        editor.set_helper(Some(CommandCompleter));
+
+        // Missing on the first run
+        let _ = editor.load_history(HISTORY_FILE);
+
+        Ok(Console { editor })
+    }
+
+    // Returns None once the input is closed
+    pub fn read_line(&mut self) -> Option<String> {
+        match self.editor.readline("> ") {
+            Ok(line) => {
+                if let Err(error) = self.editor.save_history(HISTORY_FILE) {
+                    println!("Failed to save console history: {error}");
+                }
+                Some(line)
+            },
+            // Ctrl-C no longer reaches the process while the editor owns the terminal
+            Err(ReadlineError::Interrupted) => std::process::exit(0),
+            Err(ReadlineError::Eof) => None,
+            Err(error) => {
+                println!("Console input failed: {error}");
+                None
+            }
+        }
+    }
+}
+
+struct CommandCompleter;
+
+impl Completer for CommandCompleter {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before_cursor = &line[..pos];
+        let word_start = before_cursor.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        let word = &before_cursor[word_start..];
+        let previous_words: Vec<&str> = before_cursor[..word_start].split_whitespace().collect();
+
+        let options = match previous_words.split_first() {
+            None => command_names(),
+            Some((command_name, args)) => argument_options(command_name, args.len()),
+        };
+
+        let candidates = options.into_iter()
+            .filter(|option| option.starts_with(word))
+            .map(|option| Pair { display: option.clone(), replacement: option })
+            .collect();
+
+        Ok((word_start, candidates))
+    }
+}
+
+fn command_names() -> Vec<String> {
+    ArgType::Command.completions(&[])
+}
+
+fn argument_options(command_name: &str, arg_index: usize) -> Vec<String> {
+    let Some(command) = get_global_command_registry().get(command_name.to_lowercase().as_str()) else {
+        return Vec::new();
+    };
+
+    // A text argument swallows the rest of the line
+    if command.args.iter().take(arg_index).any(|spec| spec.arg_type == ArgType::Text) {
+        return Vec::new();
+    }
+
+    match command.args.get(arg_index) {
+        Some(spec) => spec.arg_type.completions(&DIMENSION_NAMES.read().unwrap()),
+        None => Vec::new(),
+    }
+}
+
+impl Hinter for CommandCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for CommandCompleter {}
+
+impl Validator for CommandCompleter {}
+
+impl Helper for CommandCompleter {}
@@ -0,0 +1,76 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::engine::common::PingPacket;
+
+// Tracks the round trip time of ping/pong exchanges with one peer.
+// Both the client and the server own one of these per connection.
+pub struct LatencyTracker {
+    last_sent_sequence: u32,
+    last_received_sequence: u32,
+    rtt_millis: Option<f32>,
+    smoothed_rtt_millis: f32,
+    jitter_millis: f32,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self {
+            last_sent_sequence: 0,
+            last_received_sequence: 0,
+            rtt_millis: None,
+            smoothed_rtt_millis: 0.0,
+            jitter_millis: 0.0,
+        }
+    }
+
+    pub fn next_ping(&mut self) -> PingPacket {
+        self.last_sent_sequence = self.last_sent_sequence.wrapping_add(1);
+
+        PingPacket {
+            sequence: self.last_sent_sequence,
+            timestamp: timestamp_micros(),
+        }
+    }
+
+    pub fn on_pong(&mut self, pong: &PingPacket) {
+        // The timestamp is our own, echoed back, so both clocks are the same
+        let rtt = timestamp_micros().saturating_sub(pong.timestamp) as f32 / 1000.0;
+
+        match self.rtt_millis {
+            // Smoothing and jitter follow TCP (RFC 6298) and RTP (RFC 3550)
+            Some(previous_rtt) => {
+                self.smoothed_rtt_millis += (rtt - self.smoothed_rtt_millis) / 8.0;
+                self.jitter_millis += ((rtt - previous_rtt).abs() - self.jitter_millis) / 16.0;
+            }
+            None => self.smoothed_rtt_millis = rtt,
+        }
+        self.rtt_millis = Some(rtt);
+
+        if pong.sequence.wrapping_sub(self.last_received_sequence) as i32 > 0 {
+            self.last_received_sequence = pong.sequence;
+        }
+    }
+
+    // Pings that were sent after the newest one that got answered
+    pub fn missed_pings(&self) -> u32 {
+        self.last_sent_sequence.wrapping_sub(self.last_received_sequence)
+    }
+
+    pub fn describe(&self) -> String {
+        match self.rtt_millis {
+            Some(rtt) => format!("RTT {:.2}ms (smoothed {:.2}ms), jitter {:.2}ms, {} unanswered",
+                rtt, self.smoothed_rtt_millis, self.jitter_millis, self.missed_pings()),
+            None => format!("No pong received yet, {} unanswered", self.missed_pings()),
+        }
+    }
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn timestamp_micros() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or(0)
+}
@@ -1,7 +1,12 @@
 pub mod time;
 pub mod commands;
 pub mod command_registry;
+pub mod command_args;
+pub mod console;
 pub mod common;
+pub mod network;
+pub mod latency;
 pub mod server;
 pub mod client;
+pub mod bot;
 pub mod components;
\ No newline at end of file
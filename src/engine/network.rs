@@ -0,0 +1,115 @@
+use std::{cell::Cell, sync::mpsc::{Receiver, Sender}};
+
+use bincode::{Decode, Encode};
+
+use crate::engine::common::PacketHeader;
+
+// Packets smaller than this are not worth the lz4 overhead
+const COMPRESSION_THRESHOLD: usize = 100;
+
+// One end of an in-process client <-> server link. The server keeps one
+// of these per connected client, the client keeps the other end.
+pub struct Connection {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    bytes_sent: Cell<u64>,
+    bytes_received: Cell<u64>,
+}
+
+impl Connection {
+    pub fn pair() -> (Connection, Connection) {
+        let (tx_a, rx_a) = std::sync::mpsc::channel::<Vec<u8>>();
+        let (tx_b, rx_b) = std::sync::mpsc::channel::<Vec<u8>>();
+
+        (Connection::new(tx_a, rx_b), Connection::new(tx_b, rx_a))
+    }
+
+    fn new(sender: Sender<Vec<u8>>, receiver: Receiver<Vec<u8>>) -> Connection {
+        Connection {
+            sender,
+            receiver,
+            bytes_sent: Cell::new(0),
+            bytes_received: Cell::new(0),
+        }
+    }
+
+    // Returns false if the other end has hung up
+    pub fn send<T: Encode>(&self, packet: T, compress: bool) -> bool {
+        self.send_raw(encode_packet(packet, compress))
+    }
+
+    pub fn send_raw(&self, raw_packet: Vec<u8>) -> bool {
+        self.bytes_sent.set(self.bytes_sent.get() + raw_packet.len() as u64);
+        self.sender.send(raw_packet).is_ok()
+    }
+
+    pub fn try_recv<T: Decode<()>>(&self) -> Option<T> {
+        while let Ok(raw_packet) = self.receiver.try_recv() {
+            self.bytes_received.set(self.bytes_received.get() + raw_packet.len() as u64);
+            match decode_packet(&raw_packet) {
+                Some(packet) => return Some(packet),
+                None => println!("Dropped malformed packet of {} bytes", raw_packet.len()),
+            }
+        }
+
+        None
+    }
+
+    // Encoded bytes, including the packet header
+    pub fn get_bytes_sent(&self) -> u64 {
+        self.bytes_sent.get()
+    }
+
+    pub fn get_bytes_received(&self) -> u64 {
+        self.bytes_received.get()
+    }
+}
+
+pub fn encode_packet<T: Encode>(packet: T, compress: bool) -> Vec<u8> {
+    // turn into bytes
+    let encoded_packet = bincode::encode_to_vec(packet, bincode::config::standard()).unwrap();
+    let mut is_compressed = false;
+    let raw_len = encoded_packet.len();
+
+    // compress if large enough
+    let encoded_packet: Vec<u8> = if raw_len > COMPRESSION_THRESHOLD && compress {
+        let max_compressed_size = lz4_flex::block::get_maximum_output_size(raw_len);
+        let mut compressed_buffer = vec![0u8; max_compressed_size];
+
+        let compressed_len = lz4_flex::compress_into(&encoded_packet, &mut compressed_buffer)
+        .map_err(|e| format!("LZ4 Compression Error: {}", e)).unwrap();
+
+        compressed_buffer.truncate(compressed_len);
+
+        is_compressed = true;
+        compressed_buffer
+    } else {
+        encoded_packet
+    };
+
+    // Pack into header
+    let encoded_packet = PacketHeader {
+        is_compressed,
+        original_size: raw_len,
+        data: encoded_packet,
+    };
+
+    // Turn into bytes a second time
+    bincode::encode_to_vec(encoded_packet, bincode::config::standard()).unwrap()
+}
+
+pub fn decode_packet<T: Decode<()>>(raw_packet: &[u8]) -> Option<T> {
+    // decode to packet header
+    let (packet, _bytes_consumed): (PacketHeader, usize) = bincode::decode_from_slice(raw_packet, bincode::config::standard()).ok()?;
+
+    if packet.is_compressed {
+        let decompressed_packet = lz4_flex::decompress(&packet.data, packet.original_size).ok()?;
+        let (packet, _bytes) = bincode::decode_from_slice(&decompressed_packet, bincode::config::standard()).ok()?;
+
+        Some(packet)
+    } else {
+        let (packet, _bytes) = bincode::decode_from_slice(&packet.data, bincode::config::standard()).ok()?;
+
+        Some(packet)
+    }
+}
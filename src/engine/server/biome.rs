@@ -1,4 +1,4 @@
-use crate::engine::server::{constants::{BIOME_MAP_GRID_SIZE, CELLULAR_NINDEX, CONTINENTAL_NINDEX, GRIDLIKE_NINDEX, HILLY_NINDEX, MOUNTAINOUS_NINDEX, NUM_1D_NOISE_LAYERS, TEXTURE_NINDEX}, data::schema_definitions::{BiomeConfig, BiomeSchema, NoiseConfig}};
+use crate::engine::server::{constants::{BIOME_MAP_GRID_SIZE, CELLULAR_NINDEX, CONTINENTAL_NINDEX, GRIDLIKE_NINDEX, HILLY_NINDEX, MOUNTAINOUS_NINDEX, NUM_1D_NOISE_LAYERS, TEXTURE_NINDEX}, data::schema_definitions::{BiomeConfig, BiomeSchema, CaveBand, DecorationSchema, NoiseConfig}, feature::Feature};
 
 pub struct BiomeRegistry {
     pub biomes: Box<[Biome]>,
@@ -106,8 +106,12 @@ impl<'a> BiomeMap<'a> {
 }
 
 pub struct Biome {
+    pub name: String,
     pub biome_config: BiomeConfig,
     pub noise_schema: [NoiseConfig; 6],
+    pub features: Vec<Feature>,
+    pub caves: Vec<CaveBand>,
+    pub decorations: Vec<DecorationSchema>,
 }
 
 impl Biome {
@@ -121,9 +125,23 @@ impl Biome {
         noise_schema[CELLULAR_NINDEX] = schema.noise_functions.cellular;
         noise_schema[GRIDLIKE_NINDEX] = schema.noise_functions.gridlike;
 
+        // A broken feature should not take the whole dimension down
+        let features = schema.features.into_iter()
+            .filter_map(|feature_schema| {
+                let name = feature_schema.name.clone();
+                Feature::from_schema(feature_schema)
+                    .inspect_err(|error| println!("Skipping feature {name}: {error}"))
+                    .ok()
+            })
+            .collect();
+
         Biome {
+            name: schema.name,
             biome_config: schema.biome_config,
             noise_schema: noise_schema,
+            features,
+            caves: schema.caves,
+            decorations: schema.decorations,
         }
     }
 }
\ No newline at end of file
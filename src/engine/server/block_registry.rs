@@ -0,0 +1,91 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::engine::server::{common::{BlockState, BlockType}, constants::SPRITE_MAX_SIZE, data::schema_definitions::{BlockSchema, RandomTick, SpriteSchema, TileEntitySchema}};
+
+// Block properties by id, ids without an entry get the defaults
+pub struct BlockRegistry {
+    blocks: HashMap<u32, BlockSchema>,
+}
+
+impl BlockRegistry {
+    pub fn load(data_dir: &Path) -> Result<BlockRegistry, Box<dyn std::error::Error>> {
+        let file = std::fs::File::open(data_dir.join("blocks.json"))?;
+        let schemas: Vec<BlockSchema> = serde_json::from_reader(file)?;
+
+        let mut blocks: HashMap<u32, BlockSchema> = HashMap::new();
+        for schema in schemas {
+            if let Some(existing) = blocks.get(&schema.id) {
+                return Err(format!("Blocks {} and {} both use id {}", existing.name, schema.name, schema.id).into());
+            }
+            if let Some(sprite) = &schema.sprite {
+                if !(1..=SPRITE_MAX_SIZE).contains(&sprite.width) || !(1..=SPRITE_MAX_SIZE).contains(&sprite.height) {
+                    return Err(format!("Sprite {} must be between 1 and {SPRITE_MAX_SIZE} cells wide and high", schema.name).into());
+                }
+                if sprite.anchor.x >= sprite.width || sprite.anchor.y >= sprite.height {
+                    return Err(format!("Anchor of sprite {} lies outside of it", schema.name).into());
+                }
+            }
+            blocks.insert(schema.id, schema);
+        }
+
+        Ok(BlockRegistry { blocks })
+    }
+
+    pub fn empty() -> BlockRegistry {
+        BlockRegistry { blocks: HashMap::new() }
+    }
+
+    // Air only exists with id 0, everything else needs an entry
+    pub fn is_known(&self, block: BlockState) -> bool {
+        match block.block_type {
+            BlockType::Air => block.block_id == 0,
+            _ => self.blocks.contains_key(&block.block_id),
+        }
+    }
+
+    pub fn falls(&self, block: BlockState) -> bool {
+        block.block_type == BlockType::Tile && self.blocks.get(&block.block_id).is_some_and(|schema| schema.falls)
+    }
+
+    pub fn is_opaque(&self, block: BlockState) -> bool {
+        block.block_type == BlockType::Tile && !self.blocks.get(&block.block_id).is_some_and(|schema| schema.transparent)
+    }
+
+    pub fn get_light(&self, block: BlockState) -> u8 {
+        if block.block_type == BlockType::Air {
+            return 0;
+        }
+        self.blocks.get(&block.block_id).map_or(0, |schema| schema.light)
+    }
+
+    pub fn get_random_tick(&self, block: BlockState) -> Option<RandomTick> {
+        if block.block_type == BlockType::Air {
+            return None;
+        }
+        self.blocks.get(&block.block_id)?.random_tick
+    }
+
+    // Only blocks placed with the tile entity type get one
+    pub fn get_tile_entity(&self, block: BlockState) -> Option<TileEntitySchema> {
+        if block.block_type != BlockType::TileEntity {
+            return None;
+        }
+        self.blocks.get(&block.block_id)?.tile_entity
+    }
+
+    // Only blocks placed with the sprite type cover more than one cell
+    pub fn get_sprite(&self, block: BlockState) -> Option<&SpriteSchema> {
+        if block.block_type != BlockType::Sprite {
+            return None;
+        }
+        self.blocks.get(&block.block_id)?.sprite.as_ref()
+    }
+
+    pub fn get_smelts_into(&self, block_id: u32) -> Option<u32> {
+        self.blocks.get(&block_id)?.smelts_into
+    }
+
+    pub fn get_name(&self, block_id: u32) -> Option<&str> {
+        self.blocks.get(&block_id).map(|schema| schema.name.as_str())
+    }
+}
@@ -0,0 +1,70 @@
+use fastrand::Rng;
+use glam::IVec2;
+
+use crate::engine::server::{common::{BlockState, BlockType, LayerType}, data::schema_definitions::RandomTick, gravity, sprite, world::Dimension, world_edit::CellChange};
+
+/*
+/   Blocks change on their own in two ways. Scheduled updates run once for
+/   one position at a set tick, gravity and sprite support use them.
+/   Random ticks land on a few random cells of every loaded chunk each tick
+/   and run the random tick of the block there, so slow changes such as
+/   grass spreading cost the same no matter how much grass there is.
+*/
+
+pub fn run_scheduled_update(dimension: &mut Dimension, world_pos: IVec2) -> Vec<CellChange> {
+    let mut changes = gravity::update_block(dimension, world_pos);
+    changes.extend(sprite::update_support(dimension, world_pos));
+    changes
+}
+
+pub fn run_random_tick(dimension: &mut Dimension, world_pos: IVec2, rng: &mut Rng) -> Vec<CellChange> {
+    let mut changes = Vec::new();
+
+    for layer in [LayerType::Foreground, LayerType::Middleground] {
+        let Some(block) = dimension.get_block(world_pos, layer) else {
+            continue;
+        };
+        let Some(random_tick) = dimension.get_blocks().get_random_tick(block) else {
+            continue;
+        };
+
+        let change = match random_tick {
+            RandomTick::Spread { onto, chance, range } => {
+                if rng.f32() >= chance {
+                    continue;
+                }
+
+                let range = range as i32;
+                let target = world_pos + IVec2::new(rng.i32(-range..=range), rng.i32(-range..=range));
+                let covered = dimension.get_block(target + IVec2::Y, LayerType::Foreground).is_none_or(|above| above.block_type != BlockType::Air);
+                match dimension.get_block(target, layer) {
+                    Some(current) if current.block_type == block.block_type && current.block_id == onto && !covered => {
+                        replace(dimension, target, layer, current, BlockState::new(block.block_type, block.block_id))
+                    },
+                    _ => continue,
+                }
+            },
+            RandomTick::Melt { into, min_temperature } => {
+                if dimension.get_temperature(world_pos).is_none_or(|temperature| temperature < min_temperature) {
+                    continue;
+                }
+                replace(dimension, world_pos, layer, block, BlockState::new(block.block_type, into))
+            },
+            RandomTick::Grow { into, chance } => {
+                if rng.f32() >= chance {
+                    continue;
+                }
+                replace(dimension, world_pos, layer, block, BlockState::new(block.block_type, into))
+            },
+        };
+
+        changes.push(change);
+    }
+
+    changes
+}
+
+fn replace(dimension: &mut Dimension, world_pos: IVec2, layer: LayerType, before: BlockState, after: BlockState) -> CellChange {
+    dimension.set_block(world_pos, layer, after);
+    CellChange { position: world_pos, layer, before, after }
+}
@@ -1,26 +1,47 @@
-use std::{collections::HashSet, sync::{Arc}};
+use std::{collections::{HashMap, HashSet}, sync::{Arc}};
 
+use bincode::{Decode, Encode};
 use glam::IVec2;
 
-use crate::engine::{common::{Block, ChunkMesh, ChunkRelativePos}, components::alive::{EntityID, PlayerID}, server::{biome::{Biome, BiomeMap}, common::{BlockArray, BlockType, LayerType}, constants::{CHUNK_BLOCK_COUNT, CHUNK_SIZE, HUMIDITY_INDEX, TEMPERATURE_INDEX}, data::schema_definitions::{BiomeConfig, BiomeTypes, BlendingMode}, noise::{noise_sampler::NoiseSampler}}};
+use crate::engine::{common::{Block, ChunkMesh, ChunkRelativePos}, components::alive::{EntityID, PlayerID}, server::{biome::{Biome, BiomeMap}, block_registry::BlockRegistry, common::{world_to_local_pos_2d, BlockArray, BlockState, BlockType, LayerType}, decoration::place_decorations, feature::{decorate_chunk, FeaturePlacement}, lighting::LightKind, ore::place_ores, tile_entity::TileEntity, constants::{CAVERN_INDEX, CHUNK_BLOCK_COUNT, CHUNK_SIZE, HUMIDITY_INDEX, TEMPERATURE_INDEX, WORM_CAVE_INDEX}, data::schema_definitions::{BiomeConfig, BlendingMode, CaveBand, OreSchema}, noise::{noise_sampler::NoiseSampler, noise_util::unpack_signed_noise}}};
 
 pub struct Chunk {
     pub foreground: BlockArray,
     pub middleground: BlockArray,
     pub background: BlockArray,
     total_block_count: u64,
+    // Fill level of liquid in the foreground, 0 on a liquid block means full
+    liquid_levels: [u8; CHUNK_BLOCK_COUNT as usize],
+    // Priority of the feature owning a block, keyed by layer and index
+    feature_priorities: HashMap<u16, u64>,
+    // Blocks waiting to be looked at again, in no particular order. They
+    // belong to the chunk so they are stored with it, see to_bytes.
+    scheduled_updates: Vec<ScheduledUpdate>,
+    // The temperature and humidity noise the biomes were picked with, for
+    // blocks and weather that react to the climate
+    temperatures: [u8; CHUNK_BLOCK_COUNT as usize],
+    humidities: [u8; CHUNK_BLOCK_COUNT as usize],
+    // Filled in once the chunk is part of a dimension, see lighting.rs
+    sky_light: [u8; CHUNK_BLOCK_COUNT as usize],
+    block_light: [u8; CHUNK_BLOCK_COUNT as usize],
+    // State of the tile entity blocks in the foreground, keyed by index
+    tile_entities: HashMap<u16, TileEntity>,
 
     players: HashSet<PlayerID>,
     entites: HashSet<EntityID>,
 }
 
 impl Chunk {
-    pub fn generate_chunk(chunk_pos: &IVec2, biome_map: &BiomeMap, noise_sampler: &Arc<NoiseSampler>, seed: i32) -> Chunk {
+    // Also returns the feature blocks that spill into neighbouring chunks
+    pub fn generate_chunk(chunk_pos: &IVec2, biome_map: &BiomeMap, noise_sampler: &Arc<NoiseSampler>, ores: &[OreSchema], sea_level: i32, seed: i32) -> (Chunk, Vec<FeaturePlacement>) {
         let mut foreground = BlockArray::filled_basic_air();
+        let mut background = BlockArray::filled_basic_air();
         let chunk_world_pos = IVec2 { x: chunk_pos.x * CHUNK_SIZE as i32, y: chunk_pos.y * CHUNK_SIZE as i32 };
 
         let temperature_map = noise_sampler.get_noise_layer_2d(&chunk_pos, TEMPERATURE_INDEX);
         let humidity_map = noise_sampler.get_noise_layer_2d(&chunk_pos, HUMIDITY_INDEX);
+        let worm_cave_map = noise_sampler.get_noise_layer_2d(chunk_pos, WORM_CAVE_INDEX);
+        let cavern_map = noise_sampler.get_noise_layer_2d(chunk_pos, CAVERN_INDEX);
 
         // Get terrain height
         let heights: [f32; CHUNK_SIZE as usize] = get_terrain_heights(&chunk_world_pos, biome_map, noise_sampler);
@@ -34,17 +55,67 @@ impl Chunk {
 
             let biome_to_use = biome_map.get_best_biome(temperature_map.read_index(i), humidity_map.read_index(i));
 
+            // Walls come first so caves carved in front of them keep their backdrop
+            if generate_wall_id(heights[x], world_y as f32, i, &mut background, &biome_to_use.biome_config) {
+                total_block_count += 1;
+            }
+
+            // Caves only take foreground blocks out of the terrain, never water
+            let depth = heights[x] as i32 - world_y;
+            if heights[x] >= world_y as f32 && is_cave(&biome_to_use.caves, depth, worm_cave_map.read_index(i), cavern_map.read_index(i)) {
+                if generate_cave_fluid(depth, i, &mut foreground, &biome_to_use.biome_config) {
+                    total_block_count += 1;
+                }
+                continue;
+            }
+
             if generate_block_id(heights[x as usize], world_y as f32, i, &mut foreground,
-                &biome_to_use.biome_config) {total_block_count += 1}
+                &biome_to_use.biome_config, sea_level) {total_block_count += 1}
         }
 
-        return Chunk { 
+        let mut chunk = Chunk { 
             foreground,
             middleground: (BlockArray::filled_basic_air()),
-            background: (BlockArray::filled_basic_air()),
+            background,
             total_block_count,
+            liquid_levels: [0; CHUNK_BLOCK_COUNT as usize],
+            feature_priorities: HashMap::new(),
+            scheduled_updates: Vec::new(),
+            temperatures: temperature_map.layer,
+            humidities: humidity_map.layer,
+            sky_light: [0; CHUNK_BLOCK_COUNT as usize],
+            block_light: [0; CHUNK_BLOCK_COUNT as usize],
+            tile_entities: HashMap::new(),
             players: (HashSet::new()),
             entites: (HashSet::new())
+        };
+
+        let biome_at = |i: usize| biome_map.get_best_biome(temperature_map.read_index(i), humidity_map.read_index(i));
+        place_ores(&mut chunk, chunk_pos, ores, biome_at, seed);
+        place_decorations(&mut chunk, chunk_pos, &heights, biome_at, seed);
+        let overflow = decorate_chunk(&mut chunk, chunk_pos, &heights, biome_at, seed);
+
+        (chunk, overflow)
+    }
+
+    // Nothing but air, for tests that build the world by hand
+    #[cfg(test)]
+    pub fn empty() -> Chunk {
+        Chunk {
+            foreground: BlockArray::filled_basic_air(),
+            middleground: BlockArray::filled_basic_air(),
+            background: BlockArray::filled_basic_air(),
+            total_block_count: 0,
+            liquid_levels: [0; CHUNK_BLOCK_COUNT as usize],
+            feature_priorities: HashMap::new(),
+            scheduled_updates: Vec::new(),
+            temperatures: [0; CHUNK_BLOCK_COUNT as usize],
+            humidities: [0; CHUNK_BLOCK_COUNT as usize],
+            sky_light: [0; CHUNK_BLOCK_COUNT as usize],
+            block_light: [0; CHUNK_BLOCK_COUNT as usize],
+            tile_entities: HashMap::new(),
+            players: HashSet::new(),
+            entites: HashSet::new(),
         }
     }
 
@@ -62,6 +133,170 @@ impl Chunk {
         self.get_block_array_mut(layer).set_block_texture_index(chunk_relative_pos, new_texture_index);
     }
 
+    // Sets every property of a block at once and keeps the block count in sync
+    pub fn set_block(&mut self, chunk_relative_pos: ChunkRelativePos, layer: LayerType, block_type: BlockType, block_id: u32, texture_index: u8) {
+        let index = chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize;
+        self.feature_priorities.remove(&feature_key(layer, index));
+        if layer == LayerType::Foreground {
+            self.liquid_levels[index] = 0;
+        }
+
+        let block_array = self.get_block_array_mut(layer);
+        let was_air = block_array.block_type[index] == BlockType::Air;
+        let unchanged = block_array.block_type[index] == block_type && block_array.block_id[index] == block_id;
+
+        block_array.set_block_type_byindex(index, block_type);
+        block_array.set_block_id_byindex(index, block_id);
+        block_array.set_block_texture_index_byindex(index, texture_index);
+
+        match (was_air, block_type == BlockType::Air) {
+            (true, false) => self.total_block_count += 1,
+            (false, true) => self.total_block_count -= 1,
+            _ => {}
+        }
+
+        // The state belongs to the block, a different one starts without it
+        if layer == LayerType::Foreground && !unchanged {
+            self.tile_entities.remove(&(index as u16));
+        }
+    }
+
+    // Features only grow into air, and where two overlap the higher priority
+    // wins. That way the result does not depend on which chunk generated first.
+    pub fn place_feature_block(&mut self, placement: &FeaturePlacement) {
+        let local_pos = world_to_local_pos_2d(placement.world_pos);
+        let chunk_relative_pos = ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8);
+        let key = feature_key(placement.layer, local_pos.y as usize * CHUNK_SIZE as usize + local_pos.x as usize);
+
+        let free = match self.feature_priorities.get(&key) {
+            Some(priority) => *priority < placement.priority,
+            None => self.get_block(chunk_relative_pos, placement.layer).block_type == BlockType::Air,
+        };
+        if !free {
+            return;
+        }
+
+        let block = placement.block;
+        self.set_block(chunk_relative_pos, placement.layer, block.block_type, block.block_id, block.texture_index);
+        self.feature_priorities.insert(key, placement.priority);
+    }
+
+    pub fn get_liquid_level(&self, chunk_relative_pos: ChunkRelativePos) -> u8 {
+        self.liquid_levels[chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize]
+    }
+
+    pub fn get_liquid_levels(&self) -> &[u8; CHUNK_BLOCK_COUNT as usize] {
+        &self.liquid_levels
+    }
+
+    pub fn set_liquid_level(&mut self, chunk_relative_pos: ChunkRelativePos, level: u8) {
+        self.liquid_levels[chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize] = level;
+    }
+
+    // A block already waiting keeps the earlier of the two ticks
+    pub fn schedule_update(&mut self, chunk_relative_pos: ChunkRelativePos, due_tick: u64) {
+        let index = chunk_relative_pos.y as u16 * CHUNK_SIZE as u16 + chunk_relative_pos.x as u16;
+
+        match self.scheduled_updates.iter_mut().find(|update| update.index == index) {
+            Some(update) => update.due_tick = update.due_tick.min(due_tick),
+            None => self.scheduled_updates.push(ScheduledUpdate { index, due_tick }),
+        }
+    }
+
+    pub fn take_due_updates(&mut self, tick: u64) -> Vec<ChunkRelativePos> {
+        if self.scheduled_updates.is_empty() {
+            return Vec::new();
+        }
+
+        let mut due = Vec::new();
+        self.scheduled_updates.retain(|update| {
+            if update.due_tick > tick {
+                return true;
+            }
+            due.push(ChunkRelativePos::new((update.index % CHUNK_SIZE as u16) as u8, (update.index / CHUNK_SIZE as u16) as u8));
+            false
+        });
+        due
+    }
+
+    pub fn get_light(&self, chunk_relative_pos: ChunkRelativePos, kind: LightKind) -> u8 {
+        let index = chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize;
+        match kind {
+            LightKind::Sky => self.sky_light[index],
+            LightKind::Block => self.block_light[index],
+        }
+    }
+
+    pub fn set_light(&mut self, chunk_relative_pos: ChunkRelativePos, kind: LightKind, level: u8) {
+        let index = chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize;
+        match kind {
+            LightKind::Sky => self.sky_light[index] = level,
+            LightKind::Block => self.block_light[index] = level,
+        }
+    }
+
+    pub fn get_sky_light(&self) -> &[u8; CHUNK_BLOCK_COUNT as usize] {
+        &self.sky_light
+    }
+
+    pub fn get_block_light(&self) -> &[u8; CHUNK_BLOCK_COUNT as usize] {
+        &self.block_light
+    }
+
+    pub fn get_tile_entity(&self, chunk_relative_pos: ChunkRelativePos) -> Option<&TileEntity> {
+        self.tile_entities.get(&(chunk_relative_pos.y as u16 * CHUNK_SIZE as u16 + chunk_relative_pos.x as u16))
+    }
+
+    pub fn get_tile_entity_mut(&mut self, chunk_relative_pos: ChunkRelativePos) -> Option<&mut TileEntity> {
+        self.tile_entities.get_mut(&(chunk_relative_pos.y as u16 * CHUNK_SIZE as u16 + chunk_relative_pos.x as u16))
+    }
+
+    pub fn insert_tile_entity(&mut self, chunk_relative_pos: ChunkRelativePos, tile_entity: TileEntity) {
+        self.tile_entities.insert(chunk_relative_pos.y as u16 * CHUNK_SIZE as u16 + chunk_relative_pos.x as u16, tile_entity);
+    }
+
+    pub fn get_tile_entity_count(&self) -> usize {
+        self.tile_entities.len()
+    }
+
+    // Runs the tile entities that have something to do
+    pub fn tick_tile_entities(&mut self, blocks: &BlockRegistry) {
+        for (index, tile_entity) in self.tile_entities.iter_mut() {
+            if !tile_entity.needs_tick() {
+                continue;
+            }
+
+            let index = *index as usize;
+            let block = BlockState { block_type: self.foreground.block_type[index], block_id: self.foreground.block_id[index], texture_index: self.foreground.texture_index[index] };
+            if let Some(schema) = blocks.get_tile_entity(block) {
+                tile_entity.tick(schema, blocks);
+            }
+        }
+    }
+
+    pub fn get_temperature(&self, chunk_relative_pos: ChunkRelativePos) -> u8 {
+        self.temperatures[chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize]
+    }
+
+    pub fn get_humidity(&self, chunk_relative_pos: ChunkRelativePos) -> u8 {
+        self.humidities[chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize]
+    }
+
+    pub fn get_block(&self, chunk_relative_pos: ChunkRelativePos, layer: LayerType) -> BlockState {
+        let index = chunk_relative_pos.y as usize * CHUNK_SIZE as usize + chunk_relative_pos.x as usize;
+        let block_array = match layer {
+            LayerType::Foreground => &self.foreground,
+            LayerType::Middleground => &self.middleground,
+            LayerType::Background => &self.background,
+        };
+
+        BlockState {
+            block_type: block_array.block_type[index],
+            block_id: block_array.block_id[index],
+            texture_index: block_array.texture_index[index],
+        }
+    }
+
     fn get_block_array_mut(&mut self, layer: LayerType) -> &mut BlockArray {
         match layer {
             LayerType::Foreground => {
@@ -80,16 +315,113 @@ impl Chunk {
         self.total_block_count
     }
 
+    // Everything but the light, which is worked out again once the chunk is
+    // back in a dimension. Scheduled updates are stored by how many ticks
+    // they had left, the dimension clock starts over when the server does.
+    pub fn to_bytes(&self, tick: u64) -> Vec<u8> {
+        let stored = StoredChunk {
+            foreground: self.foreground,
+            middleground: self.middleground,
+            background: self.background,
+            total_block_count: self.total_block_count,
+            liquid_levels: self.liquid_levels,
+            feature_priorities: self.feature_priorities.clone(),
+            scheduled_updates: self.scheduled_updates.iter()
+                .map(|update| ScheduledUpdate { index: update.index, due_tick: update.due_tick.saturating_sub(tick) })
+                .collect(),
+            temperatures: self.temperatures,
+            humidities: self.humidities,
+            tile_entities: self.tile_entities.clone(),
+        };
+        let encoded = bincode::encode_to_vec(&stored, bincode::config::standard()).unwrap();
+
+        let mut bytes = Vec::with_capacity(encoded.len() / 2);
+        bytes.extend_from_slice(CHUNK_MAGIC);
+        bytes.extend_from_slice(&CHUNK_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&lz4_flex::compress_prepend_size(&encoded));
+        bytes
+    }
+
+    // The tick is the one of the dimension the chunk is loaded into
+    pub fn from_bytes(bytes: &[u8], tick: u64) -> Result<Chunk, String> {
+        let Some(body) = bytes.strip_prefix(CHUNK_MAGIC) else {
+            return Err("Not a chunk file".to_string());
+        };
+
+        let (version, body) = body.split_at_checked(2).ok_or("Chunk file is truncated")?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version != CHUNK_VERSION {
+            return Err(format!("Chunk has format version {version}, this build reads version {CHUNK_VERSION}"));
+        }
+
+        let decompressed = lz4_flex::decompress_size_prepended(body).map_err(|error| format!("Chunk is corrupted: {error}"))?;
+        let (stored, _): (StoredChunk, usize) = bincode::decode_from_slice(&decompressed, bincode::config::standard())
+            .map_err(|error| format!("Chunk is corrupted: {error}"))?;
+
+        Ok(Chunk {
+            foreground: stored.foreground,
+            middleground: stored.middleground,
+            background: stored.background,
+            total_block_count: stored.total_block_count,
+            liquid_levels: stored.liquid_levels,
+            feature_priorities: stored.feature_priorities,
+            scheduled_updates: stored.scheduled_updates.into_iter()
+                .map(|update| ScheduledUpdate { index: update.index, due_tick: tick + update.due_tick })
+                .collect(),
+            temperatures: stored.temperatures,
+            humidities: stored.humidities,
+            sky_light: [0; CHUNK_BLOCK_COUNT as usize],
+            block_light: [0; CHUNK_BLOCK_COUNT as usize],
+            tile_entities: stored.tile_entities,
+            players: HashSet::new(),
+            entites: HashSet::new(),
+        })
+    }
+
     pub fn to_mesh(&self) -> ChunkMesh {
         return ChunkMesh {
-            foreground: convert_layer_to_aos(self.foreground),
-            middleground: convert_layer_to_aos(self.middleground),
-            background: convert_layer_to_aos(self.background)
+            foreground: convert_layer_to_aos(self.foreground, &self.sky_light, &self.block_light, &self.liquid_levels),
+            middleground: convert_layer_to_aos(self.middleground, &self.sky_light, &self.block_light, &[0; CHUNK_BLOCK_COUNT as usize]),
+            background: convert_layer_to_aos(self.background, &self.sky_light, &self.block_light, &[0; CHUNK_BLOCK_COUNT as usize])
         }
     }
 }
 
-fn convert_layer_to_aos(layer: BlockArray) -> [Block; CHUNK_BLOCK_COUNT as usize] {
+// A block that wants to be looked at again once the dimension reaches the tick
+#[derive(Clone, Copy, Debug, PartialEq, Encode, Decode)]
+struct ScheduledUpdate {
+    index: u16,
+    due_tick: u64,
+}
+
+/*
+/   Chunk files start with the magic bytes and a little endian format
+/   version, followed by the lz4 compressed bincode of a StoredChunk, the
+/   same layout schematics use. Bump the version whenever StoredChunk changes.
+*/
+const CHUNK_MAGIC: &[u8; 4] = b"CHNK";
+const CHUNK_VERSION: u16 = 2;
+
+#[derive(Encode, Decode)]
+struct StoredChunk {
+    foreground: BlockArray,
+    middleground: BlockArray,
+    background: BlockArray,
+    total_block_count: u64,
+    liquid_levels: [u8; CHUNK_BLOCK_COUNT as usize],
+    feature_priorities: HashMap<u16, u64>,
+    // Due ticks count from the tick the chunk was stored at
+    scheduled_updates: Vec<ScheduledUpdate>,
+    temperatures: [u8; CHUNK_BLOCK_COUNT as usize],
+    humidities: [u8; CHUNK_BLOCK_COUNT as usize],
+    tile_entities: HashMap<u16, TileEntity>,
+}
+
+fn feature_key(layer: LayerType, index: usize) -> u16 {
+    (layer as usize * CHUNK_BLOCK_COUNT as usize + index) as u16
+}
+
+fn convert_layer_to_aos(layer: BlockArray, sky_light: &[u8; CHUNK_BLOCK_COUNT as usize], block_light: &[u8; CHUNK_BLOCK_COUNT as usize], liquid_levels: &[u8; CHUNK_BLOCK_COUNT as usize]) -> [Block; CHUNK_BLOCK_COUNT as usize] {
     core::array::from_fn(|i| {
         Block {
             x: (i % CHUNK_SIZE as usize) as u8,
@@ -97,11 +429,15 @@ fn convert_layer_to_aos(layer: BlockArray) -> [Block; CHUNK_BLOCK_COUNT as usize
             block_id: layer.block_id[i] as u32,
             block_type: layer.block_type[i] as u8,
             texture_index: layer.texture_index[i],
+            sky_light: sky_light[i],
+            block_light: block_light[i],
+            liquid_level: liquid_levels[i],
+            _padding: 0,
         }
     })
 }
 
-fn generate_block_id(height: f32, world_y: f32, i: usize, layer: &mut BlockArray, biome_config: &BiomeConfig) -> bool {
+fn generate_block_id(height: f32, world_y: f32, i: usize, layer: &mut BlockArray, biome_config: &BiomeConfig, sea_level: i32) -> bool {
     if height >= world_y as f32 {
         let tiles_below_surface = height as i32 - world_y as i32;
         let fg_block_id = match tiles_below_surface {
@@ -112,39 +448,80 @@ fn generate_block_id(height: f32, world_y: f32, i: usize, layer: &mut BlockArray
         layer.set_block_id_byindex(i, fg_block_id);
         layer.set_block_type_byindex(i, BlockType::Tile);
         return true;
-    } else if world_y <= 0.0 { // If above ground but below or at y0
-        // If surface level and cold, place ice
-        if world_y == 0.0 && biome_config.biome_type == BiomeTypes::Cold {
-            layer.set_block_id_byindex(i, 6); // 6 = ice
-            layer.set_block_type_byindex(i, BlockType::Tile);
-            return true;
-        } 
-
-        // If above -5 and warm, no water
-        if world_y > -5.0 && biome_config.biome_type == BiomeTypes::Warm {
-            return false;
-        }
+    } else if world_y <= sea_level as f32 { // If above ground but below or at sea level
+        let fluid = &biome_config.fluid;
 
-        // Always return ice if freezing
-        if biome_config.biome_type == BiomeTypes::Freezing {
-            layer.set_block_id_byindex(i, 6); // 6 = ice
+        // Freeze the top layer if the biome wants it
+        if world_y == sea_level as f32 && let Some(freeze_block) = fluid.freeze_block {
+            layer.set_block_id_byindex(i, freeze_block);
             layer.set_block_type_byindex(i, BlockType::Tile);
             return true;
         }
 
-        // Never place water if hot
-        if biome_config.biome_type == BiomeTypes::Hot {
+        // Keep the band right below sea level dry
+        if world_y > (sea_level - fluid.dry_depth as i32) as f32 {
             return false;
         }
 
-        // Just place water if no other case fulfilled
-        layer.set_block_id_byindex(i, 3); // 3 = watar
+        // Some biomes have no fluid at all
+        let Some(fluid_block) = fluid.fluid_block else {
+            return false;
+        };
+
+        layer.set_block_id_byindex(i, fluid_block);
         layer.set_block_type_byindex(i, BlockType::Tile);
         return true;
     }
     return false;
 }
 
+// Deep caves may be flooded, everything else carved stays air
+fn generate_cave_fluid(depth: i32, i: usize, layer: &mut BlockArray, biome_config: &BiomeConfig) -> bool {
+    let Some(lava) = &biome_config.fluid.lava else {
+        return false;
+    };
+
+    if depth < lava.min_depth as i32 {
+        return false;
+    }
+
+    layer.set_block_id_byindex(i, lava.block);
+    layer.set_block_type_byindex(i, BlockType::Tile);
+    true
+}
+
+// The surface block has the sky behind it, the layers below get walls if the biome has them
+fn generate_wall_id(height: f32, world_y: f32, i: usize, layer: &mut BlockArray, biome_config: &BiomeConfig) -> bool {
+    if height < world_y {
+        return false;
+    }
+
+    let tiles_below_surface = height as i32 - world_y as i32;
+    let wall_id = match tiles_below_surface {
+        0 => None,
+        1..=5 => biome_config.subsurface_wall,
+        _ => biome_config.base_wall,
+    };
+
+    let Some(wall_id) = wall_id else {
+        return false;
+    };
+
+    layer.set_block_id_byindex(i, wall_id);
+    layer.set_block_type_byindex(i, BlockType::Wall);
+    true
+}
+
+fn is_cave(bands: &[CaveBand], depth: i32, worm_cave_noise: u8, cavern_noise: u8) -> bool {
+    let worm_cave_noise = unpack_signed_noise(worm_cave_noise);
+    let cavern_noise = unpack_signed_noise(cavern_noise);
+
+    bands.iter().any(|band| {
+        let within_band = depth >= band.min_depth as i32 && band.max_depth.is_none_or(|max_depth| depth <= max_depth as i32);
+        within_band && (worm_cave_noise.abs() < band.worm_width || cavern_noise > band.cavern_threshold)
+    })
+}
+
 fn apply_blending(height: f32, generated_height: f32, blending_mode: &BlendingMode) -> f32 {
     match blending_mode {
         BlendingMode::Mix => return height + generated_height,
@@ -197,4 +574,40 @@ fn get_terrain_heights(chunk_world_pos: &IVec2, biome_map: &BiomeMap, noise_samp
     };
 
     heights
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_chunk_keeps_blocks_and_how_long_updates_had_left() {
+        let mut chunk = Chunk::empty();
+        chunk.set_block(ChunkRelativePos::new(3, 4), LayerType::Foreground, BlockType::Tile, 3, 0);
+        chunk.set_liquid_level(ChunkRelativePos::new(3, 4), 7);
+        chunk.schedule_update(ChunkRelativePos::new(5, 6), 130);
+
+        let loaded = Chunk::from_bytes(&chunk.to_bytes(100), 5).unwrap();
+
+        assert_eq!(loaded.get_block(ChunkRelativePos::new(3, 4), LayerType::Foreground), BlockState::new(BlockType::Tile, 3));
+        assert_eq!(loaded.get_liquid_level(ChunkRelativePos::new(3, 4)), 7);
+        assert_eq!(loaded.get_total_block_count(), chunk.get_total_block_count());
+        assert_eq!(loaded.scheduled_updates, vec![ScheduledUpdate { index: 6 * CHUNK_SIZE as u16 + 5, due_tick: 35 }]);
+    }
+
+    #[test]
+    fn stored_chunk_keeps_its_tile_entities() {
+        let mut chunk = Chunk::empty();
+        chunk.insert_tile_entity(ChunkRelativePos::new(1, 2), TileEntity::Sign { text: "hello".to_string() });
+
+        let loaded = Chunk::from_bytes(&chunk.to_bytes(0), 0).unwrap();
+
+        assert_eq!(loaded.get_tile_entity(ChunkRelativePos::new(1, 2)), Some(&TileEntity::Sign { text: "hello".to_string() }));
+        assert_eq!(loaded.get_tile_entity_count(), 1);
+    }
+
+    #[test]
+    fn other_files_are_not_read_as_chunks() {
+        assert!(Chunk::from_bytes(b"SCHM\x01\x00", 0).is_err());
+        assert!(Chunk::from_bytes(b"CHNK\x63\x00", 0).is_err());
+    }
+}
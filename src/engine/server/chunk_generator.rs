@@ -4,23 +4,29 @@ use glam::IVec2;
 use rayon::iter::{IntoParallelRefIterator};
 use rayon::iter::ParallelIterator;
 
-use crate::engine::server::{biome::BiomeRegistry, chunk::Chunk, constants::{CHUNK_BLOCK_COUNT, GPU_CHUNKGEN_THRESHOLD, PARALLEL_CHUNKGEN_THRESHOLD}, data::schema_definitions::DimensionSchema, noise::noise_sampler::NoiseSampler};
+use crate::engine::server::{biome::BiomeRegistry, chunk::Chunk, feature::FeaturePlacement, constants::{CHUNK_BLOCK_COUNT, GPU_CHUNKGEN_THRESHOLD, PARALLEL_CHUNKGEN_THRESHOLD}, data::schema_definitions::{DimensionSchema, OreSchema}, noise::noise_sampler::NoiseSampler};
 
 pub type ThreadlocalDimensionSchema = Arc<DimensionSchema>;
 
+// A finished chunk with the feature blocks that belong to its neighbours
+pub type GeneratedChunk = (Chunk, IVec2, Vec<FeaturePlacement>);
+
 pub struct ChunkGenerator {
     chunks_awaiting_generation: HashSet<IVec2>,
     chunkpos_sender: Sender<Generate>,
 }
 
 impl ChunkGenerator {
-    pub fn new(biome_registry: BiomeRegistry, dimension_schema: DimensionSchema, dimension_seed: i32) -> (ChunkGenerator, Receiver<(Chunk, IVec2)>) {
-        let (generator_sender, generator_listener) = std::sync::mpsc::channel::<(Chunk, IVec2)>();
+    pub fn new(biome_registry: BiomeRegistry, ores: Vec<OreSchema>, dimension_schema: DimensionSchema, dimension_seed: i32) -> (ChunkGenerator, Receiver<GeneratedChunk>) {
+        let (generator_sender, generator_listener) = std::sync::mpsc::channel::<GeneratedChunk>();
         let (chunkpos_sender, chunkpos_listener) = std::sync::mpsc::channel::<Generate>();
 
         let arc_registry = Arc::new(RwLock::new(biome_registry));
         let thread_registry = arc_registry.clone();
 
+        let thread_ores = Arc::new(ores);
+        let sea_level = dimension_schema.sea_level;
+
         let arc_dimension = Arc::new(dimension_schema);
         let thread_dimension = arc_dimension.clone();
 
@@ -48,8 +54,8 @@ impl ChunkGenerator {
 
                         let batch_size = batch.len();
 
-                        let generated_chunks: Vec<(IVec2, Chunk)> = {
-                            let mut chunks: Vec<(IVec2, Chunk)> = Vec::new();
+                        let generated_chunks: Vec<(IVec2, (Chunk, Vec<FeaturePlacement>))> = {
+                            let mut chunks: Vec<(IVec2, (Chunk, Vec<FeaturePlacement>))> = Vec::new();
 
                             let registry_guard = thread_registry.read().unwrap();
                             let biome_map = &registry_guard.biome_map; 
@@ -65,21 +71,21 @@ impl ChunkGenerator {
                             if batch_size >= PARALLEL_CHUNKGEN_THRESHOLD {
                                 println!("Processing large batch of {} chunks in parallel.", batch_size);
                                 chunks = batch.par_iter()
-                                    .map(|&coords| (coords, Chunk::generate_chunk(&coords, biome_map, &thread_noise_sampler, dimension_seed))).collect();
+                                    .map(|&coords| (coords, Chunk::generate_chunk(&coords, biome_map, &thread_noise_sampler, &thread_ores, sea_level, dimension_seed))).collect();
                             }
 
                             // Fallback - simple sequential generation
                             if batch_size < PARALLEL_CHUNKGEN_THRESHOLD {
                                 println!("Processing small batch of {} chunks sequentially.", batch_size);
                                 chunks = batch.into_iter()
-                                    .map(|coords| (coords, Chunk::generate_chunk(&coords, &biome_map, &thread_noise_sampler, dimension_seed))).collect();
+                                    .map(|coords| (coords, Chunk::generate_chunk(&coords, &biome_map, &thread_noise_sampler, &thread_ores, sea_level, dimension_seed))).collect();
                             }
 
                             chunks
                         };
 
-                        for (pos, chunk) in generated_chunks {
-                            let _ = generator_sender.send((chunk, pos));
+                        for (pos, (chunk, overflow)) in generated_chunks {
+                            let _ = generator_sender.send((chunk, pos, overflow));
                         }
                     },
 
@@ -91,7 +97,7 @@ impl ChunkGenerator {
                         let biome_map = &registry_guard.biome_map; 
 
                         while chunks_generated < chunk_limit {
-                            let _: Chunk = Chunk::generate_chunk(&chunk_pos, &biome_map, &thread_noise_sampler, dimension_seed); 
+                            let _ = Chunk::generate_chunk(&chunk_pos, &biome_map, &thread_noise_sampler, &thread_ores, sea_level, dimension_seed); 
                             chunks_generated += 1;
 
                             chunk_pos.x += 1;
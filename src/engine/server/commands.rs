@@ -1,4 +1,8 @@
-use crate::engine::{command_registry::{error_dimension_not_found, error_not_enough_arguments, error_wrong_type, CommandDependency, CommandEnvironment, DebugCommand}, common::ServerPacket, server::{constants::CHUNK_BLOCK_COUNT, world::Dimension}};
+use std::time::Duration;
+
+use glam::IVec2;
+
+use crate::engine::{bot::load_test::spawn_load_test, command_args::{ArgSpec, ArgType, CommandArgs}, command_registry::{error_dimension_not_found, error_player_not_found, error_wrong_environment, CommandDependency, CommandEnvironment, CommandResult, DebugCommand, OutputSink, PermissionLevel}, common::{ServerPacket, WorldTime}, server::{common::{BlockType, LayerType}, constants::{TICK_RATE, WORLD_SCAN_MAX_BLOCKS}, schematic::{list_schematics, Mirror, Schematic, SchematicTransform}, script::CommandScript, server::Server, tile_entity::{BlockStack, TileEntity}, weather, world::Dimension, world_edit::{self, EditOutcome, EditRegion}}};
 
 pub fn create_server_commands() -> Vec<DebugCommand> {
     let mut commands = Vec::new();
@@ -7,10 +11,15 @@ pub fn create_server_commands() -> Vec<DebugCommand> {
         name: "stopserver",
         aliases: &["exitserver"],
         description: "Stops the server.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Server(server) = dependency {
-                server.stop();
-            }
+        args: &[],
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, _args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            server.stop();
+            CommandResult::success_with("Server stopped")
         },
         command_environment: CommandEnvironment::Server,
     });
@@ -18,13 +27,16 @@ pub fn create_server_commands() -> Vec<DebugCommand> {
     commands.push(DebugCommand {
         name: "switchcompressionstate",
         aliases: &["scs"],
-        description: "Switches whether the server compresses or does not compress sent data.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Server(server) = dependency {
-                server.compress_sent_data = !server.compress_sent_data;
+        description: "Switches whether the server compresses or does not compress sent data. Toggles if no state is given.",
+        args: const { &[ArgSpec::optional("enabled", ArgType::Boolean)] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
 
-                println!("Compress sent data: {}", server.compress_sent_data)
-            }
+            server.compress_sent_data = args.get_bool("enabled").unwrap_or(!server.compress_sent_data);
+            CommandResult::success_with(format!("Compress sent data: {}", server.compress_sent_data))
         },
         command_environment: CommandEnvironment::Server,
     });
@@ -33,13 +45,19 @@ pub fn create_server_commands() -> Vec<DebugCommand> {
         name: "dimensions",
         aliases: &["dims"],
         description: "Returns all dimension names.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Server(server) = dependency {
-                let keys = server.get_dimension_keys();
-                for key in keys {
-                    println!("{}", key);
-                }
+        args: &[],
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, _args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let keys: Vec<String> = server.get_dimension_keys().cloned().collect();
+            for key in &keys {
+                output.write_line(key);
             }
+
+            CommandResult::success().with_data(serde_json::json!(keys))
         },
         command_environment: CommandEnvironment::Server,
     });
@@ -47,47 +65,49 @@ pub fn create_server_commands() -> Vec<DebugCommand> {
     commands.push(DebugCommand {
         name: "resetdimension",
         aliases: &["rdim"],
-        description: "Returns all dimension names.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Server(server) = dependency {
-                let Some(dimension_arg) = _args.first() else {
-                    error_not_enough_arguments();
-                    return;
-                };
+        description: "Regenerates a dimension from its schema with a new seed.",
+        args: const { &[ArgSpec::required("dimension", ArgType::Dimension), ArgSpec::optional("seed", ArgType::Integer)] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
 
-                let Some(dimension) = server.get_dimension(dimension_arg) else {
-                    error_dimension_not_found();
-                    return;
-                };
+            let Some(dimension) = server.get_dimension(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
 
-                let mut seed: i32 = 0;
-                if let Some(arg) = _args.get(1) {
-                    match arg.parse::<i32>() {
-                        Ok(seed_in) => seed = seed_in,
-                        Err(_) => {
-                            error_wrong_type();
-                            return;
-                        }
-                    }
-                } else {
-                    seed = fastrand::i32(..);
-                    println!("No seed provided, using random: {seed}");
+            let seed = match args.get_i32("seed") {
+                Some(seed) => seed,
+                None => {
+                    let seed = fastrand::i32(..);
+                    output.write_line(&format!("No seed provided, using random: {seed}"));
+                    seed
                 }
+            };
 
-                let name = dimension.name.clone();
-                server.dimensions.remove(&name);
-                let schema = server.get_dimension_schema(&name);
-
-                match schema {
-                    Some(dimension_schema) => {
-                        let mut new_dimension = Dimension::from_schema(dimension_schema, seed);
-                        new_dimension.name = name.clone();
-                        server.dimensions.insert(name, new_dimension);
-                    }
-                    None => println!("No dimension found under the name: {}", &name)
-                }
-                server.send_packet(ServerPacket::ReloadChunks);
+            // Otherwise the old chunks would be loaded back in over the new seed
+            if let Err(error) = dimension.delete_stored_chunks() {
+                return CommandResult::failure(error);
             }
+
+            let name = dimension.name.clone();
+            server.dimensions.remove(&name);
+            let schema = server.get_dimension_schema(&name);
+
+            let result = match schema {
+                Some(dimension_schema) => {
+                    let mut new_dimension = Dimension::from_schema(dimension_schema, seed);
+                    new_dimension.name = name.clone();
+                    server.dimensions.insert(name.clone(), new_dimension);
+                    CommandResult::success_with(format!("Reset dimension {name} with seed {seed}"))
+                        .with_data(serde_json::json!({ "dimension": name, "seed": seed }))
+                }
+                None => CommandResult::failure(format!("No dimension found under the name: {}", &name))
+            };
+            server.send_packet(ServerPacket::ReloadChunks);
+
+            result
         },
         command_environment: CommandEnvironment::Server,
     });
@@ -96,36 +116,1000 @@ pub fn create_server_commands() -> Vec<DebugCommand> {
         name: "testchunkspeed",
         aliases: &["tcs"],
         description: "Generates chunks for 5 seconds then returns the count.",
-        execute: |dependency, _args| {
-            if let CommandDependency::Server(server) = dependency {  
-                let Some(dimension_arg) = _args.first() else {
-                    error_not_enough_arguments();
-                    return;
-                };
+        args: const { &[ArgSpec::required("dimension", ArgType::Dimension), ArgSpec::with_default("limit", ArgType::Unsigned, "10000")] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
 
-                let Some(dimension) = server.get_dimension(dimension_arg) else {
-                    error_dimension_not_found();
-                    return;
-                };
+            let Some(dimension) = server.get_dimension(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let chunk_limit = args.get_u32("limit").unwrap_or_default();
+
+            dimension.chunk_load_speed_test(chunk_limit);
+            // The generator thread prints the timings once it is done
+            CommandResult::success_with(format!("Started generating {chunk_limit} chunks, results will be printed on the server console"))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "loadtest",
+        aliases: &["lt"],
+        description: "Connects headless bots that walk around and edit blocks, then reports TPS and bandwidth.",
+        args: const { &[ArgSpec::required("bots", ArgType::Unsigned), ArgSpec::with_default("seconds", ArgType::Unsigned, "30")] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
 
-                let mut chunk_limit: u32 = 10000;
-                if let Some(arg) = _args.get(1) {
-                    match arg.parse::<u32>() {
-                        Ok(limit) => chunk_limit = limit,
-                        Err(_) => {
-                            error_wrong_type();
-                            return;
-                        }
-                    }
+            let bot_count = args.get_u32("bots").unwrap_or_default() as usize;
+            let seconds = args.get_u32("seconds").unwrap_or_default() as u64;
+
+            spawn_load_test(server, bot_count, Duration::from_secs(seconds));
+            // The bots run on their own thread and print a report when done
+            CommandResult::success_with(format!("Started load test with {bot_count} bots, the report will be printed on the server console"))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "tickrate",
+        aliases: &["tps"],
+        description: "Prints the measured server ticks per second.",
+        args: &[],
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, _args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let tps = server.get_ticks_per_second();
+            CommandResult::success_with(format!("Server TPS: {:.2} (target {TICK_RATE})", tps))
+                .with_data(serde_json::json!({ "ticks_per_second": tps, "target": TICK_RATE }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "clients",
+        aliases: &["players", "list"],
+        description: "Lists connected players and their latency. UUIDs are only shown on the console.",
+        args: &[],
+        permission_level: PermissionLevel::Moderator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let show_uuids = args.get_source().level == PermissionLevel::Console;
+            let mut players = Vec::new();
+            for client in server.get_clients() {
+                let mut player = serde_json::json!({
+                    "nickname": client.player_nickname,
+                    "dimension": client.dimension,
+                });
+                if show_uuids {
+                    output.write_line(&format!("\"{}\" [{:016x}] {}", client.player_nickname, client.player_uuid, client.latency.describe()));
+                    player["uuid"] = serde_json::json!(format!("{:016x}", client.player_uuid));
                 } else {
-                    println!("No limit provided, defaulting to {chunk_limit}");
+                    output.write_line(&format!("\"{}\" {}", client.player_nickname, client.latency.describe()));
+                }
+                players.push(player);
+            }
+
+            CommandResult::success_with(format!("{} player(s) connected", players.len()))
+                .with_data(serde_json::json!(players))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "op",
+        aliases: &["promote"],
+        description: "Raises the permission level of a player, by nickname or UUID.",
+        args: const { &[ArgSpec::required("player", ArgType::Word), ArgSpec::with_default("level", ArgType::Choice(&["moderator", "operator"]), "operator")] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let name = args.get_str("player").unwrap_or_default();
+            let Some((uuid, nickname)) = server.resolve_player(name) else {
+                return error_player_not_found(name);
+            };
+
+            let level = args.get_str("level").and_then(PermissionLevel::from_name).unwrap_or(PermissionLevel::Operator);
+            match server.set_permission_level(uuid, &nickname, level) {
+                Ok(()) => CommandResult::success_with(format!("\"{nickname}\" is now {}", level.name())),
+                Err(error) => CommandResult::failure(error),
+            }
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "deop",
+        aliases: &["demote"],
+        description: "Resets a player back to the player permission level.",
+        args: const { &[ArgSpec::required("player", ArgType::Word)] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let name = args.get_str("player").unwrap_or_default();
+            let Some((uuid, nickname)) = server.resolve_player(name) else {
+                return error_player_not_found(name);
+            };
+
+            match server.set_permission_level(uuid, &nickname, PermissionLevel::Player) {
+                Ok(()) => CommandResult::success_with(format!("\"{nickname}\" is now player")),
+                Err(error) => CommandResult::failure(error),
+            }
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "ops",
+        aliases: &["oplist"],
+        description: "Lists players with a raised permission level. UUIDs are only shown on the console.",
+        args: &[],
+        permission_level: PermissionLevel::Moderator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let show_uuids = args.get_source().level == PermissionLevel::Console;
+            let mut entries: Vec<_> = server.get_ops().get_entries().collect();
+            entries.sort_by(|a, b| b.level.cmp(&a.level).then(a.nickname.cmp(&b.nickname)));
+
+            let mut list = Vec::new();
+            for entry in &entries {
+                let mut op = serde_json::json!({ "nickname": entry.nickname, "level": entry.level });
+                if show_uuids {
+                    output.write_line(&format!("\"{}\" [{}] {}", entry.nickname, entry.uuid, entry.level.name()));
+                    op["uuid"] = serde_json::json!(entry.uuid);
+                } else {
+                    output.write_line(&format!("\"{}\" {}", entry.nickname, entry.level.name()));
+                }
+                list.push(op);
+            }
+
+            CommandResult::success_with(format!("{} op(s)", entries.len()))
+                .with_data(serde_json::json!(list))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "exec",
+        aliases: &["run"],
+        description: "Runs a command script from the scripts directory. Stops at the first failing command unless told otherwise.",
+        args: const { &[ArgSpec::required("script", ArgType::Word), ArgSpec::with_default("stop_on_error", ArgType::Boolean, "true")] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let name = args.get_str("script").unwrap_or_default();
+            let stop_on_error = args.get_bool("stop_on_error").unwrap_or(true);
+
+            // Console level scripts are started from the startup script only, exec never grants more than operator
+            let mut source = args.get_source();
+            source.level = source.level.min(PermissionLevel::Operator);
+            let script = match CommandScript::load(name, source, stop_on_error) {
+                Ok(script) => script,
+                Err(error) => return CommandResult::failure(error),
+            };
+
+            let command_count = script.command_count();
+            match server.start_script(script) {
+                Ok(()) => CommandResult::success_with(format!("Started script {name} with {command_count} command(s), output will be printed on the server console")),
+                Err(error) => CommandResult::failure(error),
+            }
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "abortscripts",
+        aliases: &["stopscripts"],
+        description: "Stops all running command scripts.",
+        args: &[],
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, _args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let count = server.abort_scripts();
+            CommandResult::success_with(format!("Stopped {count} script(s)"))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "setblock",
+        aliases: &["sb"],
+        description: "Sets a single block, e.g. setblock overworld 10 -4 foreground tile:2",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x", ArgType::Integer),
+            ArgSpec::required("y", ArgType::Integer),
+            ArgSpec::required("layer", ArgType::Choice(&LayerType::NAMES)),
+            ArgSpec::required("block", ArgType::Block),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let dimension_name = args.get_str("dimension").unwrap_or_default().to_string();
+            let Some(dimension) = server.dimensions.get_mut(&dimension_name) else {
+                return error_dimension_not_found();
+            };
+
+            let position = IVec2::new(args.get_i32("x").unwrap_or_default(), args.get_i32("y").unwrap_or_default());
+            let region = EditRegion::from_corners(position, position);
+            let outcome = world_edit::fill(dimension, region, layer_arg(args), args.get_block("block").unwrap(), None);
+            finish_edit(server, args, &dimension_name, outcome, "Set")
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "fill",
+        aliases: &[],
+        description: "Fills the box between two corners with a block.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x1", ArgType::Integer),
+            ArgSpec::required("y1", ArgType::Integer),
+            ArgSpec::required("x2", ArgType::Integer),
+            ArgSpec::required("y2", ArgType::Integer),
+            ArgSpec::required("layer", ArgType::Choice(&LayerType::NAMES)),
+            ArgSpec::required("block", ArgType::Block),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let region = region_arg(args);
+            if let Err(error) = region.check_size() {
+                return CommandResult::failure(error);
+            }
+
+            let dimension_name = args.get_str("dimension").unwrap_or_default().to_string();
+            let Some(dimension) = server.dimensions.get_mut(&dimension_name) else {
+                return error_dimension_not_found();
+            };
+
+            let outcome = world_edit::fill(dimension, region, layer_arg(args), args.get_block("block").unwrap(), None);
+            finish_edit(server, args, &dimension_name, outcome, "Filled")
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "replace",
+        aliases: &[],
+        description: "Replaces one block with another between two corners. A block without an id matches any id.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x1", ArgType::Integer),
+            ArgSpec::required("y1", ArgType::Integer),
+            ArgSpec::required("x2", ArgType::Integer),
+            ArgSpec::required("y2", ArgType::Integer),
+            ArgSpec::required("layer", ArgType::Choice(&LayerType::NAMES)),
+            ArgSpec::required("from", ArgType::Block),
+            ArgSpec::required("to", ArgType::Block),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let region = region_arg(args);
+            if let Err(error) = region.check_size() {
+                return CommandResult::failure(error);
+            }
+
+            let dimension_name = args.get_str("dimension").unwrap_or_default().to_string();
+            let Some(dimension) = server.dimensions.get_mut(&dimension_name) else {
+                return error_dimension_not_found();
+            };
+
+            let outcome = world_edit::fill(dimension, region, layer_arg(args), args.get_block("to").unwrap(), args.get_block("from"));
+            finish_edit(server, args, &dimension_name, outcome, "Replaced")
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "clone",
+        aliases: &["copy"],
+        description: "Copies the box between two corners so its lowest corner lands on the destination. Copies every layer unless one is given.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x1", ArgType::Integer),
+            ArgSpec::required("y1", ArgType::Integer),
+            ArgSpec::required("x2", ArgType::Integer),
+            ArgSpec::required("y2", ArgType::Integer),
+            ArgSpec::required("to_x", ArgType::Integer),
+            ArgSpec::required("to_y", ArgType::Integer),
+            ArgSpec::optional("layer", ArgType::Choice(&LayerType::NAMES)),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let region = region_arg(args);
+            if let Err(error) = region.check_size() {
+                return CommandResult::failure(error);
+            }
+
+            let dimension_name = args.get_str("dimension").unwrap_or_default().to_string();
+            let Some(dimension) = server.dimensions.get_mut(&dimension_name) else {
+                return error_dimension_not_found();
+            };
+
+            let destination = IVec2::new(args.get_i32("to_x").unwrap_or_default(), args.get_i32("to_y").unwrap_or_default());
+            let layers = match args.get_str("layer") {
+                Some(_) => vec![layer_arg(args)],
+                None => LayerType::ALL.to_vec(),
+            };
+
+            let outcome = match world_edit::clone(dimension, region, destination, &layers) {
+                Ok(outcome) => outcome,
+                Err(error) => return CommandResult::failure(error),
+            };
+            finish_edit(server, args, &dimension_name, outcome, "Cloned")
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "schematicsave",
+        aliases: &["schemsave"],
+        description: "Saves the box between two corners, across every layer, to a schematic file.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x1", ArgType::Integer),
+            ArgSpec::required("y1", ArgType::Integer),
+            ArgSpec::required("x2", ArgType::Integer),
+            ArgSpec::required("y2", ArgType::Integer),
+            ArgSpec::required("name", ArgType::Word),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.get_dimension(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let schematic = match Schematic::capture(dimension, region_arg(args)) {
+                Ok(schematic) => schematic,
+                Err(error) => return CommandResult::failure(error),
+            };
+
+            match schematic.save(args.get_str("name").unwrap_or_default()) {
+                Ok(path) => CommandResult::success_with(format!("Saved {}x{} schematic to {}", schematic.width, schematic.height, path.display()))
+                    .with_data(serde_json::json!({ "path": path, "width": schematic.width, "height": schematic.height })),
+                Err(error) => CommandResult::failure(error),
+            }
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "schematicpaste",
+        aliases: &["schempaste"],
+        description: "Pastes a schematic with its lowest corner at the given position, optionally rotated clockwise and mirrored.",
+        args: const { &[
+            ArgSpec::required("name", ArgType::Word),
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x", ArgType::Integer),
+            ArgSpec::required("y", ArgType::Integer),
+            ArgSpec::with_default("rotation", ArgType::Choice(&["0", "90", "180", "270"]), "0"),
+            ArgSpec::with_default("mirror", ArgType::Choice(&["none", "x", "y"]), "none"),
+            ArgSpec::with_default("skip_air", ArgType::Boolean, "false"),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let schematic = match Schematic::load(args.get_str("name").unwrap_or_default()) {
+                Ok(schematic) => schematic,
+                Err(error) => return CommandResult::failure(error),
+            };
+
+            if let Err(error) = schematic.check_size() {
+                return CommandResult::failure(error);
+            }
+
+            let transform = SchematicTransform {
+                quarter_turns: (args.get_str("rotation").unwrap_or_default().parse::<u16>().unwrap_or(0) / 90) as u8,
+                mirror: match args.get_str("mirror") {
+                    Some("x") => Mirror::X,
+                    Some("y") => Mirror::Y,
+                    _ => Mirror::None,
+                },
+            };
+            let schematic = schematic.transformed(transform);
+
+            let dimension_name = args.get_str("dimension").unwrap_or_default().to_string();
+            let Some(dimension) = server.dimensions.get_mut(&dimension_name) else {
+                return error_dimension_not_found();
+            };
+
+            let origin = IVec2::new(args.get_i32("x").unwrap_or_default(), args.get_i32("y").unwrap_or_default());
+            let outcome = match schematic.paste(dimension, origin, args.get_bool("skip_air").unwrap_or(false)) {
+                Ok(outcome) => outcome,
+                Err(error) => return CommandResult::failure(error),
+            };
+            finish_edit(server, args, &dimension_name, outcome, "Pasted")
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "schematics",
+        aliases: &["schems"],
+        description: "Lists saved schematics and the ones shipped in the data directory.",
+        args: &[],
+        permission_level: PermissionLevel::Operator,
+        execute: |_dependency, _args, output| {
+            let names = list_schematics();
+            for name in &names {
+                output.write_line(name);
+            }
+
+            CommandResult::success_with(format!("{} schematic(s)", names.len()))
+                .with_data(serde_json::json!(names))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "orecount",
+        aliases: &["ores"],
+        description: "Counts the ores of a dimension in a region of the foreground, to help balance ore files.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x1", ArgType::Integer),
+            ArgSpec::required("y1", ArgType::Integer),
+            ArgSpec::required("x2", ArgType::Integer),
+            ArgSpec::required("y2", ArgType::Integer),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.get_dimension(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let region = region_arg(args);
+            if let Err(error) = region.check_limit(WORLD_SCAN_MAX_BLOCKS) {
+                return CommandResult::failure(error);
+            }
+
+            let ores = dimension.get_ores();
+            let mut counts = vec![0u64; ores.len()];
+            let (mut scanned, mut unloaded) = (0u64, 0u64);
+
+            for world_pos in region.positions() {
+                let Some(block) = dimension.get_block(world_pos, LayerType::Foreground) else {
+                    unloaded += 1;
+                    continue;
+                };
+
+                scanned += 1;
+                if block.block_type != BlockType::Tile {
+                    continue;
                 }
+                if let Some(index) = ores.iter().position(|ore| ore.block == block.block_id) {
+                    counts[index] += 1;
+                }
+            }
+
+            for (ore, count) in ores.iter().zip(&counts) {
+                let share = if scanned > 0 { *count as f64 / scanned as f64 * 100.0 } else { 0.0 };
+                output.write_line(&format!("{}: {count} ({share:.3}%)", ore.name));
+            }
+
+            let mut message = format!("Scanned {scanned} block(s)");
+            if unloaded > 0 {
+                message.push_str(&format!(", skipped {unloaded} in unloaded chunks"));
+            }
+
+            let ore_counts: serde_json::Map<String, serde_json::Value> = ores.iter()
+                .zip(&counts)
+                .map(|(ore, count)| (ore.name.clone(), serde_json::json!(count)))
+                .collect();
+
+            CommandResult::success_with(message).with_data(serde_json::json!({
+                "dimension": dimension.name,
+                "ores": ore_counts,
+                "scanned": scanned,
+                "unloaded": unloaded,
+            }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "liquids",
+        aliases: &[],
+        description: "Lists the liquids of a dimension and how many cells are still flowing.",
+        args: const { &[ArgSpec::required("dimension", ArgType::Dimension)] },
+        permission_level: PermissionLevel::Moderator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.get_dimension(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let simulation = dimension.get_liquids();
+            for liquid in simulation.get_liquids() {
+                output.write_line(&format!("{}: tile:{}, flows every {} tick(s)", liquid.name, liquid.block, liquid.flow_interval));
+            }
+
+            let active = simulation.get_active_count();
+            CommandResult::success_with(format!("{active} active liquid cell(s)")).with_data(serde_json::json!({
+                "dimension": dimension.name,
+                "liquids": simulation.get_liquids().iter().map(|liquid| liquid.name.clone()).collect::<Vec<String>>(),
+                "active": active,
+            }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "time",
+        aliases: &[],
+        description: "Prints or changes the time of a dimension. Set takes ticks into the day or sunrise, noon, sunset or midnight, add takes ticks.",
+        args: const { &[
+            ArgSpec::required("action", ArgType::Choice(&["query", "set", "add"])),
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::optional("value", ArgType::Word),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.dimensions.get_mut(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let world_time = dimension.get_world_time();
+            let value = args.get_str("value");
+            match args.get_str("action").unwrap_or_default() {
+                "set" => {
+                    let Some(time_of_day) = value.and_then(|value| parse_time_of_day(value, world_time.day_length)) else {
+                        return CommandResult::failure("Set needs a number of ticks into the day, or one of sunrise, noon, sunset and midnight");
+                    };
+                    let Some(time) = world_time.get_day().checked_mul(world_time.day_length).and_then(|start| start.checked_add(time_of_day)) else {
+                        return CommandResult::failure("That time is past the last day the clock can count");
+                    };
+                    dimension.set_time(time);
+                },
+                "add" => {
+                    let Some(ticks) = value.and_then(|value| value.parse::<u64>().ok()) else {
+                        return CommandResult::failure("Add needs a number of ticks");
+                    };
+                    let Some(time) = world_time.time.checked_add(ticks) else {
+                        return CommandResult::failure(format!("Adding {ticks} ticks goes past the last day the clock can count"));
+                    };
+                    dimension.set_time(time);
+                },
+                _ => {},
+            }
+
+            let world_time = dimension.get_world_time();
+            let name = dimension.name.clone();
+            server.send_world_time();
+
+            CommandResult::success_with(format!("It is {} on day {} in {name}", format_clock(&world_time), world_time.get_day() + 1)).with_data(serde_json::json!({
+                "dimension": name,
+                "time": world_time.time,
+                "day": world_time.get_day(),
+                "time_of_day": world_time.get_time_of_day(),
+                "day_length": world_time.day_length,
+                "daylight": world_time.get_daylight(),
+            }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "weather",
+        aliases: &[],
+        description: "Prints, starts or clears the weather of a dimension. Start forms a front centred on x, or at 0.",
+        args: const { &[
+            ArgSpec::required("action", ArgType::Choice(&["query", "start", "clear"])),
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::optional("x", ArgType::Integer),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.dimensions.get_mut(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            if !dimension.get_weather().has_weather() {
+                return CommandResult::failure(format!("{} has no weather", dimension.name));
+            }
+
+            match args.get_str("action").unwrap_or_default() {
+                "start" => {
+                    dimension.get_weather_mut().start_front(args.get_i32("x").unwrap_or_default() as f32);
+                },
+                "clear" => dimension.get_weather_mut().clear(),
+                _ => {},
+            }
+
+            // Fronts that formed or cleared reach clients with the next tick
+            let state = weather::get_state(dimension);
+            for front in &state.fronts {
+                output.write_line(&format!("Front {:.0} blocks wide at {:.0}x, moving {:.2} blocks per tick at {:.0}% intensity, clears in {} tick(s)",
+                    front.width, front.x, front.velocity, front.intensity * 100.0, front.remaining));
+            }
+            for span in &state.spans {
+                output.write_line(&format!("{:?} from {}x to {}x", span.precipitation, span.start, span.end));
+            }
+
+            CommandResult::success_with(format!("{} front(s) in {}", state.fronts.len(), dimension.name)).with_data(serde_json::json!({
+                "dimension": dimension.name,
+                "weather": serde_json::to_value(&state).unwrap_or_default(),
+            }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "tileentity",
+        aliases: &["te"],
+        description: "Prints the state of the tile entity at a position.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x", ArgType::Integer),
+            ArgSpec::required("y", ArgType::Integer),
+        ] },
+        permission_level: PermissionLevel::Moderator,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.get_dimension(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let position = IVec2::new(args.get_i32("x").unwrap_or_default(), args.get_i32("y").unwrap_or_default());
+            let Some(tile_entity) = dimension.get_tile_entity(position) else {
+                return CommandResult::failure(format!("No tile entity at {}, {}", position.x, position.y));
+            };
+
+            for line in tile_entity.describe(dimension.get_blocks()) {
+                output.write_line(&line);
+            }
+
+            CommandResult::success().with_data(serde_json::json!({
+                "dimension": dimension.name,
+                "x": position.x,
+                "y": position.y,
+                "tile_entity": serde_json::to_value(tile_entity).unwrap_or_default(),
+            }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "signtext",
+        aliases: &[],
+        description: "Changes what the sign at a position reads.",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x", ArgType::Integer),
+            ArgSpec::required("y", ArgType::Integer),
+            ArgSpec::required("text", ArgType::Text),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.dimensions.get_mut(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
+
+            let position = IVec2::new(args.get_i32("x").unwrap_or_default(), args.get_i32("y").unwrap_or_default());
+            let Some(TileEntity::Sign { text }) = dimension.get_tile_entity_mut(position) else {
+                return CommandResult::failure(format!("No sign at {}, {}", position.x, position.y));
+            };
+
+            *text = args.get_str("text").unwrap_or_default().to_string();
+            CommandResult::success_with(format!("Sign at {}, {} now reads \"{text}\"", position.x, position.y))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "insertblocks",
+        aliases: &[],
+        description: "Puts blocks into the container or furnace at a position, e.g. insertblocks overworld 3 7 tile:4 16",
+        args: const { &[
+            ArgSpec::required("dimension", ArgType::Dimension),
+            ArgSpec::required("x", ArgType::Integer),
+            ArgSpec::required("y", ArgType::Integer),
+            ArgSpec::required("block", ArgType::Block),
+            ArgSpec::with_default("count", ArgType::Unsigned, "1"),
+        ] },
+        permission_level: PermissionLevel::Operator,
+        execute: |dependency, args, _output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let Some(dimension) = server.dimensions.get_mut(args.get_str("dimension").unwrap_or_default()) else {
+                return error_dimension_not_found();
+            };
 
-                dimension.chunk_load_speed_test(chunk_limit);
+            let position = IVec2::new(args.get_i32("x").unwrap_or_default(), args.get_i32("y").unwrap_or_default());
+            let Some(tile_entity) = dimension.get_tile_entity_mut(position) else {
+                return CommandResult::failure(format!("No tile entity at {}, {}", position.x, position.y));
+            };
+
+            let block = args.get_block("block").unwrap().to_state();
+            let count = args.get_u32("count").unwrap_or(1);
+            let left_over = tile_entity.insert(BlockStack { block_type: block.block_type, block_id: block.block_id, count });
+            if left_over == count {
+                return CommandResult::failure(format!("The tile entity at {}, {} has no room for that block", position.x, position.y));
+            }
+
+            let mut message = format!("Inserted {} block(s)", count - left_over);
+            if left_over > 0 {
+                message.push_str(&format!(", {left_over} did not fit"));
             }
+            CommandResult::success_with(message).with_data(serde_json::json!({
+                "inserted": count - left_over,
+                "left_over": left_over,
+            }))
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "undo",
+        aliases: &[],
+        description: "Reverts your last block edits. Blocks changed by someone else since are left alone.",
+        args: const { &[ArgSpec::with_default("count", ArgType::Unsigned, "1")] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            step_history(server, args, output, false)
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "redo",
+        aliases: &[],
+        description: "Applies edits reverted by undo again. Any new edit clears what can be redone.",
+        args: const { &[ArgSpec::with_default("count", ArgType::Unsigned, "1")] },
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            step_history(server, args, output, true)
+        },
+        command_environment: CommandEnvironment::Server,
+    });
+
+    commands.push(DebugCommand {
+        name: "edithistory",
+        aliases: &["undohistory"],
+        description: "Lists your block edits that can be undone, newest first.",
+        args: &[],
+        permission_level: PermissionLevel::Player,
+        execute: |dependency, args, output| {
+            let CommandDependency::Server(server) = dependency else {
+                return error_wrong_environment();
+            };
+
+            let actor = args.get_source().actor;
+            let journal = server.get_edit_journal();
+            let operations = journal.get_undo_operations(actor);
+            for (index, operation) in operations.iter().enumerate() {
+                output.write_line(&format!("{}. {}", index + 1, operation.description));
+            }
+
+            CommandResult::success_with(format!(
+                "{} edit(s) to undo, {} to redo, using {} KiB",
+                operations.len(),
+                journal.get_redo_count(actor),
+                journal.get_memory_size(actor) / 1024,
+            ))
         },
         command_environment: CommandEnvironment::Server,
     });
 
     return commands;
-}
\ No newline at end of file
+}
+
+// Undoes or redoes up to count edits, stopping early once the history runs out
+fn step_history(server: &mut Server, args: &CommandArgs, output: &mut dyn OutputSink, redo: bool) -> CommandResult {
+    let actor = args.get_source().actor;
+    let verb = if redo { "Redid" } else { "Undid" };
+    let count = args.get_u32("count").unwrap_or(1);
+    let (mut steps, mut changed, mut conflicts, mut unloaded) = (0, 0, 0, 0);
+
+    for _ in 0..count {
+        let result = if redo { server.redo_edit(actor) } else { server.undo_edit(actor) };
+        let (description, outcome) = match result {
+            Ok(result) => result,
+            Err(error) if steps == 0 => return CommandResult::failure(error),
+            Err(_) => break,
+        };
+
+        output.write_line(&format!("{verb} \"{description}\""));
+        steps += 1;
+        changed += outcome.changes.len();
+        conflicts += outcome.conflicts;
+        unloaded += outcome.unloaded;
+    }
+
+    let mut message = format!("{verb} {steps} edit(s), {changed} block(s) changed");
+    if conflicts > 0 {
+        message.push_str(&format!(", kept {conflicts} block(s) that were changed since"));
+    }
+    if unloaded > 0 {
+        message.push_str(&format!(", skipped {unloaded} in unloaded chunks"));
+    }
+
+    CommandResult::success_with(message).with_data(serde_json::json!({
+        "steps": steps,
+        "changed": changed,
+        "conflicts": conflicts,
+        "unloaded": unloaded,
+    }))
+}
+
+// Ticks into the day, either given directly or by name
+fn parse_time_of_day(value: &str, day_length: u64) -> Option<u64> {
+    let fraction = match value.to_lowercase().as_str() {
+        "sunrise" => 0.0,
+        "noon" => 0.25,
+        "sunset" => 0.5,
+        "midnight" => 0.75,
+        _ => return value.parse::<u64>().ok().map(|ticks| ticks % day_length.max(1)),
+    };
+    Some((day_length as f64 * fraction) as u64)
+}
+
+// Time of day on a 24 hour clock, the sun rises at 06:00
+fn format_clock(world_time: &WorldTime) -> String {
+    let minutes = (world_time.get_day_fraction() as f64 * 24.0 * 60.0) as u64 + 6 * 60;
+    format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+}
+
+fn region_arg(args: &CommandArgs) -> EditRegion {
+    let first = IVec2::new(args.get_i32("x1").unwrap_or_default(), args.get_i32("y1").unwrap_or_default());
+    let second = IVec2::new(args.get_i32("x2").unwrap_or_default(), args.get_i32("y2").unwrap_or_default());
+    EditRegion::from_corners(first, second)
+}
+
+fn layer_arg(args: &CommandArgs) -> LayerType {
+    args.get_str("layer").and_then(LayerType::from_name).unwrap_or(LayerType::Foreground)
+}
+
+// Sends the changes to clients and adds them to the undo history of whoever ran the command
+fn finish_edit(server: &mut Server, args: &CommandArgs, dimension_name: &str, outcome: EditOutcome, verb: &str) -> CommandResult {
+    server.broadcast_block_changes(dimension_name, &outcome.changes);
+
+    let mut message = format!("{verb} {} block(s) in {dimension_name}", outcome.changes.len());
+    server.record_edit(args.get_source().actor, &message, dimension_name, &outcome.changes);
+    if outcome.unloaded > 0 {
+        message.push_str(&format!(", skipped {} in unloaded chunks", outcome.unloaded));
+    }
+    if outcome.obstructed > 0 {
+        message.push_str(&format!(", {} sprite(s) did not fit or had nothing to rest on", outcome.obstructed));
+    }
+
+    CommandResult::success_with(message).with_data(serde_json::json!({
+        "dimension": dimension_name,
+        "changed": outcome.changes.len(),
+        "unloaded": outcome.unloaded,
+        "obstructed": outcome.obstructed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{command_registry::{handle_server_command, parse_command_line, CommandSource}, common::get_data_path, server::constants::DEFAULT_DIMENSION};
+
+    fn server_with_dimension() -> Server {
+        let mut server = Server::empty(None);
+        let schemas = Dimension::load_dimensions(&get_data_path()).unwrap();
+        let schema = schemas.iter().find(|schema| schema.name == DEFAULT_DIMENSION).unwrap();
+        server.dimensions.insert(DEFAULT_DIMENSION.to_string(), Dimension::from_schema(schema, 1));
+        server
+    }
+
+    fn run(server: &mut Server, line: &str) -> CommandResult {
+        let mut lines: Vec<String> = Vec::new();
+        handle_server_command(server, &parse_command_line(line).unwrap(), CommandSource::console(), &mut lines)
+    }
+
+    fn time(server: &Server) -> u64 {
+        server.dimensions[DEFAULT_DIMENSION].get_world_time().time
+    }
+
+    #[test]
+    fn time_that_would_overflow_is_refused() {
+        let mut server = server_with_dimension();
+        assert!(run(&mut server, &format!("time add {DEFAULT_DIMENSION} 100")).is_success());
+
+        assert!(!run(&mut server, &format!("time add {DEFAULT_DIMENSION} {}", u64::MAX)).is_success());
+        assert_eq!(time(&server), 100);
+
+        // The last day the clock can count is cut short
+        let dimension = server.dimensions.get_mut(DEFAULT_DIMENSION).unwrap();
+        let day_length = dimension.get_world_time().day_length;
+        let last_day = u64::MAX / day_length * day_length;
+        dimension.set_time(last_day);
+        assert!(!run(&mut server, &format!("time set {DEFAULT_DIMENSION} {}", day_length - 1)).is_success());
+        assert_eq!(time(&server), last_day);
+    }
+
+    #[test]
+    fn the_clock_starts_over_after_the_last_tick() {
+        let mut server = server_with_dimension();
+        let dimension = server.dimensions.get_mut(DEFAULT_DIMENSION).unwrap();
+        dimension.set_time(u64::MAX);
+
+        dimension.tick();
+
+        assert_eq!(time(&server), 0);
+    }
+}
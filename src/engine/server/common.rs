@@ -13,13 +13,75 @@ pub enum BlockType {
     TileEntity = 4,
 }
 
+impl BlockType {
+    // Names accepted by commands, in the same order as the discriminants
+    pub const NAMES: [&'static str; 5] = ["air", "tile", "wall", "sprite", "tileentity"];
+
+    pub fn from_name(name: &str) -> Option<BlockType> {
+        match name.to_lowercase().as_str() {
+            "air" => Some(BlockType::Air),
+            "tile" => Some(BlockType::Tile),
+            "wall" => Some(BlockType::Wall),
+            "sprite" => Some(BlockType::Sprite),
+            "tileentity" => Some(BlockType::TileEntity),
+            _ => None,
+        }
+    }
+}
+
+// Everything stored for one block on one layer
+#[derive(Debug, PartialEq, Clone, Copy, Encode, Decode)]
+pub struct BlockState {
+    pub block_type: BlockType,
+    pub block_id: u32,
+    pub texture_index: u8,
+}
+
+impl BlockState {
+    pub const AIR: BlockState = BlockState { block_type: BlockType::Air, block_id: 0, texture_index: 0 };
+
+    pub fn new(block_type: BlockType, block_id: u32) -> BlockState {
+        BlockState { block_type, block_id, texture_index: 0 }
+    }
+}
+
+// A block as written in commands, `tile` or `tile:3`. Without an id it
+// places id 0 and matches any id.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BlockPattern {
+    pub block_type: BlockType,
+    pub block_id: Option<u32>,
+}
+
+impl BlockPattern {
+    pub fn parse(input: &str) -> Option<BlockPattern> {
+        let (type_name, id) = match input.split_once(':') {
+            Some((type_name, id)) => (type_name, Some(id.parse::<u32>().ok()?)),
+            None => (input, None),
+        };
+
+        Some(BlockPattern {
+            block_type: BlockType::from_name(type_name)?,
+            block_id: id,
+        })
+    }
+
+    pub fn matches(&self, block: &BlockState) -> bool {
+        self.block_type == block.block_type && self.block_id.is_none_or(|id| id == block.block_id)
+    }
+
+    pub fn to_state(self) -> BlockState {
+        BlockState::new(self.block_type, self.block_id.unwrap_or(0))
+    }
+}
+
 /*  
 /   The texture_index is used for connections in the tile and wall type,
-/   different textures for each tile in the sprite type, and like
+/   the cell of a multi tile sprite in the sprite type (see sprite.rs), and like
 /   idk what in the tile entity type lol i gotta find some use
 */ 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Encode, Decode)]
 pub struct BlockArray {
     pub block_type: [BlockType; CHUNK_BLOCK_COUNT as usize],
     pub block_id: [u32; CHUNK_BLOCK_COUNT as usize],
@@ -90,13 +152,24 @@ impl BlockArray {
 }
 
 // The fore and middle ground never have walls, while the background has only walls
-#[derive(Serialize, Deserialize, Encode, Decode, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, PartialEq, Clone, Copy)]
 pub enum LayerType {
     Foreground,
     Middleground,
     Background
 }
 
+impl LayerType {
+    pub const ALL: [LayerType; 3] = [LayerType::Foreground, LayerType::Middleground, LayerType::Background];
+    // Names accepted by commands, in the same order as ALL
+    pub const NAMES: [&'static str; 3] = ["foreground", "middleground", "background"];
+
+    pub fn from_name(name: &str) -> Option<LayerType> {
+        let index = LayerType::NAMES.iter().position(|layer_name| layer_name.eq_ignore_ascii_case(name))?;
+        Some(LayerType::ALL[index])
+    }
+}
+
 pub fn world_to_local_pos_2d(world_pos: IVec2) -> IVec2 {
     let remainder = world_pos % CHUNK_SIZE as i32;
     let shifted = remainder + CHUNK_SIZE as i32;
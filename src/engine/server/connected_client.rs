@@ -0,0 +1,125 @@
+use std::{collections::HashSet, time::Instant};
+
+use glam::{IVec2, Vec2};
+
+use crate::engine::{command_registry::PermissionLevel, latency::LatencyTracker, network::Connection, server::constants::{CHAT_BURST_MESSAGES, CHAT_SECONDS_PER_MESSAGE, DEFAULT_DIMENSION, PLAYER_MAX_SPEED, PLAYER_MOVE_CREDIT_SECONDS}};
+
+pub struct ConnectedClient {
+    pub player_uuid: u64,
+    pub player_nickname: String,
+    pub connection: Connection,
+    pub latency: LatencyTracker,
+    pub chat_limiter: ChatRateLimiter,
+    pub dimension: String,
+    // Where the server thinks the player is, see move_to
+    pub position: Vec2,
+    last_moved: Instant,
+    // Fixed level of connections made inside the server process, such as
+    // load test bots. Everyone else gets theirs from the ops list.
+    pub permission_level: Option<PermissionLevel>,
+    // Chunks the client asked for that were not generated yet
+    pub requested_chunks: HashSet<IVec2>,
+}
+
+impl ConnectedClient {
+    pub fn new(player_uuid: u64, player_nickname: String, connection: Connection, permission_level: Option<PermissionLevel>) -> ConnectedClient {
+        ConnectedClient {
+            player_uuid,
+            player_nickname,
+            connection,
+            latency: LatencyTracker::new(),
+            chat_limiter: ChatRateLimiter::new(),
+            dimension: DEFAULT_DIMENSION.to_string(),
+            position: Vec2::ZERO,
+            last_moved: Instant::now(),
+            permission_level,
+            requested_chunks: HashSet::new(),
+        }
+    }
+
+    // Reported positions are not trusted, the player only gets as far towards
+    // one as they could have walked since the last. Otherwise a client could
+    // claim to stand next to any block it wants to edit. Connections made
+    // inside the server process go where they say.
+    pub fn move_to(&mut self, reported: Vec2) {
+        if !reported.is_finite() {
+            return;
+        }
+
+        let now = Instant::now();
+        let elapsed = (now - self.last_moved).as_secs_f32().min(PLAYER_MOVE_CREDIT_SECONDS);
+        self.last_moved = now;
+
+        if self.permission_level.is_some() {
+            self.position = reported;
+            return;
+        }
+        self.position += (reported - self.position).clamp_length_max(PLAYER_MAX_SPEED * elapsed);
+    }
+}
+
+// Token bucket - every message costs one token, tokens refill over time
+pub struct ChatRateLimiter {
+    tokens: f32,
+    last_refill: Instant,
+}
+
+impl ChatRateLimiter {
+    pub fn new() -> ChatRateLimiter {
+        ChatRateLimiter {
+            tokens: CHAT_BURST_MESSAGES,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn try_consume(&mut self) -> bool {
+        let now = Instant::now();
+        let refilled = (now - self.last_refill).as_secs_f32() / CHAT_SECONDS_PER_MESSAGE;
+        self.tokens = (self.tokens + refilled).min(CHAT_BURST_MESSAGES);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for ChatRateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn client(permission_level: Option<PermissionLevel>) -> ConnectedClient {
+        let mut client = ConnectedClient::new(1, "player".to_string(), Connection::pair().0, permission_level);
+        client.last_moved = Instant::now() - Duration::from_millis(500);
+        client
+    }
+
+    #[test]
+    fn players_cannot_teleport_next_to_a_block() {
+        let mut client = client(None);
+        client.move_to(Vec2::new(1000.0, 0.0));
+        assert!(client.position.x <= PLAYER_MAX_SPEED * 0.6, "moved to {}", client.position);
+        assert!(client.position.x > 0.0);
+
+        client.move_to(Vec2::new(f32::NAN, 0.0));
+        assert!(client.position.is_finite());
+    }
+
+    #[test]
+    fn local_connections_go_where_they_say() {
+        let mut client = client(Some(PermissionLevel::Moderator));
+        client.move_to(Vec2::new(1000.0, 40.0));
+        assert_eq!(client.position, Vec2::new(1000.0, 40.0));
+    }
+}
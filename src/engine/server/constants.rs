@@ -1,3 +1,5 @@
+use crate::engine::command_registry::PermissionLevel;
+
 // stay within a size of 32 because otherwise the stack might overflow
 // thats because while constructed, the chunk is stored on the stack
 // it is only put on the heap once it enters the hashmap
@@ -18,7 +20,9 @@ pub const NUM_1D_NOISE_LAYERS: usize = 6;
 
 pub const TEMPERATURE_INDEX: usize = 0;
 pub const HUMIDITY_INDEX: usize = 1;
-pub const NUM_2D_NOISE_LAYERS: usize = 2;
+pub const WORM_CAVE_INDEX: usize = 2;
+pub const CAVERN_INDEX: usize = 3;
+pub const NUM_2D_NOISE_LAYERS: usize = 4;
 
 pub const PARALLEL_CHUNKGEN_THRESHOLD: usize = 4;
 
@@ -27,4 +31,114 @@ pub const GPU_CHUNKGEN_THRESHOLD: usize = usize::MAX;
 #[cfg(feature = "gpu-server")]
 pub const GPU_CHUNKGEN_THRESHOLD: usize = 16;
 
-pub const TICK_RATE: u64 = 60;
\ No newline at end of file
+pub const TICK_RATE: u64 = 60;
+
+// Keepalive pings are sent every this many ticks, clients that leave
+// this many pings in a row unanswered get disconnected
+pub const KEEPALIVE_INTERVAL_TICKS: u128 = 60;
+// The world is saved every this many ticks besides when the server stops, 5 minutes
+pub const AUTOSAVE_INTERVAL_TICKS: u128 = 5 * 60 * TICK_RATE as u128;
+pub const KEEPALIVE_MAX_MISSED_PINGS: u32 = 5;
+
+// Dimension new players spawn in
+pub const DEFAULT_DIMENSION: &str = "overworld";
+
+// Remote console, only enabled when a password is set
+pub const RCON_PASSWORD_ENV: &str = "SWAGENGINE_RCON_PASSWORD";
+pub const RCON_PORT_ENV: &str = "SWAGENGINE_RCON_PORT";
+pub const RCON_DEFAULT_PORT: u16 = 25575;
+pub const RCON_MAX_AUTH_ATTEMPTS: u32 = 3;
+
+pub const CHAT_MAX_MESSAGE_LENGTH: usize = 256;
+// Players may send a burst of this many messages, after which they
+// regain one message every CHAT_SECONDS_PER_MESSAGE seconds
+pub const CHAT_BURST_MESSAGES: f32 = 5.0;
+pub const CHAT_SECONDS_PER_MESSAGE: f32 = 1.0;
+// Players with raised permission levels, kept in the working directory
+pub const OPS_FILE: &str = "ops.json";
+pub const IDENTITIES_FILE: &str = "identities.json";
+
+// Command scripts are looked up in this directory, relative to the working
+// directory. The startup script runs once when the server starts, if it exists.
+pub const SCRIPTS_DIRECTORY: &str = "scripts";
+pub const SCRIPT_EXTENSION: &str = "txt";
+pub const STARTUP_SCRIPT: &str = "startup";
+// Stops scripts that exec themselves
+pub const SCRIPT_MAX_DEPTH: usize = 8;
+
+// Largest area a single world edit command may touch
+pub const WORLD_EDIT_MAX_BLOCKS: u64 = 65536;
+// Edits changing more blocks than this resend whole chunks instead of single blocks
+pub const WORLD_EDIT_BLOCK_PACKET_LIMIT: usize = 256;
+
+// Players below this level cannot place or break blocks
+pub const BLOCK_EDIT_PERMISSION_LEVEL: PermissionLevel = PermissionLevel::Moderator;
+// How far from their last reported position players may edit or look into tile entities, in blocks
+pub const BLOCK_EDIT_REACH: f32 = 16.0;
+// Blocks per second. Players standing still save up to PLAYER_MOVE_CREDIT_SECONDS
+// of movement, so positions sent less often still get through.
+pub const PLAYER_MAX_SPEED: f32 = 20.0;
+pub const PLAYER_MOVE_CREDIT_SECONDS: f32 = 1.0;
+// Players below this level cannot read containers and signs
+pub const TILE_ENTITY_PERMISSION_LEVEL: PermissionLevel = PermissionLevel::Player;
+// Chunks a client may have asked for that were not sent yet
+pub const CHUNK_REQUEST_MAX_PENDING: usize = 256;
+
+// Schematics are saved to this directory, relative to the working directory,
+// and also looked up under the same name in the data directory
+pub const SCHEMATICS_DIRECTORY: &str = "schematics";
+pub const SCHEMATIC_EXTENSION: &str = "schem";
+
+// Chunks are stored in a directory per dimension inside this one, relative
+// to the working directory, whenever the world is saved
+pub const WORLD_DIRECTORY: &str = "world";
+pub const CHUNK_EXTENSION: &str = "chunk";
+pub const PENDING_FEATURES_FILE: &str = "pending_features.bin";
+
+// Undo history kept per player, console and remote console. The oldest
+// operations are dropped once either limit is reached.
+pub const EDIT_HISTORY_MAX_OPERATIONS: usize = 32;
+pub const EDIT_HISTORY_MAX_BYTES: usize = 8 * 1024 * 1024;
+
+// Mixed into the chunk seed so features do not line up with the biome points
+pub const FEATURE_SEED_SALT: u64 = 0x5DEECE66D1F3A7B9;
+pub const ORE_SEED_SALT: u64 = 0x2545F4914F6CDD1D;
+
+// Largest region a read only scan such as orecount may cover
+pub const WORLD_SCAN_MAX_BLOCKS: u64 = 1 << 22;
+pub const DECORATION_SEED_SALT: u64 = 0x9FB21C651E98DF25;
+
+// Fill level of a full liquid cell
+pub const LIQUID_MAX_LEVEL: u8 = 16;
+// Active liquid cells looked at per tick, the rest wait for the next one
+pub const LIQUID_MAX_CELLS_PER_TICK: usize = 16384;
+
+// Ticks between a block changing and the blocks around it checking their support
+pub const GRAVITY_UPDATE_DELAY: u64 = 2;
+// Blocks above unloaded chunks check again after this many ticks
+pub const GRAVITY_RETRY_DELAY: u64 = 20;
+// In blocks per tick
+pub const FALLING_BLOCK_ACCELERATION: f32 = 0.02;
+pub const FALLING_BLOCK_MAX_SPEED: f32 = 1.5;
+
+// Light from the open sky, and the most a block can give off
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+// Most blocks of one kind a container slot holds
+pub const MAX_STACK_SIZE: u32 = 64;
+
+// Widest and highest a sprite can be, the cell of a sprite is packed into a u8
+pub const SPRITE_MAX_SIZE: u32 = 16;
+
+// Ticks in a day for dimensions that do not set their own, 20 minutes
+pub const DEFAULT_DAY_LENGTH: u64 = 20 * 60 * TICK_RATE;
+// Share of the sky light that still reaches the world at midnight
+pub const MIN_DAYLIGHT: f32 = 0.2;
+
+// Cells of every loaded chunk that get a random tick each tick
+pub const RANDOM_TICKS_PER_CHUNK: usize = 3;
+pub const RANDOM_TICK_SEED_SALT: u64 = 0xD1B54A32D192ED03;
+
+// Columns under every front that may get snow or water each tick
+pub const WEATHER_SAMPLES_PER_FRONT: usize = 4;
+pub const WEATHER_SEED_SALT: u64 = 0x9E3779B97F4A7C15;
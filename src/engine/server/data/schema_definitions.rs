@@ -1,11 +1,159 @@
 use glam::UVec2;
 use serde::Deserialize;
 
+use crate::engine::server::{common::LayerType, constants::DEFAULT_DAY_LENGTH};
+
 #[derive(Deserialize, Clone)]
 pub struct DimensionSchema {
     pub name: String,
     pub size: UVec2,
     pub biome_map_adjustments: Option<BiomeMapAdjustments>,
+    // Height fluids fill up to, inclusive
+    #[serde(default)]
+    pub sea_level: i32,
+    // Blocks that flow, anything else stays where it is put
+    #[serde(default)]
+    pub liquids: Vec<LiquidSchema>,
+    // Ticks from one sunrise to the next
+    #[serde(default = "default_day_length")]
+    pub day_length: u64,
+    // Dimensions without it stay dry
+    #[serde(default)]
+    pub weather: Option<WeatherSchema>,
+}
+
+fn default_day_length() -> u64 {
+    DEFAULT_DAY_LENGTH
+}
+
+// Properties shared by every block with the id, listed in blocks.json
+#[derive(Deserialize, Clone)]
+pub struct BlockSchema {
+    pub id: u32,
+    pub name: String,
+    // Turns into a falling block when there is nothing solid below it
+    #[serde(default)]
+    pub falls: bool,
+    // Light given off, up to MAX_LIGHT_LEVEL
+    #[serde(default)]
+    pub light: u8,
+    // Tiles block light unless they are transparent, other block types never do
+    #[serde(default)]
+    pub transparent: bool,
+    #[serde(default)]
+    pub random_tick: Option<RandomTick>,
+    // State kept for the block when it is placed as a tile entity
+    #[serde(default)]
+    pub tile_entity: Option<TileEntitySchema>,
+    // What a furnace turns the block into
+    #[serde(default)]
+    pub smelts_into: Option<u32>,
+    // Sprites covering more than one cell, see sprite.rs
+    #[serde(default)]
+    pub sprite: Option<SpriteSchema>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SpriteSchema {
+    pub width: u32,
+    pub height: u32,
+    // Cell that lands on the position the sprite is placed at, from the bottom left
+    #[serde(default)]
+    pub anchor: UVec2,
+    // Checked when the sprite is placed and whenever a block it rests on changes
+    #[serde(default)]
+    pub placement: Vec<SpritePlacement>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SpritePlacement {
+    // Solid foreground under the whole bottom row
+    Ground,
+    // Solid foreground over the whole top row
+    Ceiling,
+    // A background wall behind every cell
+    Wall,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(tag = "type")]
+pub enum TileEntitySchema {
+    // Holds stacks of blocks, a chest
+    Container {
+        slots: u32,
+    },
+    Sign,
+    // Smelts the blocks put into it one at a time
+    Furnace {
+        cook_ticks: u32,
+    },
+}
+
+// What a block does when a random tick lands on it. Blocks keep their
+// type when they change, only the id is swapped.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(tag = "type")]
+pub enum RandomTick {
+    // Turns a block with the id within range into this one, if it has air
+    // above it. Grass growing over bare dirt.
+    Spread {
+        onto: u32,
+        chance: f32,
+        range: u32,
+    },
+    // Turns into the block where the local temperature is at least min_temperature
+    Melt {
+        into: u32,
+        min_temperature: u8,
+    },
+    // Turns into the next stage, for crops
+    Grow {
+        into: u32,
+        chance: f32,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LiquidSchema {
+    pub name: String,
+    pub block: u32,
+    // Ticks between two flow steps, thicker liquids move less often
+    #[serde(default = "default_flow_interval")]
+    pub flow_interval: u32,
+}
+
+fn default_flow_interval() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WeatherSchema {
+    // Chance every tick that a front forms, while there are fewer than max_fronts
+    pub front_chance: f32,
+    pub max_fronts: u32,
+    // In blocks
+    pub min_width: u32,
+    pub max_width: u32,
+    // In ticks
+    pub min_duration: u64,
+    pub max_duration: u64,
+    // Fastest a front drifts either way, in blocks per tick
+    pub max_speed: f32,
+    // Columns drier than this stay dry under a front
+    #[serde(default)]
+    pub min_humidity: u8,
+    // Below this temperature it snows instead of rains
+    pub snow_temperature: u8,
+    // Chance a sampled column under a front at full intensity is changed,
+    // nothing is unless snow_block or rain_fills is set
+    #[serde(default)]
+    pub effect_chance: f32,
+    // Laid on top of the ground where it snows
+    #[serde(default)]
+    pub snow_block: Option<u32>,
+    // Liquid block rain tops up where it is not full
+    #[serde(default)]
+    pub rain_fills: Option<u32>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -16,8 +164,36 @@ pub struct BiomeMapAdjustments {
 
 #[derive(Deserialize)]
 pub struct BiomeSchema {
+    // Defaults to the file name
+    #[serde(default)]
+    pub name: String,
     pub biome_config: BiomeConfig,
     pub noise_functions: NoiseFunctions,
+    #[serde(default)]
+    pub features: Vec<FeatureSchema>,
+    #[serde(default)]
+    pub caves: Vec<CaveBand>,
+    #[serde(default)]
+    pub decorations: Vec<DecorationSchema>,
+}
+
+// Cave settings for a range of depths below the surface. Where bands
+// overlap, a block is carved if any of them carves it.
+#[derive(Deserialize, Clone, Copy)]
+pub struct CaveBand {
+    pub min_depth: u32,
+    // Unlimited when left out
+    pub max_depth: Option<u32>,
+    // How far from zero the worm noise may be for a tunnel, 0 disables them
+    #[serde(default)]
+    pub worm_width: f32,
+    // Cavern noise above this is carved out, anything at 1 or above disables them
+    #[serde(default = "default_cavern_threshold")]
+    pub cavern_threshold: f32,
+}
+
+fn default_cavern_threshold() -> f32 {
+    1.0
 }
 
 #[derive(Deserialize)]
@@ -28,9 +204,37 @@ pub struct BiomeConfig {
     pub surface_block: u32,
     pub subsurface_block: u32,
     pub base_block: u32,
+    // Background walls behind the subsurface and base layers, none when left out
+    #[serde(default)]
+    pub subsurface_wall: Option<u32>,
+    #[serde(default)]
+    pub base_wall: Option<u32>,
+    #[serde(default)]
+    pub fluid: FluidConfig,
+}
+
+// What fills the space between the terrain and sea level, and the deep caves
+#[derive(Deserialize, Default)]
+pub struct FluidConfig {
+    // Nothing is placed when left out
+    pub fluid_block: Option<u32>,
+    // Replaces the fluid at sea level itself, for frozen over water
+    pub freeze_block: Option<u32>,
+    // Blocks right below sea level that are left dry, for beaches
+    #[serde(default)]
+    pub dry_depth: u32,
+    pub lava: Option<LavaConfig>,
+}
+
+// Caves deeper than min_depth below the surface are flooded with the block
+#[derive(Deserialize)]
+pub struct LavaConfig {
+    pub block: u32,
+    pub min_depth: u32,
 }
 
-#[derive(Deserialize, PartialEq)]
+// Only a tag, placement rules live in the rest of the config
+#[derive(Deserialize, PartialEq, Debug)]
 pub enum BiomeTypes {
     Hot,
     Warm,
@@ -56,6 +260,95 @@ pub struct NoiseConfig {
     pub blending_mode: BlendingMode,
 }
 
+// Small sprites in the middleground, they never span chunks
+#[derive(Deserialize, Clone)]
+pub struct DecorationSchema {
+    pub name: String,
+    // Sprite id
+    pub block: u32,
+    // Chance for each spot the placement allows
+    pub chance: f32,
+    pub placement: DecorationPlacement,
+    #[serde(default = "default_decoration_length")]
+    pub min_length: u32,
+    #[serde(default = "default_decoration_length")]
+    pub max_length: u32,
+}
+
+fn default_decoration_length() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum DecorationPlacement {
+    // Grows up from the surface
+    Surface,
+    // Hangs down from the ceiling of a cave
+    Ceiling,
+}
+
+// One kind of ore, veins are scattered through the foreground after the terrain is carved
+#[derive(Deserialize, Clone)]
+pub struct OreSchema {
+    pub name: String,
+    pub block: u32,
+    // World heights the vein may start at, both inclusive
+    pub min_y: i32,
+    pub max_y: i32,
+    // Steps of the random walk that grows a vein
+    pub vein_size: u32,
+    // Average number of veins per chunk, fractions are rolled for
+    pub veins_per_chunk: f32,
+    // Biome names the vein may start in, any biome when empty
+    #[serde(default)]
+    pub biomes: Vec<String>,
+    // Tile ids the ore may replace
+    pub replaces: Vec<u32>,
+}
+
+// Something placed on top of the terrain, rooted in the first air block above the surface
+#[derive(Deserialize)]
+pub struct FeatureSchema {
+    pub name: String,
+    // Chance for each surface column of the biome, at most one feature grows per column
+    pub chance: f32,
+    #[serde(default = "default_feature_layer")]
+    pub layer: LayerType,
+    pub shape: FeatureShape,
+}
+
+fn default_feature_layer() -> LayerType {
+    LayerType::Foreground
+}
+
+// Block ids are tile ids, like the ones in BiomeConfig
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum FeatureShape {
+    Tree {
+        trunk_block: u32,
+        leaves_block: u32,
+        min_height: u32,
+        max_height: u32,
+        canopy_radius: u32,
+    },
+    Boulder {
+        block: u32,
+        min_radius: u32,
+        max_radius: u32,
+    },
+    Column {
+        block: u32,
+        min_height: u32,
+        max_height: u32,
+    },
+    // Loaded from the schematics folder of the data directory. Uses the
+    // layers of the schematic, air in it is left out.
+    Schematic {
+        name: String,
+    },
+}
+
 #[derive(Deserialize, Clone, Copy)]
 pub enum BlendingMode {
     Mix,
@@ -0,0 +1,68 @@
+use fastrand::Rng;
+use glam::IVec2;
+
+use crate::engine::{common::ChunkRelativePos, server::{biome::Biome, chunk::Chunk, common::{BlockType, LayerType}, constants::{CHUNK_SIZE, DECORATION_SEED_SALT}, data::schema_definitions::DecorationPlacement, noise::noise_util::get_chunk_seed}};
+
+/*
+/   Middleground sprites such as grass tufts on the surface and vines under
+/   cave ceilings. They run after caves and ores, only go where both the
+/   fore and middleground are still air, and are cut off at the chunk edge.
+*/
+pub fn place_decorations<'a>(chunk: &mut Chunk, chunk_pos: &IVec2, heights: &[f32; CHUNK_SIZE as usize], biome_at: impl Fn(usize) -> &'a Biome, seed: i32) {
+    let mut rng = Rng::with_seed(get_chunk_seed(seed, chunk_pos) ^ DECORATION_SEED_SALT);
+    let chunk_world_y = chunk_pos.y * CHUNK_SIZE as i32;
+    let biome_at_pos = |position: IVec2| biome_at((position.y * CHUNK_SIZE as i32 + position.x) as usize);
+
+    for x in 0..CHUNK_SIZE as i32 {
+        let height = heights[x as usize];
+
+        let surface = IVec2::new(x, height.floor() as i32 + 1 - chunk_world_y);
+        if (0..CHUNK_SIZE as i32).contains(&surface.y) {
+            decorate(chunk, &mut rng, biome_at_pos(surface), DecorationPlacement::Surface, surface);
+        }
+
+        // Air below the surface height was carved out by a cave
+        for y in 0..CHUNK_SIZE as i32 - 1 {
+            let position = IVec2::new(x, y);
+            if (y + chunk_world_y) as f32 <= height && is_free(chunk, position) && is_solid(chunk, position + IVec2::Y) {
+                decorate(chunk, &mut rng, biome_at_pos(position), DecorationPlacement::Ceiling, position);
+            }
+        }
+    }
+}
+
+fn decorate(chunk: &mut Chunk, rng: &mut Rng, biome: &Biome, placement: DecorationPlacement, start: IVec2) {
+    let Some(decoration) = biome.decorations.iter()
+        .filter(|decoration| decoration.placement == placement)
+        .find(|decoration| rng.f32() < decoration.chance) else {
+        return;
+    };
+
+    let direction = match placement {
+        DecorationPlacement::Surface => IVec2::Y,
+        DecorationPlacement::Ceiling => IVec2::NEG_Y,
+    };
+
+    let length = rng.u32(decoration.min_length..=decoration.max_length.max(decoration.min_length)) as i32;
+    for step in 0..length {
+        let position = start + direction * step;
+        if !(0..CHUNK_SIZE as i32).contains(&position.y) || !is_free(chunk, position) {
+            break;
+        }
+
+        chunk.set_block(to_relative(position), LayerType::Middleground, BlockType::Sprite, decoration.block, 0);
+    }
+}
+
+fn is_free(chunk: &Chunk, position: IVec2) -> bool {
+    chunk.get_block(to_relative(position), LayerType::Foreground).block_type == BlockType::Air
+        && chunk.get_block(to_relative(position), LayerType::Middleground).block_type == BlockType::Air
+}
+
+fn is_solid(chunk: &Chunk, position: IVec2) -> bool {
+    chunk.get_block(to_relative(position), LayerType::Foreground).block_type == BlockType::Tile
+}
+
+fn to_relative(position: IVec2) -> ChunkRelativePos {
+    ChunkRelativePos::new(position.x as u8, position.y as u8)
+}
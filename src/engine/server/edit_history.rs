@@ -0,0 +1,246 @@
+use std::collections::{HashMap, VecDeque};
+
+use glam::IVec2;
+
+use crate::engine::{command_registry::CommandActor, server::{common::{BlockState, LayerType}, constants::{EDIT_HISTORY_MAX_BYTES, EDIT_HISTORY_MAX_OPERATIONS}, world::Dimension, world_edit::{CellChange, EditOutcome}}};
+
+// Neighbouring cells in a row that changed from and to the same blocks.
+// A fill over even ground collapses into a handful of runs per row.
+#[derive(Clone, Copy)]
+struct CellRun {
+    start: IVec2,
+    length: u32,
+    layer: LayerType,
+    before: BlockState,
+    after: BlockState,
+}
+
+impl CellRun {
+    fn positions(&self) -> impl DoubleEndedIterator<Item = IVec2> {
+        let start = self.start;
+        (0..self.length as i32).map(move |offset| start + IVec2::new(offset, 0))
+    }
+}
+
+pub struct EditOperation {
+    pub description: String,
+    pub dimension: String,
+    runs: Vec<CellRun>,
+    cell_count: u64,
+}
+
+impl EditOperation {
+    pub fn new(description: String, dimension: String, changes: &[CellChange]) -> EditOperation {
+        let mut runs: Vec<CellRun> = Vec::new();
+
+        for change in changes {
+            if let Some(run) = runs.last_mut()
+                && run.layer == change.layer
+                && run.before == change.before
+                && run.after == change.after
+                && run.start.y == change.position.y
+                && run.start.x + run.length as i32 == change.position.x {
+                run.length += 1;
+                continue;
+            }
+
+            runs.push(CellRun {
+                start: change.position,
+                length: 1,
+                layer: change.layer,
+                before: change.before,
+                after: change.after,
+            });
+        }
+
+        EditOperation { description, dimension, runs, cell_count: changes.len() as u64 }
+    }
+
+    pub fn get_cell_count(&self) -> u64 {
+        self.cell_count
+    }
+
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<EditOperation>() + self.runs.len() * std::mem::size_of::<CellRun>() + self.description.len() + self.dimension.len()
+    }
+
+    // Works backwards so a cell changed twice ends up as it was at the start.
+    // Cells changed by someone else since are left alone.
+    pub fn revert(&self, dimension: &mut Dimension) -> EditOutcome {
+        let mut outcome = EditOutcome::default();
+        for run in self.runs.iter().rev() {
+            for world_pos in run.positions().rev() {
+                outcome.set_if(dimension, world_pos, run.layer, run.after, run.before);
+            }
+        }
+        outcome
+    }
+
+    pub fn reapply(&self, dimension: &mut Dimension) -> EditOutcome {
+        let mut outcome = EditOutcome::default();
+        for run in &self.runs {
+            for world_pos in run.positions() {
+                outcome.set_if(dimension, world_pos, run.layer, run.before, run.after);
+            }
+        }
+        outcome
+    }
+}
+
+#[derive(Default)]
+struct EditHistory {
+    // Newest at the back
+    undo: VecDeque<EditOperation>,
+    redo: Vec<EditOperation>,
+    memory_size: usize,
+}
+
+impl EditHistory {
+    fn trim(&mut self) {
+        while self.undo.len() > EDIT_HISTORY_MAX_OPERATIONS || (self.memory_size > EDIT_HISTORY_MAX_BYTES && !self.undo.is_empty()) {
+            if let Some(oldest) = self.undo.pop_front() {
+                self.memory_size -= oldest.memory_size();
+            }
+        }
+    }
+}
+
+// Undo and redo stacks for everyone who edits the world
+pub struct EditJournal {
+    histories: HashMap<CommandActor, EditHistory>,
+}
+
+impl EditJournal {
+    pub fn new() -> EditJournal {
+        EditJournal { histories: HashMap::new() }
+    }
+
+    // A new edit makes the redo stack meaningless
+    pub fn record(&mut self, actor: CommandActor, operation: EditOperation) {
+        if operation.get_cell_count() == 0 {
+            return;
+        }
+
+        let history = self.histories.entry(actor).or_default();
+        for dropped in history.redo.drain(..) {
+            history.memory_size -= dropped.memory_size();
+        }
+
+        history.memory_size += operation.memory_size();
+        history.undo.push_back(operation);
+        history.trim();
+    }
+
+    pub fn take_undo(&mut self, actor: CommandActor) -> Option<EditOperation> {
+        let history = self.histories.get_mut(&actor)?;
+        let operation = history.undo.pop_back()?;
+        history.memory_size -= operation.memory_size();
+        Some(operation)
+    }
+
+    pub fn take_redo(&mut self, actor: CommandActor) -> Option<EditOperation> {
+        let history = self.histories.get_mut(&actor)?;
+        let operation = history.redo.pop()?;
+        history.memory_size -= operation.memory_size();
+        Some(operation)
+    }
+
+    pub fn push_redo(&mut self, actor: CommandActor, operation: EditOperation) {
+        let history = self.histories.entry(actor).or_default();
+        history.memory_size += operation.memory_size();
+        history.redo.push(operation);
+    }
+
+    // Unlike record this keeps the redo stack, used when redoing
+    pub fn push_undo(&mut self, actor: CommandActor, operation: EditOperation) {
+        let history = self.histories.entry(actor).or_default();
+        history.memory_size += operation.memory_size();
+        history.undo.push_back(operation);
+        history.trim();
+    }
+
+    // Newest first
+    pub fn get_undo_operations(&self, actor: CommandActor) -> Vec<&EditOperation> {
+        self.histories.get(&actor).map_or_else(Vec::new, |history| history.undo.iter().rev().collect())
+    }
+
+    pub fn get_redo_count(&self, actor: CommandActor) -> usize {
+        self.histories.get(&actor).map_or(0, |history| history.redo.len())
+    }
+
+    pub fn get_memory_size(&self, actor: CommandActor) -> usize {
+        self.histories.get(&actor).map_or(0, |history| history.memory_size)
+    }
+
+    pub fn forget(&mut self, actor: CommandActor) {
+        self.histories.remove(&actor);
+    }
+}
+
+impl Default for EditJournal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{common::get_data_path, server::common::BlockType};
+
+    use super::*;
+
+    fn change(x: i32, y: i32, before: BlockState, after: BlockState) -> CellChange {
+        CellChange { position: IVec2::new(x, y), layer: LayerType::Foreground, before, after }
+    }
+
+    fn tile(id: u32) -> BlockState {
+        BlockState::new(BlockType::Tile, id)
+    }
+
+    #[test]
+    fn neighbouring_changes_share_a_run() {
+        let changes = [
+            change(0, 0, BlockState::AIR, tile(1)),
+            change(1, 0, BlockState::AIR, tile(1)),
+            change(2, 0, BlockState::AIR, tile(1)),
+            // Different block, gap in the row, and the next row
+            change(3, 0, BlockState::AIR, tile(2)),
+            change(5, 0, BlockState::AIR, tile(2)),
+            change(6, 1, BlockState::AIR, tile(2)),
+        ];
+
+        let operation = EditOperation::new(String::new(), String::new(), &changes);
+        let lengths: Vec<u32> = operation.runs.iter().map(|run| run.length).collect();
+        assert_eq!(lengths, [3, 1, 1, 1]);
+        assert_eq!(operation.get_cell_count(), 6);
+    }
+
+    #[test]
+    fn revert_undoes_repeated_changes_in_reverse() {
+        let schemas = Dimension::load_dimensions(&get_data_path()).unwrap();
+        let mut dimension = Dimension::from_schema(&schemas[0], 1);
+        dimension.insert_chunk(IVec2::ZERO, crate::engine::server::chunk::Chunk::empty());
+
+        // The same cell changes twice within one operation
+        let position = IVec2::new(4, 4);
+        let changes = [
+            change(4, 4, BlockState::AIR, tile(1)),
+            change(4, 4, tile(1), tile(2)),
+        ];
+        dimension.set_block(position, LayerType::Foreground, tile(2));
+
+        let operation = EditOperation::new(String::new(), String::new(), &changes);
+        let outcome = operation.revert(&mut dimension);
+        assert_eq!(outcome.conflicts, 0);
+        assert_eq!(dimension.get_block(position, LayerType::Foreground), Some(BlockState::AIR));
+
+        operation.reapply(&mut dimension);
+        assert_eq!(dimension.get_block(position, LayerType::Foreground), Some(tile(2)));
+
+        // Someone else changed the cell since, so neither step touches it
+        dimension.set_block(position, LayerType::Foreground, tile(3));
+        let outcome = operation.revert(&mut dimension);
+        assert_eq!(outcome.conflicts, 2);
+        assert_eq!(dimension.get_block(position, LayerType::Foreground), Some(tile(3)));
+    }
+}
@@ -0,0 +1,206 @@
+use bincode::{Decode, Encode};
+use fastrand::Rng;
+use glam::IVec2;
+
+use crate::engine::{common::{get_data_path, ChunkRelativePos}, server::{biome::Biome, chunk::Chunk, common::{world_to_chunk_pos_2d, BlockState, BlockType, LayerType}, constants::{CHUNK_SIZE, FEATURE_SEED_SALT, SCHEMATICS_DIRECTORY}, data::schema_definitions::{FeatureSchema, FeatureShape}, noise::noise_util::{get_chunk_seed, splitmix64}, schematic::Schematic}};
+
+// A single block of a feature, in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct FeaturePlacement {
+    #[bincode(with_serde)]
+    pub world_pos: IVec2,
+    pub layer: LayerType,
+    pub block: BlockState,
+    // Where two features overlap, the higher priority keeps the block
+    pub priority: u64,
+}
+
+/*
+/   Placements still waiting for their chunk are stored next to the chunk
+/   files, in the same layout: magic bytes, a little endian format version
+/   and the lz4 compressed bincode of the list.
+*/
+const PENDING_FEATURES_MAGIC: &[u8; 4] = b"FEAT";
+const PENDING_FEATURES_VERSION: u16 = 1;
+
+pub fn pending_features_to_bytes(placements: &[FeaturePlacement]) -> Vec<u8> {
+    let encoded = bincode::encode_to_vec(placements, bincode::config::standard()).unwrap();
+
+    let mut bytes = Vec::with_capacity(encoded.len() / 2);
+    bytes.extend_from_slice(PENDING_FEATURES_MAGIC);
+    bytes.extend_from_slice(&PENDING_FEATURES_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&lz4_flex::compress_prepend_size(&encoded));
+    bytes
+}
+
+pub fn pending_features_from_bytes(bytes: &[u8]) -> Result<Vec<FeaturePlacement>, String> {
+    let Some(body) = bytes.strip_prefix(PENDING_FEATURES_MAGIC) else {
+        return Err("Not a pending features file".to_string());
+    };
+
+    let (version, body) = body.split_at_checked(2).ok_or("Pending features file is truncated")?;
+    let version = u16::from_le_bytes([version[0], version[1]]);
+    if version != PENDING_FEATURES_VERSION {
+        return Err(format!("Pending features have format version {version}, this build reads version {PENDING_FEATURES_VERSION}"));
+    }
+
+    let decompressed = lz4_flex::decompress_size_prepended(body).map_err(|error| format!("Pending features are corrupted: {error}"))?;
+    let (placements, _): (Vec<FeaturePlacement>, usize) = bincode::decode_from_slice(&decompressed, bincode::config::standard())
+        .map_err(|error| format!("Pending features are corrupted: {error}"))?;
+    Ok(placements)
+}
+
+pub struct Feature {
+    pub name: String,
+    chance: f32,
+    layer: LayerType,
+    shape: FeatureShape,
+    // Loaded once when the biome is, only set for the schematic shape
+    schematic: Option<Schematic>,
+}
+
+impl Feature {
+    pub fn from_schema(schema: FeatureSchema) -> Result<Feature, String> {
+        let schematic = match &schema.shape {
+            FeatureShape::Schematic { name } => {
+                let schematic = Schematic::load_from(&get_data_path().join(SCHEMATICS_DIRECTORY), name)?;
+                schematic.check_size()?;
+                Some(schematic)
+            },
+            _ => None,
+        };
+
+        Ok(Feature {
+            name: schema.name,
+            chance: schema.chance,
+            layer: schema.layer,
+            shape: schema.shape,
+            schematic,
+        })
+    }
+
+    // Blocks relative to the root, which is the first air block above the surface
+    fn blocks(&self, rng: &mut Rng) -> Vec<(IVec2, LayerType, BlockState)> {
+        let mut blocks = Vec::new();
+        let tile = |block_id: u32| BlockState::new(BlockType::Tile, block_id);
+
+        match &self.shape {
+            FeatureShape::Tree { trunk_block, leaves_block, min_height, max_height, canopy_radius } => {
+                let height = rng.u32(*min_height..=(*max_height).max(*min_height)) as i32;
+                for y in 0..height {
+                    blocks.push((IVec2::new(0, y), self.layer, tile(*trunk_block)));
+                }
+
+                for offset in disk(*canopy_radius as i32) {
+                    let position = IVec2::new(0, height) + offset;
+                    if position.x == 0 && position.y < height {
+                        continue;
+                    }
+                    blocks.push((position, self.layer, tile(*leaves_block)));
+                }
+            },
+            FeatureShape::Boulder { block, min_radius, max_radius } => {
+                let radius = rng.u32(*min_radius..=(*max_radius).max(*min_radius)) as i32;
+                // Half sunk into the ground, features never replace terrain so only the top shows
+                let center = IVec2::new(0, radius / 2);
+                for offset in disk(radius) {
+                    blocks.push((center + offset, self.layer, tile(*block)));
+                }
+            },
+            FeatureShape::Column { block, min_height, max_height } => {
+                let height = rng.u32(*min_height..=(*max_height).max(*min_height)) as i32;
+                for y in 0..height {
+                    blocks.push((IVec2::new(0, y), self.layer, tile(*block)));
+                }
+            },
+            FeatureShape::Schematic { .. } => {
+                let Some(schematic) = &self.schematic else {
+                    return blocks;
+                };
+
+                // Centered on the root, standing on the surface
+                let offset_x = schematic.width as i32 / 2;
+                for layer in LayerType::ALL {
+                    for y in 0..schematic.height {
+                        for x in 0..schematic.width {
+                            let block = schematic.get(x, y, layer);
+                            if block.block_type != BlockType::Air {
+                                blocks.push((IVec2::new(x as i32 - offset_x, y as i32), layer, block));
+                            }
+                        }
+                    }
+                }
+            },
+        }
+
+        blocks
+    }
+}
+
+// Offsets within the radius, slightly wider than a plain circle so small ones are not diamonds
+fn disk(radius: i32) -> impl Iterator<Item = IVec2> {
+    (-radius..=radius)
+        .flat_map(move |y| (-radius..=radius).map(move |x| IVec2::new(x, y)))
+        .filter(move |offset| offset.length_squared() <= radius * radius + radius)
+}
+
+/*
+/   Rolls for one feature in every column whose surface lies in this chunk.
+/   Blocks landing inside the chunk are placed right away, the rest is
+/   returned so the dimension can place it once the neighbour exists. All
+/   randomness comes from the chunk seed, so the result is deterministic.
+*/
+pub fn decorate_chunk<'a>(chunk: &mut Chunk, chunk_pos: &IVec2, heights: &[f32; CHUNK_SIZE as usize], biome_at: impl Fn(usize) -> &'a Biome, seed: i32) -> Vec<FeaturePlacement> {
+    let chunk_seed = get_chunk_seed(seed, chunk_pos) ^ FEATURE_SEED_SALT;
+    let mut rng = Rng::with_seed(chunk_seed);
+    let chunk_world_pos = *chunk_pos * CHUNK_SIZE as i32;
+    let mut overflow = Vec::new();
+
+    for x in 0..CHUNK_SIZE as i32 {
+        let root_y = heights[x as usize].floor() as i32 + 1;
+        let local_y = root_y - chunk_world_pos.y;
+        if !(0..CHUNK_SIZE as i32).contains(&local_y) {
+            continue;
+        }
+
+        // Nothing grows under water or ice
+        if chunk.get_block(ChunkRelativePos::new(x as u8, local_y as u8), LayerType::Foreground).block_type != BlockType::Air {
+            continue;
+        }
+
+        let biome = biome_at((local_y * CHUNK_SIZE as i32 + x) as usize);
+        let Some(feature) = biome.features.iter().find(|feature| rng.f32() < feature.chance) else {
+            continue;
+        };
+
+        let root = IVec2::new(chunk_world_pos.x + x, root_y);
+        let priority = splitmix64(chunk_seed.wrapping_add(x as u64));
+
+        for (offset, layer, block) in feature.blocks(&mut rng) {
+            let placement = FeaturePlacement { world_pos: root + offset, layer, block, priority };
+            if world_to_chunk_pos_2d(placement.world_pos) == *chunk_pos {
+                chunk.place_feature_block(&placement);
+            } else {
+                overflow.push(placement);
+            }
+        }
+    }
+
+    overflow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_features_survive_storing() {
+        let placements = vec![
+            FeaturePlacement { world_pos: IVec2::new(-40, 7), layer: LayerType::Foreground, block: BlockState::new(BlockType::Tile, 7), priority: 3 },
+            FeaturePlacement { world_pos: IVec2::new(12, -90), layer: LayerType::Background, block: BlockState::new(BlockType::Wall, 8), priority: 9 },
+        ];
+
+        assert_eq!(pending_features_from_bytes(&pending_features_to_bytes(&placements)).unwrap(), placements);
+        assert!(pending_features_from_bytes(b"CHNK\x01\x00").is_err());
+    }
+}
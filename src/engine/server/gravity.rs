@@ -0,0 +1,210 @@
+use glam::IVec2;
+
+use crate::engine::server::{common::{BlockState, BlockType, LayerType}, constants::{FALLING_BLOCK_ACCELERATION, FALLING_BLOCK_MAX_SPEED, GRAVITY_RETRY_DELAY}, world::Dimension, world_edit::CellChange};
+
+/*
+/   Blocks that fall are taken out of the world when their update finds
+/   nothing below them and live on as an entity of the dimension. Once the
+/   entity hits something it is put back as a block. Clients only see the
+/   block leave and land, entities are not sent to them yet.
+/
+/   Liquids do not hold falling blocks up. A block landing in one takes
+/   the place of the liquid in that cell.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct FallingBlock {
+    pub block: BlockState,
+    pub x: i32,
+    // Cell the block is in
+    pub cell: i32,
+    // How far it has sunk into the cell below, from 0 to 1
+    pub offset: f32,
+    // In blocks per tick
+    pub velocity: f32,
+}
+
+enum Below {
+    Free,
+    Solid,
+    // The block waits there until the chunk is generated
+    Unloaded,
+}
+
+fn below(dimension: &Dimension, world_pos: IVec2) -> Below {
+    match dimension.get_block(world_pos, LayerType::Foreground) {
+        Some(block) if block.block_type == BlockType::Air || dimension.get_liquids().is_liquid(block) => Below::Free,
+        Some(_) => Below::Solid,
+        // The bottom of the world holds blocks up
+        None if !dimension.block_within_world_bounds(world_pos) => Below::Solid,
+        None => Below::Unloaded,
+    }
+}
+
+// Runs when a scheduled update reaches the block, falling blocks without support start to fall
+pub fn update_block(dimension: &mut Dimension, world_pos: IVec2) -> Vec<CellChange> {
+    let Some(block) = dimension.get_block(world_pos, LayerType::Foreground) else {
+        return Vec::new();
+    };
+
+    if !dimension.get_blocks().falls(block) {
+        return Vec::new();
+    }
+
+    match below(dimension, world_pos - IVec2::Y) {
+        Below::Solid => Vec::new(),
+        Below::Unloaded => {
+            dimension.schedule_update(world_pos, GRAVITY_RETRY_DELAY);
+            Vec::new()
+        },
+        Below::Free => {
+            dimension.set_block(world_pos, LayerType::Foreground, BlockState::AIR);
+            dimension.get_ecs_world_mut().spawn((FallingBlock { block, x: world_pos.x, cell: world_pos.y, offset: 0.0, velocity: 0.0 },));
+            vec![CellChange { position: world_pos, layer: LayerType::Foreground, before: block, after: BlockState::AIR }]
+        },
+    }
+}
+
+// Moves every falling block down and places the ones that landed
+pub fn move_falling_blocks(dimension: &mut Dimension) -> Vec<CellChange> {
+    let mut falling_blocks: Vec<(hecs::Entity, FallingBlock)> = dimension.get_ecs_world()
+        .query::<&FallingBlock>()
+        .iter()
+        .map(|(entity, falling_block)| (entity, *falling_block))
+        .collect();
+
+    if falling_blocks.is_empty() {
+        return Vec::new();
+    }
+
+    // Lowest first, so the bottom of a falling stack lands before the rest reaches it
+    falling_blocks.sort_unstable_by_key(|(_, falling_block)| (falling_block.cell, falling_block.x));
+
+    let mut changes = Vec::new();
+    for (entity, mut falling_block) in falling_blocks {
+        falling_block.velocity = (falling_block.velocity + FALLING_BLOCK_ACCELERATION).min(FALLING_BLOCK_MAX_SPEED);
+        falling_block.offset += falling_block.velocity;
+
+        // One cell at a time, so fast blocks can not pass through a floor
+        let mut landed = false;
+        while falling_block.offset > 0.0 {
+            match below(dimension, IVec2::new(falling_block.x, falling_block.cell - 1)) {
+                Below::Free if falling_block.offset >= 1.0 => {
+                    falling_block.cell -= 1;
+                    falling_block.offset -= 1.0;
+                },
+                Below::Free => break,
+                Below::Solid => {
+                    landed = true;
+                    break;
+                },
+                Below::Unloaded => {
+                    falling_block.offset = 0.0;
+                    falling_block.velocity = 0.0;
+                    break;
+                },
+            }
+        }
+
+        if !landed {
+            let _ = dimension.get_ecs_world_mut().insert_one(entity, falling_block);
+            continue;
+        }
+
+        let _ = dimension.get_ecs_world_mut().despawn(entity);
+        if let Some(change) = land(dimension, falling_block) {
+            changes.push(change);
+        }
+    }
+
+    changes
+}
+
+// Drops every falling block straight to where it would come to rest, so
+// none of them are missing from the chunks when the world is saved
+pub fn settle_falling_blocks(dimension: &mut Dimension) -> Vec<CellChange> {
+    let mut falling_blocks: Vec<(hecs::Entity, FallingBlock)> = dimension.get_ecs_world()
+        .query::<&FallingBlock>()
+        .iter()
+        .map(|(entity, falling_block)| (entity, *falling_block))
+        .collect();
+
+    // Lowest first, so a falling stack lands in order
+    falling_blocks.sort_unstable_by_key(|(_, falling_block)| (falling_block.cell, falling_block.x));
+
+    let mut changes = Vec::new();
+    for (entity, mut falling_block) in falling_blocks {
+        let _ = dimension.get_ecs_world_mut().despawn(entity);
+
+        loop {
+            match below(dimension, IVec2::new(falling_block.x, falling_block.cell - 1)) {
+                Below::Free => falling_block.cell -= 1,
+                Below::Solid => break,
+                // Falls on once the chunk below is generated
+                Below::Unloaded => {
+                    dimension.schedule_update(IVec2::new(falling_block.x, falling_block.cell), GRAVITY_RETRY_DELAY);
+                    break;
+                },
+            }
+        }
+
+        if let Some(change) = land(dimension, falling_block) {
+            changes.push(change);
+        }
+    }
+
+    changes
+}
+
+// Something may have been built into the cell while the block fell through
+// it, then the block lands on top of that instead. It is lost if there is
+// no loaded free cell above.
+fn land(dimension: &mut Dimension, falling_block: FallingBlock) -> Option<CellChange> {
+    let mut world_pos = IVec2::new(falling_block.x, falling_block.cell);
+
+    loop {
+        let before = dimension.get_block(world_pos, LayerType::Foreground)?;
+        if before.block_type == BlockType::Air || dimension.get_liquids().is_liquid(before) {
+            dimension.set_block(world_pos, LayerType::Foreground, falling_block.block);
+            return Some(CellChange { position: world_pos, layer: LayerType::Foreground, before, after: falling_block.block });
+        }
+        world_pos.y += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{common::get_data_path, server::{chunk::Chunk, constants::DEFAULT_DIMENSION}};
+
+    const SAND: BlockState = BlockState { block_type: BlockType::Tile, block_id: 1, texture_index: 0 };
+
+    // One empty chunk in the corner of the world, its bottom row rests on the world floor
+    fn empty_dimension() -> Dimension {
+        let schemas = Dimension::load_dimensions(&get_data_path()).unwrap();
+        let schema = schemas.iter().find(|schema| schema.name == DEFAULT_DIMENSION).unwrap();
+        let mut dimension = Dimension::from_schema(schema, 1);
+        dimension.insert_chunk(IVec2::ZERO, Chunk::empty());
+        dimension
+    }
+
+    fn spawn_falling(dimension: &mut Dimension, x: i32, cell: i32) {
+        dimension.get_ecs_world_mut().spawn((FallingBlock { block: SAND, x, cell, offset: 0.5, velocity: 1.0 },));
+    }
+
+    #[test]
+    fn settling_puts_falling_blocks_down_where_they_would_land() {
+        let mut dimension = empty_dimension();
+        spawn_falling(&mut dimension, 3, 11);
+        spawn_falling(&mut dimension, 3, 10);
+        spawn_falling(&mut dimension, 5, 20);
+
+        let changes = settle_falling_blocks(&mut dimension);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(dimension.get_ecs_world().query::<&FallingBlock>().iter().count(), 0);
+        for position in [IVec2::new(3, 0), IVec2::new(3, 1), IVec2::new(5, 0)] {
+            assert_eq!(dimension.get_block(position, LayerType::Foreground), Some(SAND));
+        }
+        assert_eq!(dimension.get_block(IVec2::new(3, 11), LayerType::Foreground), Some(BlockState::AIR));
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IdentityEntry {
+    // Hex, like in the ops list
+    pub uuid: String,
+    pub token: String,
+    // Last known nickname, only for reading the file
+    pub nickname: String,
+}
+
+/*
+/   UUIDs are picked by the clients, so on their own they prove nothing.
+/   The first time a UUID joins the server hands out a random token for it,
+/   and every later join with that UUID has to send the same token back.
+/   The ops list is keyed by UUID and only trusted because of this.
+*/
+pub struct PlayerIdentities {
+    path: PathBuf,
+    entries: HashMap<u64, IdentityEntry>,
+}
+
+impl PlayerIdentities {
+    pub fn load(path: impl Into<PathBuf>) -> PlayerIdentities {
+        let path = path.into();
+        let mut entries = HashMap::new();
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str::<Vec<IdentityEntry>>(&contents) {
+                Ok(list) => {
+                    for entry in list {
+                        match u64::from_str_radix(&entry.uuid, 16) {
+                            Ok(uuid) => { entries.insert(uuid, entry); },
+                            Err(_) => println!("Skipping identity with invalid UUID \"{}\"", entry.uuid),
+                        }
+                    }
+                },
+                Err(error) => println!("Failed to parse {}: {error}", path.display()),
+            },
+            // No file yet, nobody has joined
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {},
+            Err(error) => println!("Failed to read {}: {error}", path.display()),
+        }
+
+        PlayerIdentities { path, entries }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut list: Vec<&IdentityEntry> = self.entries.values().collect();
+        list.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+
+        let contents = serde_json::to_string_pretty(&list).map_err(std::io::Error::other)?;
+        std::fs::write(&self.path, contents)
+    }
+
+    // Returns the new token if the UUID joined for the first time
+    pub fn authenticate(&mut self, uuid: u64, nickname: &str, token: Option<u64>) -> Result<Option<u64>, String> {
+        if let Some(entry) = self.entries.get_mut(&uuid) {
+            if token.map(|token| format!("{token:016x}")) != Some(entry.token.clone()) {
+                return Err("This UUID belongs to another player".to_string());
+            }
+
+            if entry.nickname != nickname {
+                entry.nickname = nickname.to_string();
+                self.save_or_report();
+            }
+            return Ok(None);
+        }
+
+        let token = getrandom::u64().map_err(|error| format!("Failed to create a token: {error}"))?;
+        self.entries.insert(uuid, IdentityEntry {
+            uuid: format!("{uuid:016x}"),
+            token: format!("{token:016x}"),
+            nickname: nickname.to_string(),
+        });
+        self.save_or_report();
+
+        Ok(Some(token))
+    }
+
+    pub fn is_known(&self, uuid: u64) -> bool {
+        self.entries.contains_key(&uuid)
+    }
+
+    fn save_or_report(&self) {
+        if let Err(error) = self.save() {
+            println!("Failed to save {}: {error}", self.path.display());
+        }
+    }
+}
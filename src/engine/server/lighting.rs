@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use glam::IVec2;
+
+use crate::engine::{common::ChunkRelativePos, server::{block_registry::BlockRegistry, chunk::Chunk, common::{world_to_chunk_pos_2d, world_to_local_pos_2d, LayerType}, constants::{CHUNK_SIZE, MAX_LIGHT_LEVEL}}};
+
+/*
+/   Light is flood filled through the foreground, one level less with every
+/   step. Sky light starts at full strength in the top row of every chunk
+/   that has nothing loaded above it and falls straight down without getting
+/   weaker, block light starts at the blocks that give it off. Opaque cells
+/   are lit by their neighbours so the faces of the terrain show up, but
+/   they pass nothing on.
+/
+/   Chunks are lit as they arrive, which also spreads their light into the
+/   neighbours already loaded. Changed blocks are relit together once per
+/   tick: first everything that came through them is taken out, then the
+/   light around the hole fills it back in.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+impl LightKind {
+    pub const ALL: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+}
+
+const NEIGHBOURS: [IVec2; 4] = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+
+pub struct Lighting<'a> {
+    pub chunks: &'a mut HashMap<IVec2, Chunk>,
+    pub blocks: &'a BlockRegistry,
+    // Chunks whose light changed and that clients need again
+    pub changed_chunks: &'a mut HashSet<IVec2>,
+}
+
+impl Lighting<'_> {
+    // Lights a chunk that was just added, and its neighbours from it
+    pub fn light_chunk(&mut self, chunk_pos: IVec2) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+        let size = CHUNK_SIZE as i32;
+
+        // The top row of the chunk below was open to the sky until now
+        let covered_row: Vec<IVec2> = (0..size).map(|x| origin + IVec2::new(x, -1)).collect();
+        if self.chunks.contains_key(&(chunk_pos - IVec2::Y)) {
+            self.relight(&covered_row);
+        }
+
+        for kind in LightKind::ALL {
+            let mut queue = VecDeque::new();
+
+            for y in 0..size {
+                for x in 0..size {
+                    let world_pos = origin + IVec2::new(x, y);
+                    let source = self.source_level(world_pos, kind);
+                    if source > 0 {
+                        self.set_light(world_pos, kind, source);
+                        queue.push_back(world_pos);
+                    }
+                }
+            }
+
+            // Light already around the chunk spreads into it
+            for i in 0..size {
+                for outside in [IVec2::new(i, -1), IVec2::new(i, size), IVec2::new(-1, i), IVec2::new(size, i)] {
+                    queue.push_back(origin + outside);
+                }
+            }
+
+            self.spread(kind, queue);
+        }
+    }
+
+    // Brings the light up to date after the blocks at the positions changed
+    pub fn relight(&mut self, positions: &[IVec2]) {
+        for kind in LightKind::ALL {
+            let mut removal = VecDeque::new();
+            for world_pos in positions {
+                let Some(level) = self.get_light(*world_pos, kind) else {
+                    continue;
+                };
+                self.set_light(*world_pos, kind, 0);
+                removal.push_back((*world_pos, level));
+            }
+
+            let mut queue = self.remove(kind, removal);
+
+            // The changed cells may give off light themselves, and their
+            // neighbours shine back into them
+            for world_pos in positions {
+                let source = self.source_level(*world_pos, kind);
+                if source > 0 {
+                    self.set_light(*world_pos, kind, source);
+                    queue.push_back(*world_pos);
+                }
+                for offset in NEIGHBOURS {
+                    queue.push_back(*world_pos + offset);
+                }
+            }
+
+            self.spread(kind, queue);
+        }
+    }
+
+    fn spread(&mut self, kind: LightKind, mut queue: VecDeque<IVec2>) {
+        while let Some(world_pos) = queue.pop_front() {
+            let Some(level) = self.get_light(world_pos, kind) else {
+                continue;
+            };
+            if level <= 1 || self.is_opaque(world_pos) {
+                continue;
+            }
+
+            for offset in NEIGHBOURS {
+                let target = world_pos + offset;
+                let candidate = self.passed_on(kind, offset, level);
+                let Some(current) = self.get_light(target, kind) else {
+                    continue;
+                };
+
+                if current < candidate {
+                    self.set_light(target, kind, candidate);
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+
+    // Darkens everything that got its light through the queued cells. Returns
+    // the lit cells found around the dark area, which spread back into it.
+    fn remove(&mut self, kind: LightKind, mut removal: VecDeque<(IVec2, u8)>) -> VecDeque<IVec2> {
+        let mut relight = VecDeque::new();
+
+        while let Some((world_pos, level)) = removal.pop_front() {
+            for offset in NEIGHBOURS {
+                let target = world_pos + offset;
+                let Some(current) = self.get_light(target, kind) else {
+                    continue;
+                };
+                if current == 0 {
+                    continue;
+                }
+
+                if current < level || current == self.passed_on(kind, offset, level) {
+                    self.set_light(target, kind, 0);
+
+                    // Sources are never darkened, they light the area again
+                    let source = self.source_level(target, kind);
+                    if source > 0 {
+                        self.set_light(target, kind, source);
+                        relight.push_back(target);
+                    } else if !self.is_opaque(target) {
+                        removal.push_back((target, current));
+                    }
+                } else {
+                    relight.push_back(target);
+                }
+            }
+        }
+
+        relight
+    }
+
+    // Light a cell gives to the neighbour at the offset
+    fn passed_on(&self, kind: LightKind, offset: IVec2, level: u8) -> u8 {
+        if kind == LightKind::Sky && offset == IVec2::NEG_Y && level == MAX_LIGHT_LEVEL {
+            return MAX_LIGHT_LEVEL;
+        }
+        level.saturating_sub(1)
+    }
+
+    fn source_level(&self, world_pos: IVec2, kind: LightKind) -> u8 {
+        let Some((chunk, chunk_relative_pos)) = self.locate(world_pos) else {
+            return 0;
+        };
+
+        match kind {
+            LightKind::Sky => {
+                let chunk_pos = world_to_chunk_pos_2d(world_pos);
+                let above = world_to_chunk_pos_2d(world_pos + IVec2::Y);
+                if above != chunk_pos && !self.chunks.contains_key(&above) { MAX_LIGHT_LEVEL } else { 0 }
+            },
+            LightKind::Block => {
+                let foreground = self.blocks.get_light(chunk.get_block(chunk_relative_pos, LayerType::Foreground));
+                let middleground = self.blocks.get_light(chunk.get_block(chunk_relative_pos, LayerType::Middleground));
+                foreground.max(middleground).min(MAX_LIGHT_LEVEL)
+            },
+        }
+    }
+
+    fn is_opaque(&self, world_pos: IVec2) -> bool {
+        self.locate(world_pos).is_none_or(|(chunk, chunk_relative_pos)| self.blocks.is_opaque(chunk.get_block(chunk_relative_pos, LayerType::Foreground)))
+    }
+
+    // None if the chunk is not loaded
+    fn get_light(&self, world_pos: IVec2, kind: LightKind) -> Option<u8> {
+        let (chunk, chunk_relative_pos) = self.locate(world_pos)?;
+        Some(chunk.get_light(chunk_relative_pos, kind))
+    }
+
+    fn set_light(&mut self, world_pos: IVec2, kind: LightKind, level: u8) {
+        let chunk_pos = world_to_chunk_pos_2d(world_pos);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+
+        let local_pos = world_to_local_pos_2d(world_pos);
+        chunk.set_light(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8), kind, level);
+        self.changed_chunks.insert(chunk_pos);
+    }
+
+    fn locate(&self, world_pos: IVec2) -> Option<(&Chunk, ChunkRelativePos)> {
+        let chunk = self.chunks.get(&world_to_chunk_pos_2d(world_pos))?;
+        let local_pos = world_to_local_pos_2d(world_pos);
+        Some((chunk, ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{common::get_data_path, server::common::BlockType};
+
+    use super::*;
+
+    // Not a tile, so the light spreads out of its own cell
+    const TORCH: u32 = 22;
+
+    fn block_light(chunks: &HashMap<IVec2, Chunk>, x: u8, y: u8) -> u8 {
+        chunks[&IVec2::ZERO].get_light(ChunkRelativePos::new(x, y), LightKind::Block)
+    }
+
+    #[test]
+    fn removed_light_source_leaves_no_light_behind() {
+        let blocks = BlockRegistry::load(&get_data_path()).unwrap();
+        let mut chunks = HashMap::from([(IVec2::ZERO, Chunk::empty())]);
+        let mut changed_chunks = HashSet::new();
+        let mut lighting = Lighting { chunks: &mut chunks, blocks: &blocks, changed_chunks: &mut changed_chunks };
+
+        let torch = ChunkRelativePos::new(10, 10);
+        lighting.chunks.get_mut(&IVec2::ZERO).unwrap().set_block(torch, LayerType::Foreground, BlockType::Sprite, TORCH, 0);
+        lighting.light_chunk(IVec2::ZERO);
+
+        let level = block_light(lighting.chunks, 10, 10);
+        assert!(level > 2);
+        assert_eq!(block_light(lighting.chunks, 12, 10), level - 2);
+
+        lighting.chunks.get_mut(&IVec2::ZERO).unwrap().set_block(torch, LayerType::Foreground, BlockType::Air, 0, 0);
+        lighting.relight(&[IVec2::new(10, 10)]);
+
+        assert!(chunks[&IVec2::ZERO].get_block_light().iter().all(|level| *level == 0));
+    }
+
+    #[test]
+    fn sky_light_stops_under_a_roof() {
+        let blocks = BlockRegistry::load(&get_data_path()).unwrap();
+        let mut chunks = HashMap::from([(IVec2::ZERO, Chunk::empty())]);
+        let mut changed_chunks = HashSet::new();
+        let mut lighting = Lighting { chunks: &mut chunks, blocks: &blocks, changed_chunks: &mut changed_chunks };
+        lighting.light_chunk(IVec2::ZERO);
+
+        let sky = |lighting: &Lighting, y: u8| lighting.chunks[&IVec2::ZERO].get_light(ChunkRelativePos::new(0, y), LightKind::Sky);
+        assert_eq!(sky(&lighting, 0), MAX_LIGHT_LEVEL);
+
+        // A roof across the whole chunk
+        let mut roof = Vec::new();
+        for x in 0..CHUNK_SIZE {
+            lighting.chunks.get_mut(&IVec2::ZERO).unwrap().set_block(ChunkRelativePos::new(x, 20), LayerType::Foreground, BlockType::Tile, 0, 0);
+            roof.push(IVec2::new(x as i32, 20));
+        }
+        lighting.relight(&roof);
+
+        assert_eq!(sky(&lighting, 25), MAX_LIGHT_LEVEL);
+        assert_eq!(sky(&lighting, 10), 0);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::IVec2;
+
+use crate::engine::{common::ChunkRelativePos, server::{chunk::Chunk, common::{world_to_chunk_pos_2d, world_to_local_pos_2d, BlockState, BlockType, LayerType}, constants::{CHUNK_SIZE, LIQUID_MAX_CELLS_PER_TICK, LIQUID_MAX_LEVEL}, data::schema_definitions::LiquidSchema, world_edit::CellChange}};
+
+/*
+/   Liquids are ordinary foreground tiles, the chunk keeps a fill level for
+/   every cell next to its block arrays. Only cells that changed, or had a
+/   neighbour change, are looked at during a tick, so a settled ocean costs
+/   nothing until something next to it is edited.
+/
+/   Each step a cell first drains down as far as there is room, then evens
+/   out with its side neighbours. Liquid only ever moves towards less of
+/   itself, so every body of it settles eventually.
+*/
+pub struct LiquidSimulation {
+    liquids: Vec<LiquidSchema>,
+    // World positions to look at during the next tick
+    active: HashSet<IVec2>,
+    // Chunks where a fill level changed, clients only hear about new blocks otherwise
+    changed_chunks: HashSet<IVec2>,
+    tick: u64,
+}
+
+// What a foreground cell is, as far as liquids care
+#[derive(Clone, Copy)]
+enum Cell {
+    Empty,
+    // Index into the liquid list and the fill level
+    Liquid(usize, u8),
+    Solid,
+}
+
+const NEIGHBOURS: [IVec2; 4] = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+
+impl LiquidSimulation {
+    pub fn new(liquids: Vec<LiquidSchema>) -> LiquidSimulation {
+        LiquidSimulation { liquids, active: HashSet::new(), changed_chunks: HashSet::new(), tick: 0 }
+    }
+
+    pub fn get_liquids(&self) -> &[LiquidSchema] {
+        &self.liquids
+    }
+
+    pub fn get_active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    // Chunks whose fill levels changed since the last call
+    pub fn take_changed_chunks(&mut self) -> Vec<IVec2> {
+        self.changed_chunks.drain().collect()
+    }
+
+    // Wakes a cell and its neighbours after the block there changed
+    pub fn activate_around(&mut self, world_pos: IVec2) {
+        self.active.insert(world_pos);
+        for offset in NEIGHBOURS {
+            self.active.insert(world_pos + offset);
+        }
+    }
+
+    // Liquid that stopped at the edge of the loaded world may now flow on.
+    // Only liquid that has moved before keeps a level, generated water stays asleep.
+    pub fn activate_chunk_border(&mut self, chunks: &HashMap<IVec2, Chunk>, chunk_pos: IVec2) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+        let size = CHUNK_SIZE as i32;
+
+        for i in 0..size {
+            for outside in [IVec2::new(i, -1), IVec2::new(i, size), IVec2::new(-1, i), IVec2::new(size, i)] {
+                let world_pos = origin + outside;
+                let Some((chunk, chunk_relative_pos)) = locate(chunks, world_pos) else {
+                    continue;
+                };
+
+                if chunk.get_liquid_level(chunk_relative_pos) != 0 && self.liquid_index(chunk.get_block(chunk_relative_pos, LayerType::Foreground)).is_some() {
+                    self.active.insert(world_pos);
+                }
+            }
+        }
+    }
+
+    // Moves liquid for one server tick and returns every block that changed
+    pub fn tick(&mut self, chunks: &mut HashMap<IVec2, Chunk>) -> Vec<CellChange> {
+        self.tick += 1;
+        let mut changes = Vec::new();
+        if self.active.is_empty() {
+            return changes;
+        }
+
+        // Lowest rows first, so a falling column moves down as one piece
+        let mut cells: Vec<IVec2> = self.active.drain().collect();
+        cells.sort_unstable_by_key(|world_pos| (world_pos.y, world_pos.x));
+        if cells.len() > LIQUID_MAX_CELLS_PER_TICK {
+            self.active.extend(cells.drain(LIQUID_MAX_CELLS_PER_TICK..));
+        }
+
+        // Swapping which side goes first every tick keeps puddles from drifting
+        let sides = if self.tick.is_multiple_of(2) { [IVec2::NEG_X, IVec2::X] } else { [IVec2::X, IVec2::NEG_X] };
+
+        for world_pos in cells {
+            let Some(Cell::Liquid(liquid, start_level)) = self.read(chunks, world_pos) else {
+                continue;
+            };
+
+            if !self.tick.is_multiple_of(self.liquids[liquid].flow_interval.max(1) as u64) {
+                self.active.insert(world_pos);
+                continue;
+            }
+
+            let mut level = start_level;
+
+            let below = world_pos - IVec2::Y;
+            if let Some(below_level) = self.room_for(chunks, below, liquid) {
+                let amount = level.min(LIQUID_MAX_LEVEL - below_level);
+                if amount > 0 {
+                    level -= amount;
+                    self.write(chunks, below, liquid, below_level + amount, &mut changes);
+                }
+            }
+
+            for side in sides {
+                let target = world_pos + side;
+                let Some(target_level) = self.room_for(chunks, target, liquid) else {
+                    continue;
+                };
+
+                // A difference of one can not be split, that is what settled looks like
+                if level >= target_level + 2 {
+                    let amount = (level - target_level) / 2;
+                    level -= amount;
+                    self.write(chunks, target, liquid, target_level + amount, &mut changes);
+                }
+            }
+
+            if level != start_level {
+                self.write(chunks, world_pos, liquid, level, &mut changes);
+                self.activate_around(world_pos);
+            }
+        }
+
+        changes
+    }
+
+    // Raises the level of liquid already in the cell by one, if it is the
+    // given block and not full. Returns false if nothing changed.
+    pub fn top_up(&mut self, chunks: &mut HashMap<IVec2, Chunk>, world_pos: IVec2, block_id: u32) -> bool {
+        let Some(Cell::Liquid(liquid, level)) = self.read(chunks, world_pos) else {
+            return false;
+        };
+        if self.liquids[liquid].block != block_id || level >= LIQUID_MAX_LEVEL {
+            return false;
+        }
+
+        // The block stays the same, so there is no change to report
+        self.write(chunks, world_pos, liquid, level + 1, &mut Vec::new());
+        self.activate_around(world_pos);
+        true
+    }
+
+    pub fn is_liquid(&self, block: BlockState) -> bool {
+        self.liquid_index(block).is_some()
+    }
+
+    fn liquid_index(&self, block: BlockState) -> Option<usize> {
+        if block.block_type != BlockType::Tile {
+            return None;
+        }
+        self.liquids.iter().position(|liquid| liquid.block == block.block_id)
+    }
+
+    // Unloaded cells count as solid, nothing flows into them
+    fn read(&self, chunks: &HashMap<IVec2, Chunk>, world_pos: IVec2) -> Option<Cell> {
+        let (chunk, chunk_relative_pos) = locate(chunks, world_pos)?;
+        let block = chunk.get_block(chunk_relative_pos, LayerType::Foreground);
+
+        if block.block_type == BlockType::Air {
+            return Some(Cell::Empty);
+        }
+
+        let Some(liquid) = self.liquid_index(block) else {
+            return Some(Cell::Solid);
+        };
+
+        let level = match chunk.get_liquid_level(chunk_relative_pos) {
+            0 => LIQUID_MAX_LEVEL,
+            level => level,
+        };
+        Some(Cell::Liquid(liquid, level))
+    }
+
+    // Level already in a cell the liquid may flow into, None if it can not.
+    // Different liquids do not mix.
+    fn room_for(&self, chunks: &HashMap<IVec2, Chunk>, world_pos: IVec2, liquid: usize) -> Option<u8> {
+        match self.read(chunks, world_pos)? {
+            Cell::Empty => Some(0),
+            Cell::Liquid(other, level) if other == liquid => Some(level),
+            Cell::Liquid(..) | Cell::Solid => None,
+        }
+    }
+
+    // A level of 0 empties the cell
+    fn write(&mut self, chunks: &mut HashMap<IVec2, Chunk>, world_pos: IVec2, liquid: usize, level: u8, changes: &mut Vec<CellChange>) {
+        let chunk_pos = world_to_chunk_pos_2d(world_pos);
+        let Some(chunk) = chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+
+        let local_pos = world_to_local_pos_2d(world_pos);
+        let chunk_relative_pos = ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8);
+
+        let before = chunk.get_block(chunk_relative_pos, LayerType::Foreground);
+        let after = match level {
+            0 => BlockState::AIR,
+            _ => BlockState::new(BlockType::Tile, self.liquids[liquid].block),
+        };
+
+        if before != after {
+            chunk.set_block(chunk_relative_pos, LayerType::Foreground, after.block_type, after.block_id, after.texture_index);
+            changes.push(CellChange { position: world_pos, layer: LayerType::Foreground, before, after });
+        }
+        chunk.set_liquid_level(chunk_relative_pos, level);
+        self.changed_chunks.insert(chunk_pos);
+    }
+}
+
+fn locate(chunks: &HashMap<IVec2, Chunk>, world_pos: IVec2) -> Option<(&Chunk, ChunkRelativePos)> {
+    let chunk = chunks.get(&world_to_chunk_pos_2d(world_pos))?;
+    let local_pos = world_to_local_pos_2d(world_pos);
+    Some((chunk, ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATER: u32 = 3;
+    const STONE: u32 = 0;
+
+    // One chunk with a stone floor in its bottom row, the unloaded world around it counts as solid
+    fn basin() -> (LiquidSimulation, HashMap<IVec2, Chunk>) {
+        let mut chunk = Chunk::empty();
+        for x in 0..CHUNK_SIZE {
+            chunk.set_block(ChunkRelativePos::new(x, 0), LayerType::Foreground, BlockType::Tile, STONE, 0);
+        }
+
+        let liquids = vec![LiquidSchema { name: "water".to_string(), block: WATER, flow_interval: 1 }];
+        (LiquidSimulation::new(liquids), HashMap::from([(IVec2::ZERO, chunk)]))
+    }
+
+    fn levels(simulation: &LiquidSimulation, chunks: &HashMap<IVec2, Chunk>) -> HashMap<IVec2, u8> {
+        let mut levels = HashMap::new();
+        for y in 0..CHUNK_SIZE as i32 {
+            for x in 0..CHUNK_SIZE as i32 {
+                if let Some(Cell::Liquid(_, level)) = simulation.read(chunks, IVec2::new(x, y)) {
+                    levels.insert(IVec2::new(x, y), level);
+                }
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn poured_liquid_settles_flat_and_keeps_its_volume() {
+        let (mut simulation, mut chunks) = basin();
+        let source = IVec2::new(16, 10);
+        chunks.get_mut(&IVec2::ZERO).unwrap().set_block(ChunkRelativePos::new(16, 10), LayerType::Foreground, BlockType::Tile, WATER, 0);
+        simulation.activate_around(source);
+
+        let mut ticks = 0;
+        while simulation.get_active_count() > 0 {
+            simulation.tick(&mut chunks);
+            ticks += 1;
+            assert!(ticks < 10_000, "liquid never settled");
+        }
+
+        let levels = levels(&simulation, &chunks);
+        assert_eq!(levels.values().map(|level| *level as u32).sum::<u32>(), LIQUID_MAX_LEVEL as u32);
+        assert!(levels.keys().all(|world_pos| world_pos.y == 1), "everything drained onto the floor");
+
+        for (world_pos, level) in &levels {
+            for side in [IVec2::X, IVec2::NEG_X] {
+                let neighbour = levels.get(&(*world_pos + side)).copied().unwrap_or(0);
+                assert!(level.abs_diff(neighbour) <= 1, "uneven at {world_pos}");
+            }
+        }
+    }
+
+    #[test]
+    fn top_up_fills_only_partial_cells_of_the_same_liquid() {
+        let (mut simulation, mut chunks) = basin();
+        let world_pos = IVec2::new(4, 1);
+        let chunk = chunks.get_mut(&IVec2::ZERO).unwrap();
+        chunk.set_block(ChunkRelativePos::new(4, 1), LayerType::Foreground, BlockType::Tile, WATER, 0);
+        chunk.set_liquid_level(ChunkRelativePos::new(4, 1), LIQUID_MAX_LEVEL - 1);
+
+        assert!(!simulation.top_up(&mut chunks, world_pos, STONE));
+        assert!(simulation.top_up(&mut chunks, world_pos, WATER));
+        assert!(!simulation.top_up(&mut chunks, world_pos, WATER), "already full");
+    }
+
+    #[test]
+    fn level_changes_mark_the_chunk_for_clients() {
+        let (mut simulation, mut chunks) = basin();
+        let chunk = chunks.get_mut(&IVec2::ZERO).unwrap();
+        chunk.set_block(ChunkRelativePos::new(4, 1), LayerType::Foreground, BlockType::Tile, WATER, 0);
+        chunk.set_liquid_level(ChunkRelativePos::new(4, 1), 8);
+
+        // Topping up keeps the block, so only the level tells clients about it
+        assert!(simulation.top_up(&mut chunks, IVec2::new(4, 1), WATER));
+        assert_eq!(simulation.take_changed_chunks(), vec![IVec2::ZERO]);
+        assert!(simulation.take_changed_chunks().is_empty());
+    }
+}
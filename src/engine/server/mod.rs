@@ -1,5 +1,24 @@
 pub mod world;
+pub mod world_edit;
+pub mod schematic;
+pub mod feature;
+pub mod ore;
+pub mod decoration;
+pub mod liquid;
+pub mod gravity;
+pub mod block_update;
+pub mod lighting;
+pub mod tile_entity;
+pub mod sprite;
+pub mod weather;
+pub mod block_registry;
+pub mod edit_history;
 pub mod server;
+pub mod connected_client;
+pub mod rcon;
+pub mod ops;
+pub mod identities;
+pub mod script;
 pub mod chunk;
 pub mod chunk_generator;
 pub mod common;
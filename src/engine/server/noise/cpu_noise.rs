@@ -4,7 +4,7 @@ use fastnoise_lite::{CellularReturnType, FastNoiseLite, FractalType, NoiseType};
 use fastrand::Rng;
 use glam::IVec2;
 
-use crate::engine::server::{chunk_generator::ThreadlocalDimensionSchema, constants::{BIOME_SAMPLE_POINT_AMOUNT, CELLULAR_NINDEX, CHUNK_BLOCK_COUNT, CHUNK_SIZE, CONTINENTAL_NINDEX, GRIDLIKE_NINDEX, HILLY_NINDEX, MOUNTAINOUS_NINDEX, TEXTURE_NINDEX}, noise::noise_util::{get_chunk_seed, interpolate_idw}};
+use crate::engine::server::{chunk_generator::ThreadlocalDimensionSchema, constants::{BIOME_SAMPLE_POINT_AMOUNT, CELLULAR_NINDEX, CHUNK_BLOCK_COUNT, CHUNK_SIZE, CONTINENTAL_NINDEX, GRIDLIKE_NINDEX, HILLY_NINDEX, MOUNTAINOUS_NINDEX, TEXTURE_NINDEX}, noise::noise_util::{get_chunk_seed, interpolate_idw, pack_signed_noise}};
 
 pub struct CPUNoise {
     biome_sampling_noise: FastNoiseLite,
@@ -14,6 +14,8 @@ pub struct CPUNoise {
     texture_noise: FastNoiseLite,
     cellular_noise: FastNoiseLite,
     gridlike_noise: FastNoiseLite,
+    worm_cave_noise: FastNoiseLite,
+    cavern_noise: FastNoiseLite,
     world_seed: i32,
     dimension_schema: ThreadlocalDimensionSchema
 }
@@ -75,6 +77,21 @@ impl CPUNoise {
         gridlike_noise.set_fractal_gain(Some(0.25));
         gridlike_noise.set_fractal_weighted_strength(Some(0.07));
 
+        // Tunnels follow the zero crossings of this one
+        let mut worm_cave_noise = FastNoiseLite::with_seed(rng.i32(..));
+        worm_cave_noise.set_frequency(Some(0.012));
+        worm_cave_noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        worm_cave_noise.set_fractal_type(Some(FractalType::FBm));
+        worm_cave_noise.set_fractal_octaves(Some(2));
+
+        let mut cavern_noise = FastNoiseLite::with_seed(rng.i32(..));
+        cavern_noise.set_frequency(Some(0.025));
+        cavern_noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        cavern_noise.set_fractal_type(Some(FractalType::FBm));
+        cavern_noise.set_fractal_octaves(Some(3));
+        cavern_noise.set_fractal_lacunarity(Some(2.0));
+        cavern_noise.set_fractal_gain(Some(0.5));
+
         CPUNoise {
             biome_sampling_noise,
             continental_noise,
@@ -83,6 +100,8 @@ impl CPUNoise {
             texture_noise,
             cellular_noise,
             gridlike_noise,
+            worm_cave_noise,
+            cavern_noise,
             world_seed,
             dimension_schema,
         }
@@ -117,6 +136,23 @@ impl CPUNoise {
         self.generate_temperature_and_humidity_map(&chunk_world_pos, sampled_temperature, sampled_humidity)
     }
 
+    // Both maps hold noise from -1 to 1 packed into a byte
+    pub fn get_cave_maps(&self, chunk_pos: &IVec2)
+    -> ([u8; CHUNK_BLOCK_COUNT as usize], [u8; CHUNK_BLOCK_COUNT as usize]) {
+        let mut worm_caves: [u8; CHUNK_BLOCK_COUNT as usize] = [0; CHUNK_BLOCK_COUNT as usize];
+        let mut caverns: [u8; CHUNK_BLOCK_COUNT as usize] = [0; CHUNK_BLOCK_COUNT as usize];
+
+        for i in 0..CHUNK_BLOCK_COUNT as usize {
+            let world_x = (i % CHUNK_SIZE as usize) as f32 + (chunk_pos.x * CHUNK_SIZE as i32) as f32;
+            let world_y = (i / CHUNK_SIZE as usize) as f32 + (chunk_pos.y * CHUNK_SIZE as i32) as f32;
+
+            worm_caves[i] = pack_signed_noise(self.worm_cave_noise.get_noise_2d(world_x, world_y));
+            caverns[i] = pack_signed_noise(self.cavern_noise.get_noise_2d(world_x, world_y));
+        }
+
+        (worm_caves, caverns)
+    }
+
     fn sample_noise_at_biome_points(&self, temperature_points: [IVec2; BIOME_SAMPLE_POINT_AMOUNT], humidity_points: [IVec2; BIOME_SAMPLE_POINT_AMOUNT], chunk_world_pos: &IVec2)
     ->  ([(IVec2, f32); BIOME_SAMPLE_POINT_AMOUNT], [(IVec2, f32); BIOME_SAMPLE_POINT_AMOUNT])
     {
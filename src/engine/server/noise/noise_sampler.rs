@@ -1,6 +1,6 @@
 use crate::engine::server::chunk_generator::ThreadlocalDimensionSchema;
 use crate::engine::server::common::{world_to_chunk_pos, world_to_chunk_pos_2d, world_to_local_pos, world_to_local_pos_2d};
-use crate::engine::server::constants::{CHUNK_SIZE, HUMIDITY_INDEX, TEMPERATURE_INDEX};
+use crate::engine::server::constants::{CAVERN_INDEX, CHUNK_SIZE, HUMIDITY_INDEX, TEMPERATURE_INDEX, WORM_CAVE_INDEX};
 #[cfg(not(feature = "gpu-server"))]
 use crate::engine::server::constants::{NUM_1D_NOISE_LAYERS, NUM_2D_NOISE_LAYERS};
 #[cfg(not(feature = "gpu-server"))]
@@ -93,19 +93,7 @@ impl NoiseSampler {
         let chunk_pos = world_to_chunk_pos_2d(*world_pos);
 
         let layer = *self.cache_2d[noise_layer_index].entry(chunk_pos).or_insert_with(|| {
-            let (temperature, humidity) = self.cpu_noise.get_temperature_and_humidity_map(&chunk_pos);
-            let temperature = NoiseLayer2D {layer: temperature};
-            let humidity = NoiseLayer2D {layer: humidity};
-            let return_value = match noise_layer_index {
-                TEMPERATURE_INDEX => {temperature}
-                HUMIDITY_INDEX => {humidity}
-                _ => {panic!("2D Layer index not found")}
-            };
-
-            self.cache_2d[TEMPERATURE_INDEX].insert(chunk_pos, temperature);
-            self.cache_2d[HUMIDITY_INDEX].insert(chunk_pos, humidity);
-
-            return_value
+            self.generate_layer_2d(&chunk_pos, noise_layer_index)
         });
 
         layer.read(chunk_relative_pos)
@@ -120,22 +108,32 @@ impl NoiseSampler {
 
             // key not found, we just create the whole thing ourselves
             None => {
-                let (temperature, humidity) = self.cpu_noise.get_temperature_and_humidity_map(&chunk_pos);
-                let temperature = NoiseLayer2D {layer: temperature};
-                let humidity = NoiseLayer2D {layer: humidity};
-                let return_value = match noise_layer_index {
-                    TEMPERATURE_INDEX => {temperature}
-                    HUMIDITY_INDEX => {humidity}
-                    _ => {panic!("2D Layer index not found")}
-                };
-
-                return_value
+                self.generate_layer_2d(chunk_pos, noise_layer_index)
             }
         };
 
         layer
     }
 
+    // 2D layers are generated in pairs. The one that was not asked for goes
+    // into the cache, where the chunk generator picks it up right after.
+    fn generate_layer_2d(&self, chunk_pos: &IVec2, noise_layer_index: usize) -> NoiseLayer2D {
+        let (first_index, second_index, (first, second)) = match noise_layer_index {
+            TEMPERATURE_INDEX | HUMIDITY_INDEX => (TEMPERATURE_INDEX, HUMIDITY_INDEX, self.cpu_noise.get_temperature_and_humidity_map(chunk_pos)),
+            WORM_CAVE_INDEX | CAVERN_INDEX => (WORM_CAVE_INDEX, CAVERN_INDEX, self.cpu_noise.get_cave_maps(chunk_pos)),
+            _ => {panic!("2D Layer index not found")}
+        };
+
+        let (requested, other_index, other) = if noise_layer_index == first_index {
+            (first, second_index, second)
+        } else {
+            (second, first_index, first)
+        };
+
+        self.cache_2d[other_index].insert(*chunk_pos, NoiseLayer2D {layer: other});
+        NoiseLayer2D {layer: requested}
+    }
+
     fn get_temperature_and_humidity_map(&self, chunk_pos: &IVec2)
     -> ([u8; CHUNK_BLOCK_COUNT as usize], [u8; CHUNK_BLOCK_COUNT as usize]) {
         self.cpu_noise.get_temperature_and_humidity_map(chunk_pos)
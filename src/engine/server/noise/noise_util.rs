@@ -45,6 +45,15 @@ pub fn interpolate_idw(
     }
 }
 
+// Maps noise from -1 to 1 onto a byte, so it fits a NoiseLayer2D
+pub fn pack_signed_noise(value: f32) -> u8 {
+    ((value.clamp(-1.0, 1.0) + 1.0) * 127.5).round() as u8
+}
+
+pub fn unpack_signed_noise(value: u8) -> f32 {
+    value as f32 / 127.5 - 1.0
+}
+
 pub fn get_chunk_seed(world_seed: i32, chunk_pos: &IVec2) -> u64 {
     let s = world_seed as u32 as u64;
     let xx = chunk_pos.x as u32 as u64;
@@ -0,0 +1,84 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::command_registry::PermissionLevel;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OpEntry {
+    // Hex, the same way the clients command prints it
+    pub uuid: String,
+    // Last known nickname, only for reading the file
+    pub nickname: String,
+    pub level: PermissionLevel,
+}
+
+// Permission levels of players, keyed by UUID and saved to disk on every change.
+// Players that are not listed have the player level.
+pub struct OpsList {
+    path: PathBuf,
+    entries: HashMap<u64, OpEntry>,
+}
+
+impl OpsList {
+    pub fn load(path: impl Into<PathBuf>) -> OpsList {
+        let path = path.into();
+        let mut entries = HashMap::new();
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str::<Vec<OpEntry>>(&contents) {
+                Ok(list) => {
+                    for entry in list {
+                        match u64::from_str_radix(&entry.uuid, 16) {
+                            Ok(uuid) => { entries.insert(uuid, entry); },
+                            Err(_) => println!("Skipping ops entry with invalid UUID \"{}\"", entry.uuid),
+                        }
+                    }
+                },
+                Err(error) => println!("Failed to parse {}: {error}", path.display()),
+            },
+            // No file yet, nobody has been opped
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {},
+            Err(error) => println!("Failed to read {}: {error}", path.display()),
+        }
+
+        OpsList { path, entries }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut list: Vec<&OpEntry> = self.entries.values().collect();
+        list.sort_by(|a, b| a.nickname.cmp(&b.nickname));
+
+        let contents = serde_json::to_string_pretty(&list).map_err(std::io::Error::other)?;
+        std::fs::write(&self.path, contents)
+    }
+
+    pub fn get_level(&self, uuid: u64) -> PermissionLevel {
+        self.entries.get(&uuid).map_or(PermissionLevel::Player, |entry| entry.level)
+    }
+
+    // Setting a player back to the player level removes them from the list
+    pub fn set_level(&mut self, uuid: u64, nickname: &str, level: PermissionLevel) -> std::io::Result<()> {
+        if level == PermissionLevel::Player {
+            self.entries.remove(&uuid);
+        } else {
+            self.entries.insert(uuid, OpEntry {
+                uuid: format!("{uuid:016x}"),
+                nickname: nickname.to_string(),
+                level,
+            });
+        }
+
+        self.save()
+    }
+
+    pub fn find_by_nickname(&self, nickname: &str) -> Option<u64> {
+        self.entries.iter()
+            .find(|(_, entry)| entry.nickname.eq_ignore_ascii_case(nickname))
+            .map(|(uuid, _)| *uuid)
+    }
+
+    pub fn get_entries(&self) -> impl Iterator<Item = &OpEntry> {
+        self.entries.values()
+    }
+}
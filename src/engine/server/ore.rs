@@ -0,0 +1,56 @@
+use fastrand::Rng;
+use glam::IVec2;
+
+use crate::engine::{common::ChunkRelativePos, server::{biome::Biome, chunk::Chunk, common::{BlockType, LayerType}, constants::{CHUNK_SIZE, ORE_SEED_SALT}, data::schema_definitions::OreSchema, noise::noise_util::get_chunk_seed}};
+
+const STEPS: [IVec2; 4] = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+
+/*
+/   Grows ore veins as short random walks from random starting points.
+/   Veins stay inside the chunk, a step that would leave it is skipped,
+/   so every chunk can be decorated on its own. The walk only replaces
+/   the tiles the ore lists, which keeps caves and water untouched.
+*/
+pub fn place_ores<'a>(chunk: &mut Chunk, chunk_pos: &IVec2, ores: &[OreSchema], biome_at: impl Fn(usize) -> &'a Biome, seed: i32) {
+    let mut rng = Rng::with_seed(get_chunk_seed(seed, chunk_pos) ^ ORE_SEED_SALT);
+    let chunk_world_y = chunk_pos.y * CHUNK_SIZE as i32;
+
+    for ore in ores {
+        let mut vein_count = ore.veins_per_chunk as u32;
+        if rng.f32() < ore.veins_per_chunk.fract() {
+            vein_count += 1;
+        }
+
+        for _ in 0..vein_count {
+            let start = IVec2::new(rng.i32(0..CHUNK_SIZE as i32), rng.i32(0..CHUNK_SIZE as i32));
+            let world_y = start.y + chunk_world_y;
+            if world_y < ore.min_y || world_y > ore.max_y {
+                continue;
+            }
+
+            let biome = biome_at(start.y as usize * CHUNK_SIZE as usize + start.x as usize);
+            if !ore.biomes.is_empty() && !ore.biomes.contains(&biome.name) {
+                continue;
+            }
+
+            grow_vein(chunk, ore, start, &mut rng);
+        }
+    }
+}
+
+fn grow_vein(chunk: &mut Chunk, ore: &OreSchema, start: IVec2, rng: &mut Rng) {
+    let mut position = start;
+
+    for _ in 0..ore.vein_size {
+        let chunk_relative_pos = ChunkRelativePos::new(position.x as u8, position.y as u8);
+        let block = chunk.get_block(chunk_relative_pos, LayerType::Foreground);
+        if block.block_type == BlockType::Tile && ore.replaces.contains(&block.block_id) {
+            chunk.set_block(chunk_relative_pos, LayerType::Foreground, BlockType::Tile, ore.block, 0);
+        }
+
+        let next = position + STEPS[rng.usize(0..STEPS.len())];
+        if next.cmpge(IVec2::ZERO).all() && next.cmplt(IVec2::splat(CHUNK_SIZE as i32)).all() {
+            position = next;
+        }
+    }
+}
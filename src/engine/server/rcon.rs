@@ -0,0 +1,221 @@
+use std::{io::{BufRead, BufReader, Write}, net::{TcpListener, TcpStream}, sync::mpsc::{Receiver, Sender}};
+
+use crate::engine::{command_registry::{self, CommandEnvironment, CommandResult, DebugCommandWithArgs}, server::constants::{RCON_MAX_AUTH_ATTEMPTS, RCON_PASSWORD_ENV, RCON_PORT_ENV, RCON_DEFAULT_PORT}};
+
+/*
+/   Line based remote console. After connecting, send `auth <password>`,
+/   then one command per line. Every reply is zero or more output lines
+/   prefixed with "> ", an optional "DATA <json>" line, and finally a
+/   single "OK", "ERR command failed" or "ERR <reason>" line.
+*/
+
+pub struct RemoteCommand {
+    pub command: DebugCommandWithArgs,
+    pub reply: Sender<CommandResult>,
+}
+
+// Returns None if no password is configured, remote access stays disabled then
+pub fn start_rcon() -> Option<Receiver<RemoteCommand>> {
+    let Ok(password) = std::env::var(RCON_PASSWORD_ENV) else {
+        println!("RCON disabled - set {RCON_PASSWORD_ENV} to enable it");
+        return None;
+    };
+
+    if password.is_empty() {
+        println!("RCON disabled - {RCON_PASSWORD_ENV} is empty");
+        return None;
+    }
+
+    let port = match std::env::var(RCON_PORT_ENV) {
+        Ok(port) => match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => {
+                println!("Invalid {RCON_PORT_ENV} \"{port}\", using {RCON_DEFAULT_PORT}");
+                RCON_DEFAULT_PORT
+            }
+        },
+        Err(_) => RCON_DEFAULT_PORT,
+    };
+
+    // Only ever reachable from this machine
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("RCON failed to bind to port {port}: {error}");
+            return None;
+        }
+    };
+
+    let (tx_remote_to_server, rx_remote_to_server) = std::sync::mpsc::channel::<RemoteCommand>();
+
+    std::thread::spawn(move || {
+        println!("RCON listening on 127.0.0.1:{port}");
+
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+
+            let tx_to_server = tx_remote_to_server.clone();
+            let password = password.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+                if let Err(error) = handle_connection(stream, &password, tx_to_server) {
+                    println!("RCON connection {peer} closed with error: {error}");
+                }
+            });
+        }
+    });
+
+    Some(rx_remote_to_server)
+}
+
+fn handle_connection(stream: TcpStream, password: &str, tx_to_server: Sender<RemoteCommand>) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+    let mut authenticated = false;
+    let mut failed_attempts = 0;
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if !authenticated {
+            let attempt = line.strip_prefix("auth ").unwrap_or("");
+            if constant_time_eq(attempt.as_bytes(), password.as_bytes()) {
+                authenticated = true;
+                writeln!(writer, "OK")?;
+            } else {
+                failed_attempts += 1;
+                writeln!(writer, "ERR authentication failed")?;
+                if failed_attempts >= RCON_MAX_AUTH_ATTEMPTS {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+
+        match dispatch(line, &tx_to_server) {
+            Ok(result) => {
+                for output_line in &result.lines {
+                    writeln!(writer, "> {output_line}")?;
+                }
+                if let Some(data) = &result.data {
+                    writeln!(writer, "DATA {data}")?;
+                }
+                if result.is_success() {
+                    writeln!(writer, "OK")?;
+                } else {
+                    writeln!(writer, "ERR command failed")?;
+                }
+            },
+            Err(reason) => writeln!(writer, "ERR {reason}")?,
+        }
+    }
+
+    Ok(())
+}
+
+// Err means the command never ran, a failed command is still Ok
+fn dispatch(line: &str, tx_to_server: &Sender<RemoteCommand>) -> Result<CommandResult, String> {
+    let command = command_registry::parse_command_line(line)?;
+
+    if command.debug_command.command_environment == CommandEnvironment::Client {
+        return Err("client commands cannot be run remotely".to_string());
+    }
+
+    // Everything runs on the server thread, between ticks
+    let (reply, reply_listener) = std::sync::mpsc::channel::<CommandResult>();
+    if tx_to_server.send(RemoteCommand { command, reply }).is_err() {
+        return Err("server is not running".to_string());
+    }
+
+    reply_listener.recv().map_err(|_| "server stopped before replying".to_string())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{command_registry::capture_output, server::server::Server};
+
+    use super::*;
+
+    // Starts a connection and returns the client end, the session ends once
+    // the input has been answered
+    fn open_session(input: &str, tx_to_server: Sender<RemoteCommand>) -> TcpStream {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        std::thread::spawn(move || handle_connection(stream, "secret", tx_to_server));
+
+        client.write_all(input.as_bytes()).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        client
+    }
+
+    fn read_reply(client: TcpStream) -> Vec<String> {
+        BufReader::new(client).lines().map(Result::unwrap).collect()
+    }
+
+    // Runs one connection against a stand-in for the server thread, which
+    // answers every command the way process_commands does
+    fn run_session(input: &str) -> Vec<String> {
+        let (tx_to_server, rx_to_server) = std::sync::mpsc::channel::<RemoteCommand>();
+        std::thread::spawn(move || {
+            for remote_command in rx_to_server {
+                let result = capture_output(|output| {
+                    output.write_line("written while running");
+                    CommandResult::success_with("done").with_data(serde_json::json!({ "count": 1 }))
+                });
+                let _ = remote_command.reply.send(result);
+            }
+        });
+
+        read_reply(open_session(input, tx_to_server))
+    }
+
+    #[test]
+    fn replies_carry_the_command_output() {
+        let reply = run_session("auth secret\nhelp\n");
+        assert_eq!(reply, ["OK", "> written while running", "> done", "DATA {\"count\":1}", "OK"]);
+    }
+
+    // The server thread keeps ticking while the session waits on it
+    #[test]
+    fn replies_come_from_the_server_command_queue() {
+        let (tx_to_server, rx_to_server) = std::sync::mpsc::channel::<RemoteCommand>();
+        let mut server = Server::empty(Some(rx_to_server));
+
+        let client = open_session("auth secret\ndimensions\nkillprocess\n", tx_to_server);
+        let reader = std::thread::spawn(move || read_reply(client));
+        while !reader.is_finished() {
+            server.process_commands();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert_eq!(reader.join().unwrap(), [
+            "OK",
+            "DATA []",
+            "OK",
+            "> You do not have permission to run \"killprocess\", it requires console level.",
+            "ERR command failed",
+        ]);
+    }
+
+    #[test]
+    fn commands_before_auth_are_refused() {
+        let reply = run_session("help\nauth secret\n");
+        assert_eq!(reply, ["ERR authentication failed", "OK"]);
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+
+use bincode::{Decode, Encode};
+use glam::{I64Vec2, IVec2};
+
+use crate::engine::{common::{get_data_path, resolve_file_name}, server::{common::{BlockState, BlockType, LayerType}, constants::{SCHEMATICS_DIRECTORY, SCHEMATIC_EXTENSION, WORLD_EDIT_MAX_BLOCKS}, world::Dimension, world_edit::{EditOutcome, EditRegion}}};
+
+/*
+/   Schematic files start with the magic bytes and a little endian format
+/   version, followed by the lz4 compressed bincode of the Schematic.
+/   Bump the version whenever the Schematic layout changes.
+*/
+const SCHEMATIC_MAGIC: &[u8; 4] = b"SCHM";
+const SCHEMATIC_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mirror {
+    None,
+    X,
+    Y,
+}
+
+// Mirroring happens before rotating
+#[derive(Clone, Copy, Debug)]
+pub struct SchematicTransform {
+    // Clockwise, in steps of 90 degrees
+    pub quarter_turns: u8,
+    pub mirror: Mirror,
+}
+
+// A copy of a rectangular region across all layers, independent of where it came from
+#[derive(Encode, Decode, Clone)]
+pub struct Schematic {
+    pub width: u32,
+    pub height: u32,
+    // Row by row from the minimum corner, one array per layer in LayerType::ALL order
+    layers: [Vec<BlockState>; 3],
+}
+
+impl Schematic {
+    // Fails if part of the region is not loaded, a partial copy is never what you want
+    pub fn capture(dimension: &Dimension, region: EditRegion) -> Result<Schematic, String> {
+        region.check_size()?;
+
+        let size = region.max.as_i64vec2() - region.min.as_i64vec2() + 1;
+        let mut layers: [Vec<BlockState>; 3] = Default::default();
+
+        for (layer_index, layer) in LayerType::ALL.iter().enumerate() {
+            for world_pos in region.positions() {
+                let Some(block) = dimension.get_block(world_pos, *layer) else {
+                    return Err(format!("Block at {}, {} is in a chunk that is not loaded", world_pos.x, world_pos.y));
+                };
+                layers[layer_index].push(block);
+            }
+        }
+
+        Ok(Schematic { width: size.x as u32, height: size.y as u32, layers })
+    }
+
+    pub fn get(&self, x: u32, y: u32, layer: LayerType) -> BlockState {
+        self.layers[layer as usize][(y * self.width + x) as usize]
+    }
+
+    pub fn block_count(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    pub fn transformed(&self, transform: SchematicTransform) -> Schematic {
+        let quarter_turns = transform.quarter_turns % 4;
+        let (width, height) = if quarter_turns % 2 == 1 { (self.height, self.width) } else { (self.width, self.height) };
+        let mut layers: [Vec<BlockState>; 3] = std::array::from_fn(|_| vec![BlockState::AIR; (width * height) as usize]);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (mut new_x, mut new_y) = match transform.mirror {
+                    Mirror::None => (x, y),
+                    Mirror::X => (self.width - 1 - x, y),
+                    Mirror::Y => (x, self.height - 1 - y),
+                };
+
+                // y points up, so a clockwise turn maps (x, y) to (y, -x)
+                let (mut current_width, mut current_height) = (self.width, self.height);
+                for _ in 0..quarter_turns {
+                    (new_x, new_y) = (new_y, current_width - 1 - new_x);
+                    (current_width, current_height) = (current_height, current_width);
+                }
+
+                let source = (y * self.width + x) as usize;
+                let target = (new_y * width + new_x) as usize;
+                for (layer, source_layer) in layers.iter_mut().zip(&self.layers) {
+                    layer[target] = source_layer[source];
+                }
+            }
+        }
+
+        Schematic { width, height, layers }
+    }
+
+    // The minimum corner of the schematic lands on the origin
+    pub fn paste(&self, dimension: &mut Dimension, origin: IVec2, skip_air: bool) -> Result<EditOutcome, String> {
+        let far_corner = origin.as_i64vec2() + I64Vec2::new(self.width as i64, self.height as i64) - 1;
+        if far_corner.x > i32::MAX as i64 || far_corner.y > i32::MAX as i64 {
+            return Err(format!("A {}x{} schematic does not fit at {}, {}", self.width, self.height, origin.x, origin.y));
+        }
+
+        let mut outcome = EditOutcome::default();
+
+        for layer in LayerType::ALL {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let block = self.get(x, y, layer);
+                    if skip_air && block.block_type == BlockType::Air {
+                        continue;
+                    }
+
+                    outcome.set(dimension, origin + IVec2::new(x as i32, y as i32), layer, block);
+                }
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let encoded = bincode::encode_to_vec(self, bincode::config::standard()).unwrap();
+
+        let mut bytes = Vec::with_capacity(encoded.len() / 2);
+        bytes.extend_from_slice(SCHEMATIC_MAGIC);
+        bytes.extend_from_slice(&SCHEMATIC_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&lz4_flex::compress_prepend_size(&encoded));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Schematic, String> {
+        let Some(body) = bytes.strip_prefix(SCHEMATIC_MAGIC) else {
+            return Err("Not a schematic file".to_string());
+        };
+
+        let (version, body) = body.split_at_checked(2).ok_or("Schematic file is truncated")?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version != SCHEMATIC_VERSION {
+            return Err(format!("Schematic has format version {version}, this build reads version {SCHEMATIC_VERSION}"));
+        }
+
+        let decompressed = lz4_flex::decompress_size_prepended(body).map_err(|error| format!("Schematic is corrupted: {error}"))?;
+        let (schematic, _): (Schematic, usize) = bincode::decode_from_slice(&decompressed, bincode::config::standard())
+            .map_err(|error| format!("Schematic is corrupted: {error}"))?;
+
+        let expected_len = schematic.block_count() as usize;
+        if schematic.layers.iter().any(|layer| layer.len() != expected_len) {
+            return Err("Schematic size does not match its contents".to_string());
+        }
+
+        Ok(schematic)
+    }
+
+    pub fn save(&self, name: &str) -> Result<PathBuf, String> {
+        let path = resolve_file_name(Path::new(SCHEMATICS_DIRECTORY), name, SCHEMATIC_EXTENSION)?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|error| format!("Failed to create {}: {error}", directory.display()))?;
+        }
+
+        std::fs::write(&path, self.to_bytes()).map_err(|error| format!("Failed to write {}: {error}", path.display()))?;
+        Ok(path)
+    }
+
+    // Saved schematics win over the ones shipped in the data directory
+    pub fn load(name: &str) -> Result<Schematic, String> {
+        for directory in schematic_directories() {
+            if resolve_file_name(&directory, name, SCHEMATIC_EXTENSION)?.exists() {
+                return Schematic::load_from(&directory, name);
+            }
+        }
+
+        Err(format!("No schematic named \"{name}\""))
+    }
+
+    pub fn load_from(directory: &Path, name: &str) -> Result<Schematic, String> {
+        let path = resolve_file_name(directory, name, SCHEMATIC_EXTENSION)?;
+        let bytes = std::fs::read(&path).map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+        Schematic::from_bytes(&bytes).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    pub fn check_size(&self) -> Result<(), String> {
+        if self.block_count() > WORLD_EDIT_MAX_BLOCKS {
+            return Err(format!("Schematic of {} blocks is too large, the limit is {WORLD_EDIT_MAX_BLOCKS}", self.block_count()));
+        }
+        Ok(())
+    }
+}
+
+pub fn schematic_directories() -> [PathBuf; 2] {
+    [PathBuf::from(SCHEMATICS_DIRECTORY), get_data_path().join(SCHEMATICS_DIRECTORY)]
+}
+
+// Names of every schematic that can be loaded, relative to their directory and without the extension
+pub fn list_schematics() -> Vec<String> {
+    let mut names = Vec::new();
+
+    for directory in schematic_directories() {
+        collect_schematics(&directory, &directory, &mut names);
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn collect_schematics(root: &Path, directory: &Path, names: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_schematics(root, &path, names);
+        } else if path.extension().is_some_and(|extension| extension == SCHEMATIC_EXTENSION)
+            && let Ok(relative) = path.with_extension("").strip_prefix(root) {
+            names.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every cell holds a tile whose id is its position, 10 * x + y
+    fn numbered(width: u32, height: u32) -> Schematic {
+        let cells: Vec<BlockState> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| BlockState::new(BlockType::Tile, 10 * x + y)))
+            .collect();
+        Schematic { width, height, layers: [cells.clone(), cells.clone(), cells] }
+    }
+
+    fn id_at(schematic: &Schematic, x: u32, y: u32) -> u32 {
+        schematic.get(x, y, LayerType::Foreground).block_id
+    }
+
+    #[test]
+    fn quarter_turn_is_clockwise() {
+        let source = numbered(3, 2);
+        let turned = source.transformed(SchematicTransform { quarter_turns: 1, mirror: Mirror::None });
+
+        assert_eq!((turned.width, turned.height), (2, 3));
+        // The top left corner ends up top right, the bottom left one top left
+        assert_eq!(id_at(&turned, 1, 2), id_at(&source, 0, 1));
+        assert_eq!(id_at(&turned, 0, 2), id_at(&source, 0, 0));
+        assert_eq!(id_at(&turned, 0, 0), id_at(&source, 2, 0));
+    }
+
+    #[test]
+    fn mirroring_flips_one_axis() {
+        let source = numbered(3, 2);
+        let mirrored_x = source.transformed(SchematicTransform { quarter_turns: 0, mirror: Mirror::X });
+        let mirrored_y = source.transformed(SchematicTransform { quarter_turns: 0, mirror: Mirror::Y });
+
+        assert_eq!(id_at(&mirrored_x, 0, 0), id_at(&source, 2, 0));
+        assert_eq!(id_at(&mirrored_y, 0, 0), id_at(&source, 0, 1));
+    }
+
+    #[test]
+    fn four_turns_change_nothing() {
+        let source = numbered(4, 3);
+        let turned = source.transformed(SchematicTransform { quarter_turns: 4, mirror: Mirror::None });
+        let twice = source.transformed(SchematicTransform { quarter_turns: 2, mirror: Mirror::None })
+            .transformed(SchematicTransform { quarter_turns: 2, mirror: Mirror::None });
+
+        for schematic in [turned, twice] {
+            assert_eq!((schematic.width, schematic.height), (4, 3));
+            assert!(schematic.layers == source.layers);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use crate::engine::{command_registry::CommandSource, common::resolve_file_name, server::constants::{SCRIPTS_DIRECTORY, SCRIPT_EXTENSION}};
+
+/*
+/   A command script is a text file with one console command per line.
+/   Blank lines and lines starting with # or // are skipped, and
+/   `wait <ticks>` pauses the script for that many server ticks.
+/   Commands between waits all run in the same tick.
+*/
+
+enum ScriptLine {
+    Command(String),
+    Wait(u32),
+}
+
+pub enum ScriptStep {
+    Waiting,
+    Finished,
+    Command { line_number: usize, command_line: String },
+}
+
+pub struct CommandScript {
+    pub name: String,
+    // Commands run on behalf of whoever started the script
+    pub source: CommandSource,
+    pub stop_on_error: bool,
+    lines: Vec<(usize, ScriptLine)>,
+    cursor: usize,
+    wait_ticks: u32,
+}
+
+impl CommandScript {
+    pub fn load(name: &str, source: CommandSource, stop_on_error: bool) -> Result<CommandScript, String> {
+        let path = script_path(name)?;
+        let contents = std::fs::read_to_string(&path).map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+
+        let mut lines = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+
+            // Checked up front so a typo does not surface halfway through a benchmark
+            let mut words = line.split_whitespace();
+            if words.next() == Some("wait") {
+                let ticks: Vec<&str> = words.collect();
+                let [ticks] = ticks[..] else {
+                    return Err(format!("{name}:{line_number}: wait expects a number of ticks, got \"{}\"", ticks.join(" ")));
+                };
+                let Ok(ticks) = ticks.parse::<u32>() else {
+                    return Err(format!("{name}:{line_number}: wait expects a number of ticks, got \"{ticks}\""));
+                };
+                lines.push((line_number, ScriptLine::Wait(ticks)));
+            } else {
+                lines.push((line_number, ScriptLine::Command(line.to_string())));
+            }
+        }
+
+        Ok(CommandScript {
+            name: name.to_string(),
+            source,
+            stop_on_error,
+            lines,
+            cursor: 0,
+            wait_ticks: 0,
+        })
+    }
+
+    pub fn command_count(&self) -> usize {
+        self.lines.iter().filter(|(_, line)| matches!(line, ScriptLine::Command(_))).count()
+    }
+
+    // Called until it returns Waiting or Finished, once per tick
+    pub fn next_step(&mut self) -> ScriptStep {
+        loop {
+            if self.wait_ticks > 0 {
+                self.wait_ticks -= 1;
+                return ScriptStep::Waiting;
+            }
+
+            let Some((line_number, line)) = self.lines.get(self.cursor) else {
+                return ScriptStep::Finished;
+            };
+            self.cursor += 1;
+
+            match line {
+                ScriptLine::Wait(ticks) => self.wait_ticks = *ticks,
+                ScriptLine::Command(command_line) => {
+                    return ScriptStep::Command { line_number: *line_number, command_line: command_line.clone() };
+                }
+            }
+        }
+    }
+}
+
+pub fn script_path(name: &str) -> Result<PathBuf, String> {
+    resolve_file_name(Path::new(SCRIPTS_DIRECTORY), name, SCRIPT_EXTENSION)
+}
@@ -1,17 +1,30 @@
-use std::{collections::{hash_map::Keys, HashMap}, sync::mpsc::{Receiver, Sender}};
-use crate::engine::{command_registry::{self, DebugCommandWithArgs}, common::{get_data_path, PacketHeader, ServerPacket}, server::{data::schema_definitions::DimensionSchema, world::Dimension}};
+use std::{collections::{hash_map::Keys, HashMap, HashSet}, sync::mpsc::Receiver, time::{Duration, Instant}};
+use glam::{IVec2, Vec2};
+use crate::engine::{command_registry::{self, capture_output, CommandActor, CommandEnvironment, CommandResult, ConsoleOutput, CommandSource, DebugCommandWithArgs, PermissionLevel}, console, common::{get_data_path, BlockChange, ChatMessage, ClientPacket, HandshakePacket, PacketChunk, PacketChunkLight, PacketChunkLiquid, ServerPacket, ServerStatus}, network::{encode_packet, Connection}, server::{connected_client::ConnectedClient, edit_history::{EditJournal, EditOperation}, gravity, identities::PlayerIdentities, ops::OpsList, rcon::{self, RemoteCommand}, script::{self, CommandScript, ScriptStep}, weather, common::{world_to_chunk_pos_2d, BlockState}, world_edit::{CellChange, EditOutcome}, constants::{BLOCK_EDIT_PERMISSION_LEVEL, BLOCK_EDIT_REACH, CHAT_MAX_MESSAGE_LENGTH, CHUNK_REQUEST_MAX_PENDING, IDENTITIES_FILE, KEEPALIVE_MAX_MISSED_PINGS, OPS_FILE, SCRIPT_MAX_DEPTH, STARTUP_SCRIPT, TILE_ENTITY_PERMISSION_LEVEL, WORLD_EDIT_BLOCK_PACKET_LIMIT}, data::schema_definitions::DimensionSchema, world::Dimension}};
 
 pub struct Server {
     pub dimensions: HashMap<String, Dimension>,
     running: bool,
     console_listener: Receiver<DebugCommandWithArgs>,
-    client_sender: Sender<Vec<u8>>,
+    remote_listener: Option<Receiver<RemoteCommand>>,
+    connection_listener: Receiver<Connection>,
+    // With the fixed permission level of local connections
+    pending_connections: Vec<(Connection, Option<PermissionLevel>)>,
+    clients: HashMap<u64, ConnectedClient>,
+    ops: OpsList,
+    identities: PlayerIdentities,
+    // Nested scripts are pushed on top, only the last one runs
+    scripts: Vec<CommandScript>,
+    edit_journal: EditJournal,
     pub compress_sent_data: bool,
-    dimension_schemas: Vec<DimensionSchema>
+    dimension_schemas: Vec<DimensionSchema>,
+    tick_sample_start: Instant,
+    ticks_in_sample: u32,
+    ticks_per_second: f32,
 }
 
 impl Server {
-    pub fn start_server(console_listener: Receiver<DebugCommandWithArgs>, client_sender: Sender<Vec<u8>>) -> Server {
+    pub fn start_server(console_listener: Receiver<DebugCommandWithArgs>, connection_listener: Receiver<Connection>) -> Server {
         let dimension_schemas: Vec<DimensionSchema> = match Dimension::load_dimensions(&get_data_path()) {
             Ok(schemas) => schemas,
             Err(error) => panic!("Problem opening file: {error:?}")
@@ -24,67 +37,689 @@ impl Server {
             dimensions.insert(schema.name.clone(), Dimension::from_schema(schema, seed));
         }
 
-        return Server {
+        console::set_dimension_names(dimensions.keys().cloned().collect());
+
+        let mut server = Server {
             dimensions,
             running: true,
             console_listener: console_listener,
-            client_sender: client_sender,
+            remote_listener: rcon::start_rcon(),
+            connection_listener,
+            pending_connections: Vec::new(),
+            clients: HashMap::new(),
+            ops: OpsList::load(OPS_FILE),
+            identities: PlayerIdentities::load(IDENTITIES_FILE),
+            scripts: Vec::new(),
+            edit_journal: EditJournal::new(),
             compress_sent_data: true,
             dimension_schemas,
+            tick_sample_start: Instant::now(),
+            ticks_in_sample: 0,
+            ticks_per_second: 0.0,
+        };
+
+        if let Ok(path) = script::script_path(STARTUP_SCRIPT) && path.exists() {
+            match CommandScript::load(STARTUP_SCRIPT, CommandSource::console(), true) {
+                Ok(script) => {
+                    println!("Running startup script {}", path.display());
+                    let _ = server.start_script(script);
+                },
+                Err(error) => println!("Startup script not run: {error}"),
+            }
+        }
+
+        server
+    }
+
+    // No dimensions, files or listeners, for tests that drive the server by hand
+    #[cfg(test)]
+    pub fn empty(remote_listener: Option<Receiver<RemoteCommand>>) -> Server {
+        Server {
+            dimensions: HashMap::new(),
+            running: true,
+            console_listener: std::sync::mpsc::channel().1,
+            remote_listener,
+            connection_listener: std::sync::mpsc::channel().1,
+            pending_connections: Vec::new(),
+            clients: HashMap::new(),
+            // An empty path never exists, so nothing is read or written until someone is opped
+            ops: OpsList::load(""),
+            identities: PlayerIdentities::load(""),
+            scripts: Vec::new(),
+            edit_journal: EditJournal::new(),
+            compress_sent_data: false,
+            dimension_schemas: Vec::new(),
+            tick_sample_start: Instant::now(),
+            ticks_in_sample: 0,
+            ticks_per_second: 0.0,
         }
     }
 
     pub fn stop(&mut self) {
         println!("Stopping server!");
-        // TODO: Nothing here yet, add saving later
+        self.save();
         self.running = false;
     }
 
+    // Blocks still falling are put down first, the chunks only hold blocks
+    pub fn save(&mut self) {
+        let settled: Vec<(String, Vec<CellChange>)> = self.dimensions.iter_mut()
+            .map(|(name, dimension)| (name.clone(), gravity::settle_falling_blocks(dimension)))
+            .filter(|(_, changes)| !changes.is_empty())
+            .collect();
+        for (dimension_name, changes) in settled {
+            self.broadcast_block_changes(&dimension_name, &changes);
+        }
+
+        for dimension in self.dimensions.values() {
+            match dimension.save_chunks() {
+                Ok(count) => println!("Saved {count} chunks of {}", dimension.name),
+                Err(error) => println!("Failed to save {}: {error}", dimension.name),
+            }
+        }
+    }
+
     pub fn on_tick(&mut self) {
         for dimension in self.dimensions.values_mut() {
             dimension.load_chunks();
         }
+
+        // Liquids and falling blocks move on the server and reach clients like any other block change
+        let tick_changes: Vec<(String, Vec<CellChange>)> = self.dimensions.iter_mut()
+            .map(|(name, dimension)| (name.clone(), dimension.tick()))
+            .filter(|(_, changes)| !changes.is_empty())
+            .collect();
+        for (dimension_name, changes) in tick_changes {
+            self.broadcast_block_changes(&dimension_name, &changes);
+        }
+        self.send_light_changes();
+        self.send_liquid_changes();
+
+        // Fronts that form or clear show up at once, not with the next update
+        let weather_changes: Vec<String> = self.dimensions.iter_mut()
+            .filter_map(|(name, dimension)| dimension.get_weather_mut().take_changed().then(|| name.clone()))
+            .collect();
+        for dimension_name in weather_changes {
+            if let Some(dimension) = self.dimensions.get(&dimension_name) {
+                self.send_weather_of(dimension);
+            }
+        }
+
+        self.send_requested_chunks();
+
+        self.ticks_in_sample += 1;
+        let elapsed = self.tick_sample_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.ticks_per_second = self.ticks_in_sample as f32 / elapsed.as_secs_f32();
+            self.ticks_in_sample = 0;
+            self.tick_sample_start = Instant::now();
+        }
     }
 
     pub fn process_commands(&mut self) {
         while let Ok(cmd) = self.console_listener.try_recv() {
-            command_registry::handle_server_command(self, &cmd);
+            let result = command_registry::handle_server_command(self, &cmd, CommandSource::console(), &mut ConsoleOutput);
+            result.write_to(&mut ConsoleOutput);
         }
+
+        let remote_commands: Vec<RemoteCommand> = match &self.remote_listener {
+            Some(remote_listener) => remote_listener.try_iter().collect(),
+            None => Vec::new(),
+        };
+
+        for remote_command in remote_commands {
+            let cmd = &remote_command.command;
+            println!("Remote console issued command: {}", cmd.debug_command.name);
+
+            let source = CommandSource::remote();
+            let reply = match cmd.debug_command.command_environment {
+                CommandEnvironment::Server => capture_output(|output| command_registry::handle_server_command(self, cmd, source, output)),
+                CommandEnvironment::Main => capture_output(|output| command_registry::handle_main_command(cmd, source, output)),
+                CommandEnvironment::Client => CommandResult::failure("Client commands cannot be run remotely"),
+            };
+
+            let _ = remote_command.reply.send(reply);
+        }
+
+        self.run_scripts();
     }
 
+    pub fn start_script(&mut self, script: CommandScript) -> Result<(), String> {
+        if self.scripts.len() >= SCRIPT_MAX_DEPTH {
+            return Err(format!("Too many nested scripts, the limit is {SCRIPT_MAX_DEPTH}"));
+        }
+
+        self.scripts.push(script);
+        Ok(())
+    }
+
+    // Returns how many scripts were running
+    pub fn abort_scripts(&mut self) -> usize {
+        let count = self.scripts.len();
+        self.scripts.clear();
+        count
+    }
+
+    // Runs script commands until every script is waiting or done. Output goes to the console.
+    fn run_scripts(&mut self) {
+        while !self.scripts.is_empty() {
+            let index = self.scripts.len() - 1;
+            let script = &mut self.scripts[index];
+            let (line_number, command_line) = match script.next_step() {
+                ScriptStep::Waiting => return,
+                ScriptStep::Finished => {
+                    println!("Script {} finished", script.name);
+                    self.scripts.pop();
+                    continue;
+                },
+                ScriptStep::Command { line_number, command_line } => (line_number, command_line),
+            };
+
+            let name = script.name.clone();
+            let source = script.source;
+            println!("[{name}:{line_number}] {command_line}");
+
+            let result = match command_registry::parse_command_line(&command_line) {
+                Ok(cmd) => match cmd.debug_command.command_environment {
+                    CommandEnvironment::Server => command_registry::handle_server_command(self, &cmd, source, &mut ConsoleOutput),
+                    CommandEnvironment::Main => command_registry::handle_main_command(&cmd, source, &mut ConsoleOutput),
+                    CommandEnvironment::Client => CommandResult::failure("Client commands cannot run in scripts, the server has no client to send them to."),
+                },
+                Err(error) => CommandResult::failure(error),
+            };
+            result.write_to(&mut ConsoleOutput);
+
+            // The command may have aborted the scripts or started a nested one
+            let stop = self.scripts.get(index).is_some_and(|script| script.name == name && script.stop_on_error);
+            if !result.is_success() && stop {
+                println!("Script {name} stopped at line {line_number} because the command failed");
+                self.scripts.truncate(index);
+            }
+        }
+    }
+
+    // Attaches a connection made inside the server process, such as a load test bot
+    pub fn accept_local_connection(&mut self, connection: Connection, permission_level: PermissionLevel) {
+        self.pending_connections.push((connection, Some(permission_level)));
+    }
+
+    pub fn process_packets(&mut self) {
+        while let Ok(connection) = self.connection_listener.try_recv() {
+            self.pending_connections.push((connection, None));
+        }
+
+        // Connections only become clients once they introduce themselves
+        let mut index = 0;
+        while index < self.pending_connections.len() {
+            if let Some(ClientPacket::Handshake(handshake)) = self.pending_connections[index].0.try_recv() {
+                let (connection, permission_level) = self.pending_connections.swap_remove(index);
+                self.accept_client(connection, handshake, permission_level);
+            } else {
+                index += 1;
+            }
+        }
+
+        let mut received_packets: Vec<(u64, ClientPacket)> = Vec::new();
+        for (uuid, client) in &self.clients {
+            while let Some(packet) = client.connection.try_recv::<ClientPacket>() {
+                received_packets.push((*uuid, packet));
+            }
+        }
+
+        for (uuid, packet) in received_packets {
+            self.handle_client_packet(uuid, packet);
+        }
+    }
+
+    fn accept_client(&mut self, connection: Connection, handshake: HandshakePacket, permission_level: Option<PermissionLevel>) {
+        if self.clients.contains_key(&handshake.player_uuid) {
+            connection.send(ServerPacket::Disconnect("A player with this UUID is already connected".to_string()), self.compress_sent_data);
+            return;
+        }
+
+        // Local connections are made by the server itself and have a fixed level, so there is nothing to prove
+        if permission_level.is_none() {
+            match self.identities.authenticate(handshake.player_uuid, &handshake.player_nickname, handshake.identity_token) {
+                Ok(Some(token)) => {
+                    connection.send(ServerPacket::Identity(token), self.compress_sent_data);
+                },
+                Ok(None) => {},
+                Err(reason) => {
+                    println!("Player \"{}\" was refused: {reason}", handshake.player_nickname);
+                    connection.send(ServerPacket::Disconnect(reason), self.compress_sent_data);
+                    return;
+                }
+            }
+        }
+
+        println!("Player \"{}\" joined", handshake.player_nickname);
+        let client = ConnectedClient::new(handshake.player_uuid, handshake.player_nickname, connection, permission_level);
+        self.clients.insert(handshake.player_uuid, client);
+        self.send_world_time();
+        self.send_weather();
+    }
+
+    fn handle_client_packet(&mut self, uuid: u64, packet: ClientPacket) {
+        let compress = self.compress_sent_data;
+        let Some(client) = self.clients.get_mut(&uuid) else {
+            return;
+        };
+
+        match packet {
+            ClientPacket::Handshake(_) => {
+                // Already shook hands, nothing to do
+            },
+            ClientPacket::Ping(ping) => {
+                client.connection.send(ServerPacket::Pong(ping), compress);
+            },
+            ClientPacket::Pong(pong) => {
+                client.latency.on_pong(&pong);
+            },
+            ClientPacket::Chat(message) => {
+                self.handle_chat(uuid, message);
+            },
+            ClientPacket::Disconnect => {
+                self.disconnect_client(uuid, "Left the game");
+            },
+            ClientPacket::PlayerPosition((x, y)) => {
+                client.move_to(Vec2::new(x, y));
+            },
+            ClientPacket::RequestChunks(positions) => {
+                let Some(dimension) = self.dimensions.get(&client.dimension) else {
+                    return;
+                };
+
+                // Anything past the limit is dropped, the client asks again later
+                let room = CHUNK_REQUEST_MAX_PENDING.saturating_sub(client.requested_chunks.len());
+                client.requested_chunks.extend(positions.into_iter()
+                    .map(|(x, y)| IVec2::new(x, y))
+                    .filter(|chunk_pos| dimension.chunk_within_world_bounds(chunk_pos))
+                    .take(room));
+            },
+            ClientPacket::BlockEdit((position, block_change)) => {
+                if let Err(error) = self.handle_block_edit(uuid, position, block_change) {
+                    self.send_packet_to(uuid, ServerPacket::Message(error));
+                }
+            },
+            ClientPacket::RequestTileEntity(position) => {
+                if let Err(error) = self.handle_tile_entity_request(uuid, position) {
+                    self.send_packet_to(uuid, ServerPacket::Message(error));
+                }
+            },
+        }
+    }
+
+    // Players may only look into tile entities they could also reach to edit
+    fn handle_tile_entity_request(&mut self, uuid: u64, position: (i64, i64)) -> Result<(), String> {
+        if self.get_permission_level(uuid) < TILE_ENTITY_PERMISSION_LEVEL {
+            return Err("You do not have permission to look into tile entities".to_string());
+        }
+
+        let Some(client) = self.clients.get(&uuid) else {
+            return Ok(());
+        };
+        let Some(dimension) = self.dimensions.get(&client.dimension) else {
+            return Ok(());
+        };
+
+        let world_pos = block_in_reach(client, dimension, position)?;
+        let tile_entity = dimension.get_tile_entity(world_pos).cloned();
+        client.connection.send(ServerPacket::TileEntity((position, tile_entity)), self.compress_sent_data);
+        Ok(())
+    }
+
+    // Clients are not trusted with edits, everything is checked against what the server knows
+    fn handle_block_edit(&mut self, uuid: u64, position: (i64, i64), block_change: BlockChange) -> Result<(), String> {
+        if self.get_permission_level(uuid) < BLOCK_EDIT_PERMISSION_LEVEL {
+            return Err("You do not have permission to edit blocks".to_string());
+        }
+
+        let Some(client) = self.clients.get(&uuid) else {
+            return Ok(());
+        };
+        let dimension_name = client.dimension.clone();
+        let Some(dimension) = self.dimensions.get_mut(&dimension_name) else {
+            return Ok(());
+        };
+
+        let world_pos = block_in_reach(client, dimension, position)?;
+
+        let block = BlockState::new(block_change.block_type, block_change.block_id);
+        if !dimension.get_blocks().is_known(block) {
+            return Err(format!("Unknown block {:?}:{}", block_change.block_type, block_change.block_id));
+        }
+
+        let mut outcome = EditOutcome::default();
+        outcome.place(dimension, world_pos, block_change.layer, block);
+
+        self.broadcast_block_changes(&dimension_name, &outcome.changes);
+        let description = format!("Placed {:?} at {}, {}", block_change.block_type, world_pos.x, world_pos.y);
+        self.record_edit(CommandActor::Player(uuid), &description, &dimension_name, &outcome.changes);
+        Ok(())
+    }
+
+    // Local connections keep the level they were attached with
+    pub fn get_permission_level(&self, uuid: u64) -> PermissionLevel {
+        match self.clients.get(&uuid).and_then(|client| client.permission_level) {
+            Some(level) => level,
+            None => self.ops.get_level(uuid),
+        }
+    }
+
+    fn send_requested_chunks(&mut self) {
+        let compress = self.compress_sent_data;
+
+        for client in self.clients.values_mut() {
+            if client.requested_chunks.is_empty() {
+                continue;
+            }
+
+            let Some(dimension) = self.dimensions.get_mut(&client.dimension) else {
+                client.requested_chunks.clear();
+                continue;
+            };
+
+            client.requested_chunks.retain(|chunk_pos| {
+                if let Some(chunk) = dimension.get_chunk(chunk_pos) {
+                    client.connection.send(ServerPacket::Chunk(((chunk_pos.x, chunk_pos.y), Box::new(PacketChunk::from(chunk)))), compress);
+                    return false;
+                }
+
+                // Keep waiting for it unless it lies outside the world
+                dimension.request_chunk(*chunk_pos)
+            });
+        }
+    }
+
+    fn handle_chat(&mut self, uuid: u64, message: String) {
+        let Some(client) = self.clients.get_mut(&uuid) else {
+            return;
+        };
+
+        let message = message.trim().to_string();
+        if message.is_empty() {
+            return;
+        }
+
+        if message.chars().count() > CHAT_MAX_MESSAGE_LENGTH {
+            self.send_packet_to(uuid, ServerPacket::Message(format!("Message is too long, the limit is {CHAT_MAX_MESSAGE_LENGTH} characters")));
+            return;
+        }
+
+        if !client.chat_limiter.try_consume() {
+            self.send_packet_to(uuid, ServerPacket::Message("You are sending messages too quickly".to_string()));
+            return;
+        }
+
+        let nickname = client.player_nickname.clone();
+
+        if let Some(command_line) = message.strip_prefix('/') {
+            println!("Player \"{}\" issued command: /{}", nickname, command_line);
+            self.handle_chat_command(uuid, command_line);
+            return;
+        }
+
+        println!("<{}> {}", nickname, message);
+        self.send_packet(ServerPacket::Chat(ChatMessage {
+            sender_uuid: uuid,
+            sender_nickname: nickname,
+            message,
+        }));
+    }
+
+    // Chat commands go through the same registry as the console
+    fn handle_chat_command(&mut self, uuid: u64, command_line: &str) {
+        let cmd = match command_registry::parse_command_line(command_line) {
+            Ok(cmd) => cmd,
+            Err(error) => {
+                self.send_packet_to(uuid, ServerPacket::Message(error));
+                return;
+            }
+        };
+
+        let source = CommandSource::player(uuid, self.get_permission_level(uuid));
+        let result = match cmd.debug_command.command_environment {
+            CommandEnvironment::Server => capture_output(|output| command_registry::handle_server_command(self, &cmd, source, output)),
+            CommandEnvironment::Main => capture_output(|output| command_registry::handle_main_command(&cmd, source, output)),
+            CommandEnvironment::Client => CommandResult::failure("That command runs on the client, use your console instead."),
+        };
+
+        for line in result.lines {
+            self.send_packet_to(uuid, ServerPacket::Message(line));
+        }
+    }
+
+    pub fn send_keepalive(&mut self) {
+        let mut timed_out: Vec<u64> = Vec::new();
+
+        for (uuid, client) in &mut self.clients {
+            if client.latency.missed_pings() >= KEEPALIVE_MAX_MISSED_PINGS {
+                timed_out.push(*uuid);
+                continue;
+            }
+
+            let ping = client.latency.next_ping();
+            if !client.connection.send(ServerPacket::Ping(ping), self.compress_sent_data) {
+                timed_out.push(*uuid);
+            }
+        }
+
+        for uuid in timed_out {
+            self.disconnect_client(uuid, &format!("Timed out after {KEEPALIVE_MAX_MISSED_PINGS} unanswered pings"));
+        }
+    }
+
+    pub fn send_status(&self) {
+        self.send_packet(ServerPacket::Status(ServerStatus {
+            ticks_per_second: self.ticks_per_second,
+            player_count: self.clients.len() as u32,
+        }));
+    }
+
+    // Every client gets the clock of the dimension it is in
+    pub fn send_world_time(&self) {
+        for client in self.clients.values() {
+            if let Some(dimension) = self.dimensions.get(&client.dimension) {
+                client.connection.send(ServerPacket::Time(dimension.get_world_time()), self.compress_sent_data);
+            }
+        }
+    }
+
+    pub fn send_weather(&self) {
+        for dimension in self.dimensions.values() {
+            self.send_weather_of(dimension);
+        }
+    }
+
+    // To the clients in the dimension, if it has weather at all
+    fn send_weather_of(&self, dimension: &Dimension) {
+        let mut clients = self.clients.values().filter(|client| client.dimension == dimension.name).peekable();
+        if !dimension.get_weather().has_weather() || clients.peek().is_none() {
+            return;
+        }
+
+        let encoded_packet = encode_packet(ServerPacket::Weather(weather::get_state(dimension)), self.compress_sent_data);
+        for client in clients {
+            client.connection.send_raw(encoded_packet.clone());
+        }
+    }
+
+    pub fn disconnect_client(&mut self, uuid: u64, reason: &str) {
+        if let Some(client) = self.clients.remove(&uuid) {
+            self.edit_journal.forget(CommandActor::Player(uuid));
+            client.connection.send(ServerPacket::Disconnect(reason.to_string()), self.compress_sent_data);
+            println!("Player \"{}\" disconnected: {}", client.player_nickname, reason);
+        }
+    }
+
+    // Sends the packet to every connected client
     pub fn send_packet(&self, packet: ServerPacket) {
-        // turn into bytes
-        let encoded_packet = bincode::encode_to_vec(packet, bincode::config::standard()).unwrap();
-        let mut is_compressed = false;
-        let raw_len = encoded_packet.len();
+        let encoded_packet = encode_packet(packet, self.compress_sent_data);
+
+        for client in self.clients.values() {
+            client.connection.send_raw(encoded_packet.clone());
+        }
+    }
 
-        // compress if large enough
-        let encoded_packet: Vec<u8> = if raw_len > 100 && self.compress_sent_data {
-            let max_compressed_size = lz4_flex::block::get_maximum_output_size(raw_len);
-            let mut compressed_buffer = vec![0u8; max_compressed_size];
+    // Light spreads well past the blocks that changed, so it is sent for whole chunks
+    fn send_light_changes(&mut self) {
+        let dimension_names: Vec<String> = self.dimensions.keys().cloned().collect();
+
+        for dimension_name in dimension_names {
+            let Some(dimension) = self.dimensions.get_mut(&dimension_name) else {
+                continue;
+            };
+            let chunk_positions = dimension.take_light_changes();
+            if chunk_positions.is_empty() || !self.clients.values().any(|client| client.dimension == dimension_name) {
+                continue;
+            }
 
-            let compressed_len = lz4_flex::compress_into(&encoded_packet, &mut compressed_buffer)
-            .map_err(|e| format!("LZ4 Compression Error: {}", e)).unwrap();
+            let Some(dimension) = self.dimensions.get(&dimension_name) else {
+                continue;
+            };
+            for chunk_pos in chunk_positions {
+                if let Some(chunk) = dimension.get_chunk(&chunk_pos) {
+                    self.send_packet_to_dimension(&dimension_name, ServerPacket::ChunkLight(((chunk_pos.x, chunk_pos.y), Box::new(PacketChunkLight::from(chunk)))));
+                }
+            }
+        }
+    }
 
-            compressed_buffer.truncate(compressed_len);
+    // Fill levels are not part of a block change, they follow per chunk like light does
+    fn send_liquid_changes(&mut self) {
+        let dimension_names: Vec<String> = self.dimensions.keys().cloned().collect();
 
-            is_compressed = true;
-            compressed_buffer
-        } else {
-            encoded_packet
+        for dimension_name in dimension_names {
+            let Some(dimension) = self.dimensions.get_mut(&dimension_name) else {
+                continue;
+            };
+            let chunk_positions = dimension.take_liquid_changes();
+            if chunk_positions.is_empty() || !self.clients.values().any(|client| client.dimension == dimension_name) {
+                continue;
+            }
+
+            let Some(dimension) = self.dimensions.get(&dimension_name) else {
+                continue;
+            };
+            for chunk_pos in chunk_positions {
+                if let Some(chunk) = dimension.get_chunk(&chunk_pos) {
+                    self.send_packet_to_dimension(&dimension_name, ServerPacket::ChunkLiquid(((chunk_pos.x, chunk_pos.y), Box::new(PacketChunkLiquid::from(chunk)))));
+                }
+            }
+        }
+    }
+
+    pub fn send_packet_to_dimension(&self, dimension_name: &str, packet: ServerPacket) {
+        let encoded_packet = encode_packet(packet, self.compress_sent_data);
+
+        for client in self.clients.values().filter(|client| client.dimension == dimension_name) {
+            client.connection.send_raw(encoded_packet.clone());
+        }
+    }
+
+    // Small edits go out block by block, large ones resend the chunks they touched
+    pub fn broadcast_block_changes(&self, dimension_name: &str, changes: &[CellChange]) {
+        if changes.len() <= WORLD_EDIT_BLOCK_PACKET_LIMIT {
+            for change in changes {
+                self.send_packet_to_dimension(dimension_name, change.to_packet());
+            }
+            return;
+        }
+
+        let Some(dimension) = self.dimensions.get(dimension_name) else {
+            return;
+        };
+
+        let chunk_positions: HashSet<IVec2> = changes.iter().map(|change| world_to_chunk_pos_2d(change.position)).collect();
+        for chunk_pos in chunk_positions {
+            if let Some(chunk) = dimension.get_chunk(&chunk_pos) {
+                self.send_packet_to_dimension(dimension_name, ServerPacket::Chunk(((chunk_pos.x, chunk_pos.y), Box::new(PacketChunk::from(chunk)))));
+            }
+        }
+    }
+
+    pub fn record_edit(&mut self, actor: CommandActor, description: &str, dimension_name: &str, changes: &[CellChange]) {
+        self.edit_journal.record(actor, EditOperation::new(description.to_string(), dimension_name.to_string(), changes));
+    }
+
+    // Reverts the actor's last edit, skipping blocks that were changed again since
+    pub fn undo_edit(&mut self, actor: CommandActor) -> Result<(String, EditOutcome), String> {
+        let Some(operation) = self.edit_journal.take_undo(actor) else {
+            return Err("Nothing left to undo".to_string());
+        };
+        let Some(dimension) = self.dimensions.get_mut(&operation.dimension) else {
+            return Err(format!("Dimension \"{}\" of \"{}\" no longer exists", operation.dimension, operation.description));
         };
 
-        // Pack into header
-        let encoded_packet = PacketHeader {
-            is_compressed,
-            original_size: raw_len,
-            data: encoded_packet,
+        let outcome = operation.revert(dimension);
+        self.broadcast_block_changes(&operation.dimension, &outcome.changes);
+
+        let description = operation.description.clone();
+        self.edit_journal.push_redo(actor, operation);
+        Ok((description, outcome))
+    }
+
+    pub fn redo_edit(&mut self, actor: CommandActor) -> Result<(String, EditOutcome), String> {
+        let Some(operation) = self.edit_journal.take_redo(actor) else {
+            return Err("Nothing left to redo".to_string());
+        };
+        let Some(dimension) = self.dimensions.get_mut(&operation.dimension) else {
+            return Err(format!("Dimension \"{}\" of \"{}\" no longer exists", operation.dimension, operation.description));
         };
 
-        // Turn into bytes a second time
-        let encoded_packet = bincode::encode_to_vec(encoded_packet, bincode::config::standard()).unwrap();
+        let outcome = operation.reapply(dimension);
+        self.broadcast_block_changes(&operation.dimension, &outcome.changes);
 
-        let _ = self.client_sender.send(encoded_packet);
+        let description = operation.description.clone();
+        self.edit_journal.push_undo(actor, operation);
+        Ok((description, outcome))
+    }
+
+    pub fn get_edit_journal(&self) -> &EditJournal {
+        &self.edit_journal
+    }
+
+    pub fn send_packet_to(&self, uuid: u64, packet: ServerPacket) {
+        if let Some(client) = self.clients.get(&uuid) {
+            client.connection.send(packet, self.compress_sent_data);
+        }
+    }
+
+    pub fn get_clients(&self) -> impl Iterator<Item = &ConnectedClient> {
+        self.clients.values()
+    }
+
+    pub fn get_ops(&self) -> &OpsList {
+        &self.ops
+    }
+
+    // Saves the ops list and tells the player if they are online. Only UUIDs
+    // that already joined can be raised, until then anyone could claim them.
+    pub fn set_permission_level(&mut self, uuid: u64, nickname: &str, level: PermissionLevel) -> Result<(), String> {
+        if level > PermissionLevel::Player && !self.identities.is_known(uuid) {
+            return Err(format!("\"{nickname}\" has never joined, so nobody holds that UUID yet"));
+        }
+
+        self.ops.set_level(uuid, nickname, level).map_err(|error| format!("Failed to save ops list: {error}"))?;
+        self.send_packet_to(uuid, ServerPacket::Message(format!("Your permission level is now {}", level.name())));
+        Ok(())
+    }
+
+    // Looks a player up by online nickname, nickname in the ops list, or hex UUID
+    pub fn resolve_player(&self, name: &str) -> Option<(u64, String)> {
+        if let Some(client) = self.clients.values().find(|client| client.player_nickname.eq_ignore_ascii_case(name)) {
+            return Some((client.player_uuid, client.player_nickname.clone()));
+        }
+
+        let uuid = self.ops.find_by_nickname(name).or_else(|| u64::from_str_radix(name, 16).ok())?;
+        let nickname = match self.clients.get(&uuid) {
+            Some(client) => client.player_nickname.clone(),
+            None => self.ops.get_entries()
+                .find(|entry| entry.uuid == format!("{uuid:016x}"))
+                .map_or_else(|| name.to_string(), |entry| entry.nickname.clone()),
+        };
+
+        Some((uuid, nickname))
     }
 
     pub fn get_dimension(&self, name: &str) -> Option<&Dimension> {
@@ -105,7 +740,126 @@ impl Server {
         return self.dimensions.keys();
     }
 
+    pub fn get_ticks_per_second(&self) -> f32 {
+        self.ticks_per_second
+    }
+
     pub fn is_running(&self) -> bool {
         return self.running;
     }
+}
+
+// Checks a block position sent by a client against the world and how far
+// the player can reach from where the server last saw them
+fn block_in_reach(client: &ConnectedClient, dimension: &Dimension, position: (i64, i64)) -> Result<IVec2, String> {
+    let (Ok(x), Ok(y)) = (i32::try_from(position.0), i32::try_from(position.1)) else {
+        return Err(format!("Block position {}, {} is outside of the world", position.0, position.1));
+    };
+
+    let world_pos = IVec2::new(x, y);
+    if !dimension.block_within_world_bounds(world_pos) {
+        return Err(format!("Block position {x}, {y} is outside of the world"));
+    }
+    if (world_pos.as_vec2() + 0.5).distance(client.position) > BLOCK_EDIT_REACH {
+        return Err(format!("Block position {x}, {y} is out of reach"));
+    }
+    Ok(world_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{common::ChunkRelativePos, server::{chunk::Chunk, constants::DEFAULT_DIMENSION, tile_entity::TileEntity}};
+
+    use super::*;
+
+    // A player level client, returns the client end of its connection
+    fn join_as_player(server: &mut Server, uuid: u64) -> Connection {
+        let (client_connection, server_connection) = Connection::pair();
+        server.clients.insert(uuid, ConnectedClient::new(uuid, "player".to_string(), server_connection, None));
+        client_connection
+    }
+
+    fn received_messages(connection: &Connection) -> Vec<String> {
+        std::iter::from_fn(|| connection.try_recv::<ServerPacket>())
+            .filter_map(|packet| match packet {
+                ServerPacket::Message(message) => Some(message),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // The default dimension with one empty chunk at the origin, which has a sign at 2, 3
+    fn server_with_sign() -> Server {
+        let mut server = Server::empty(None);
+        let schemas = Dimension::load_dimensions(&get_data_path()).unwrap();
+        let schema = schemas.iter().find(|schema| schema.name == DEFAULT_DIMENSION).unwrap();
+        let mut dimension = Dimension::from_schema(schema, 1);
+
+        let mut chunk = Chunk::empty();
+        chunk.insert_tile_entity(ChunkRelativePos::new(2, 3), TileEntity::Sign { text: "hi".to_string() });
+        dimension.insert_chunk(IVec2::ZERO, chunk);
+        server.dimensions.insert(DEFAULT_DIMENSION.to_string(), dimension);
+        server
+    }
+
+    fn tile_entity_replies(connection: &Connection) -> Vec<((i64, i64), Option<TileEntity>)> {
+        std::iter::from_fn(|| connection.try_recv::<ServerPacket>())
+            .filter_map(|packet| match packet {
+                ServerPacket::TileEntity(reply) => Some(reply),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tile_entities_are_only_sent_within_reach() {
+        let mut server = server_with_sign();
+        let connection = join_as_player(&mut server, 7);
+
+        server.handle_client_packet(7, ClientPacket::RequestTileEntity((2, 3)));
+        assert_eq!(tile_entity_replies(&connection), [((2, 3), Some(TileEntity::Sign { text: "hi".to_string() }))]);
+
+        // Far away, and a position that only lands on the sign once truncated to i32
+        server.handle_client_packet(7, ClientPacket::RequestTileEntity((2, 3 + 20)));
+        server.handle_client_packet(7, ClientPacket::RequestTileEntity(((1 << 32) + 2, 3)));
+        assert!(tile_entity_replies(&connection).is_empty());
+    }
+
+    #[test]
+    fn chunk_requests_outside_the_world_or_past_the_limit_are_dropped() {
+        let mut server = server_with_sign();
+        let _connection = join_as_player(&mut server, 7);
+
+        let far_away = (0..1000).map(|x| (x, 1 << 20));
+        let inside = (0..CHUNK_REQUEST_MAX_PENDING as i32 * 2).map(|i| (i % 40 - 20, i / 40 - 5));
+        server.handle_client_packet(7, ClientPacket::RequestChunks(far_away.chain(inside).collect()));
+
+        let requested = &server.clients[&7].requested_chunks;
+        assert_eq!(requested.len(), CHUNK_REQUEST_MAX_PENDING);
+        assert!(requested.iter().all(|chunk_pos| chunk_pos.y != 1 << 20));
+    }
+
+    #[test]
+    fn chat_commands_above_the_player_level_are_refused() {
+        let mut server = Server::empty(None);
+        let connection = join_as_player(&mut server, 7);
+
+        server.handle_chat(7, "/stopserver".to_string());
+
+        assert!(server.is_running(), "stopserver ran from chat");
+        let messages = received_messages(&connection);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("requires operator level"), "{messages:?}");
+    }
+
+    #[test]
+    fn chat_commands_at_the_player_level_run() {
+        let mut server = Server::empty(None);
+        let connection = join_as_player(&mut server, 7);
+
+        server.handle_chat(7, "/help help".to_string());
+
+        let messages = received_messages(&connection);
+        assert!(messages.iter().any(|message| message.contains("Requires: player")), "{messages:?}");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use glam::{IVec2, UVec2};
+
+use crate::engine::server::{common::{BlockState, BlockType, LayerType}, data::schema_definitions::{SpritePlacement, SpriteSchema}, world::Dimension, world_edit::{CellChange, EditOutcome}};
+
+/*
+/   Sprites with a footprint in blocks.json cover more than one cell. Each
+/   cell is a block of its own with the id of the sprite, and its
+/   texture_index says which cell of the footprint it is, x in the low four
+/   bits and y in the high four, counted from the bottom left. From any
+/   cell the whole object can be found again, and the client knows which
+/   part of the sprite to draw there.
+/
+/   Objects are placed and broken as a whole by edits. Copies such as undo
+/   or pasting a schematic move the cells one by one, exactly as they are.
+*/
+
+pub fn pack_cell(cell: UVec2) -> u8 {
+    (cell.y << 4 | cell.x) as u8
+}
+
+pub fn unpack_cell(texture_index: u8) -> UVec2 {
+    UVec2::new((texture_index & 0xF) as u32, (texture_index >> 4) as u32)
+}
+
+// Places the sprite with its anchor on the position. Every cell has to be
+// air and the placement rules have to hold, or nothing is placed.
+pub fn place_object(dimension: &mut Dimension, outcome: &mut EditOutcome, world_pos: IVec2, layer: LayerType, block: BlockState) {
+    let Some(schema) = dimension.get_blocks().get_sprite(block).cloned() else {
+        return;
+    };
+
+    let origin = world_pos - schema.anchor.as_ivec2();
+    for (cell_pos, _) in cells(&schema, origin) {
+        match dimension.get_block(cell_pos, layer) {
+            None => {
+                outcome.unloaded += 1;
+                return;
+            },
+            Some(current) if current.block_type != BlockType::Air => {
+                outcome.obstructed += 1;
+                return;
+            },
+            Some(_) => {},
+        }
+    }
+
+    if !is_supported(dimension, &schema, origin) {
+        outcome.obstructed += 1;
+        return;
+    }
+
+    for (cell_pos, cell) in cells(&schema, origin) {
+        let cell_block = BlockState { block_type: BlockType::Sprite, block_id: block.block_id, texture_index: pack_cell(cell) };
+        outcome.set(dimension, cell_pos, layer, cell_block);
+    }
+}
+
+// Removes every cell of the object the position is part of. Cells that were
+// replaced some other way are left alone.
+pub fn break_object(dimension: &mut Dimension, outcome: &mut EditOutcome, world_pos: IVec2, layer: LayerType) {
+    let Some((block, schema, origin)) = find_object(dimension, world_pos, layer) else {
+        return;
+    };
+
+    for (cell_pos, cell) in cells(&schema, origin) {
+        let expected = BlockState { block_type: BlockType::Sprite, block_id: block.block_id, texture_index: pack_cell(cell) };
+        if dimension.get_block(cell_pos, layer) == Some(expected) {
+            outcome.set(dimension, cell_pos, layer, BlockState::AIR);
+        }
+    }
+}
+
+// Runs as a scheduled update after a block next to a sprite changed,
+// sprites that lost what they rest on break
+pub fn update_support(dimension: &mut Dimension, world_pos: IVec2) -> Vec<CellChange> {
+    let mut outcome = EditOutcome::default();
+
+    for layer in [LayerType::Foreground, LayerType::Middleground] {
+        let Some((_, schema, origin)) = find_object(dimension, world_pos, layer) else {
+            continue;
+        };
+
+        if !is_supported(dimension, &schema, origin) {
+            break_object(dimension, &mut outcome, world_pos, layer);
+        }
+    }
+
+    outcome.changes
+}
+
+// Whether a change at the position may take away the support of a sprite next to it
+pub fn needs_support_check(dimension: &Dimension, world_pos: IVec2) -> bool {
+    [LayerType::Foreground, LayerType::Middleground].into_iter().any(|layer| {
+        dimension.get_block(world_pos, layer)
+            .and_then(|block| dimension.get_blocks().get_sprite(block))
+            .is_some_and(|schema| !schema.placement.is_empty())
+    })
+}
+
+// The block, its footprint and the world position of its bottom left cell
+fn find_object(dimension: &Dimension, world_pos: IVec2, layer: LayerType) -> Option<(BlockState, SpriteSchema, IVec2)> {
+    let block = dimension.get_block(world_pos, layer)?;
+    let schema = dimension.get_blocks().get_sprite(block)?.clone();
+    let origin = world_pos - unpack_cell(block.texture_index).as_ivec2();
+    Some((block, schema, origin))
+}
+
+fn cells(schema: &SpriteSchema, origin: IVec2) -> impl Iterator<Item = (IVec2, UVec2)> {
+    let (width, height) = (schema.width, schema.height);
+    (0..height).flat_map(move |y| (0..width).map(move |x| (origin + IVec2::new(x as i32, y as i32), UVec2::new(x, y))))
+}
+
+// Unloaded cells count as missing support
+fn is_supported(dimension: &Dimension, schema: &SpriteSchema, origin: IVec2) -> bool {
+    let is_solid = |world_pos: IVec2| dimension.get_block(world_pos, LayerType::Foreground)
+        .is_some_and(|block| block.block_type == BlockType::Tile && !dimension.get_liquids().is_liquid(block));
+    let row = |y: i32| (0..schema.width as i32).map(move |x| origin + IVec2::new(x, y));
+
+    schema.placement.iter().all(|placement| match placement {
+        SpritePlacement::Ground => row(-1).all(is_solid),
+        SpritePlacement::Ceiling => row(schema.height as i32).all(is_solid),
+        SpritePlacement::Wall => cells(schema, origin).all(|(cell_pos, _)| {
+            dimension.get_block(cell_pos, LayerType::Background).is_some_and(|block| block.block_type == BlockType::Wall)
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_survive_packing() {
+        for y in 0..16 {
+            for x in 0..16 {
+                let cell = UVec2::new(x, y);
+                assert_eq!(unpack_cell(pack_cell(cell)), cell);
+            }
+        }
+        assert_eq!(pack_cell(UVec2::new(2, 1)), 0x12);
+    }
+}
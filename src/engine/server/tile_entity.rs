@@ -0,0 +1,154 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::server::{block_registry::BlockRegistry, common::BlockType, constants::MAX_STACK_SIZE, data::schema_definitions::TileEntitySchema};
+
+/*
+/   Tile entities are the state a single block carries on top of its type
+/   and id, such as the blocks in a chest. The chunk owns them, keyed by
+/   the local index of their foreground block, so they are created and
+/   removed together with it and are stored in the chunk file with it.
+/
+/   Clients never get them with the chunk, they ask for the one they want
+/   to look at and the server answers with its current state.
+*/
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, PartialEq)]
+pub enum TileEntity {
+    Container {
+        slots: Vec<Option<BlockStack>>,
+    },
+    Sign {
+        text: String,
+    },
+    Furnace {
+        input: Option<BlockStack>,
+        output: Option<BlockStack>,
+        // Ticks spent on the block being smelted
+        progress: u32,
+    },
+}
+
+// There are no items yet, containers hold blocks
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy, PartialEq)]
+pub struct BlockStack {
+    pub block_type: BlockType,
+    pub block_id: u32,
+    pub count: u32,
+}
+
+impl TileEntity {
+    pub fn new(schema: TileEntitySchema) -> TileEntity {
+        match schema {
+            TileEntitySchema::Container { slots } => TileEntity::Container { slots: vec![None; slots as usize] },
+            TileEntitySchema::Sign => TileEntity::Sign { text: String::new() },
+            TileEntitySchema::Furnace { .. } => TileEntity::Furnace { input: None, output: None, progress: 0 },
+        }
+    }
+
+    // Most tile entities only change when someone edits them
+    pub fn needs_tick(&self) -> bool {
+        matches!(self, TileEntity::Furnace { input: Some(_), .. })
+    }
+
+    pub fn tick(&mut self, schema: TileEntitySchema, blocks: &BlockRegistry) {
+        let (TileEntity::Furnace { input, output, progress }, TileEntitySchema::Furnace { cook_ticks }) = (self, schema) else {
+            return;
+        };
+
+        let Some(stack) = input else {
+            return;
+        };
+
+        // Blocks that do not smelt, or a full output, leave the furnace idle
+        let product = blocks.get_smelts_into(stack.block_id)
+            .map(|block_id| BlockStack { block_type: stack.block_type, block_id, count: 1 })
+            .filter(|product| output.is_none_or(|output| output.can_merge(product) && output.count < MAX_STACK_SIZE));
+        let Some(product) = product else {
+            *progress = 0;
+            return;
+        };
+
+        *progress += 1;
+        if *progress < cook_ticks {
+            return;
+        }
+
+        *progress = 0;
+        stack.count -= 1;
+        if stack.count == 0 {
+            *input = None;
+        }
+        match output {
+            Some(output) => output.count += 1,
+            None => *output = Some(product),
+        }
+    }
+
+    // Puts as many blocks as fit and returns how many did not
+    pub fn insert(&mut self, stack: BlockStack) -> u32 {
+        let mut remaining = stack.count;
+
+        let mut slots: Vec<&mut Option<BlockStack>> = match self {
+            TileEntity::Container { slots } => slots.iter_mut().collect(),
+            TileEntity::Furnace { input, .. } => vec![input],
+            TileEntity::Sign { .. } => Vec::new(),
+        };
+
+        // Topping up stacks of the same block comes before starting new ones
+        slots.sort_by_key(|slot| slot.is_none());
+        for slot in slots {
+            if remaining == 0 {
+                break;
+            }
+
+            match slot {
+                Some(existing) if existing.can_merge(&stack) => {
+                    let moved = remaining.min(MAX_STACK_SIZE.saturating_sub(existing.count));
+                    existing.count += moved;
+                    remaining -= moved;
+                },
+                Some(_) => {},
+                None => {
+                    let moved = remaining.min(MAX_STACK_SIZE);
+                    *slot = Some(BlockStack { count: moved, ..stack });
+                    remaining -= moved;
+                },
+            }
+        }
+
+        remaining
+    }
+
+    // One line per part of the state, for commands
+    pub fn describe(&self, blocks: &BlockRegistry) -> Vec<String> {
+        let describe_stack = |stack: &Option<BlockStack>| match stack {
+            Some(stack) => format!("{} x{}", blocks.get_name(stack.block_id).unwrap_or("unknown"), stack.count),
+            None => "empty".to_string(),
+        };
+
+        match self {
+            TileEntity::Container { slots } => {
+                let used: Vec<String> = slots.iter()
+                    .enumerate()
+                    .filter(|(_, slot)| slot.is_some())
+                    .map(|(index, slot)| format!("Slot {index}: {}", describe_stack(slot)))
+                    .collect();
+                let mut lines = vec![format!("Container with {} of {} slot(s) used", used.len(), slots.len())];
+                lines.extend(used);
+                lines
+            },
+            TileEntity::Sign { text } => vec![format!("Sign reading \"{text}\"")],
+            TileEntity::Furnace { input, output, progress } => vec![
+                format!("Furnace, {progress} tick(s) into smelting"),
+                format!("Input: {}", describe_stack(input)),
+                format!("Output: {}", describe_stack(output)),
+            ],
+        }
+    }
+}
+
+impl BlockStack {
+    pub fn can_merge(&self, other: &BlockStack) -> bool {
+        self.block_type == other.block_type && self.block_id == other.block_id
+    }
+}
@@ -0,0 +1,197 @@
+use fastrand::Rng;
+use glam::IVec2;
+
+use crate::engine::{common::{Precipitation, PrecipitationSpan, WeatherFront, WeatherState}, server::{common::{BlockState, BlockType, LayerType}, constants::{KEEPALIVE_INTERVAL_TICKS, WEATHER_SAMPLES_PER_FRONT}, data::schema_definitions::WeatherSchema, world::Dimension, world_edit::CellChange}};
+
+/*
+/   Weather comes in fronts, bands a few hundred blocks wide that form at a
+/   random spot, drift sideways for a while and clear again. A front only
+/   says that something falls, the climate on the ground decides what: dry
+/   columns stay dry, cold ones get snow and the rest rain. The climate is
+/   read from the surface of the column, the topmost foreground block.
+/
+/   Snow can settle on the ground and rain can top up liquid that is not
+/   full. A few random columns under every front are looked at each tick,
+/   like random ticks, so a wide front costs no more than a narrow one.
+*/
+pub struct Weather {
+    schema: Option<WeatherSchema>,
+    fronts: Vec<WeatherFront>,
+    rng: Rng,
+    // Whether a front formed or cleared since the last call to take_changed
+    changed: bool,
+}
+
+impl Weather {
+    pub fn new(schema: Option<WeatherSchema>, seed: u64) -> Weather {
+        Weather { schema, fronts: Vec::new(), rng: Rng::with_seed(seed), changed: false }
+    }
+
+    pub fn has_weather(&self) -> bool {
+        self.schema.is_some()
+    }
+
+    pub fn get_fronts(&self) -> &[WeatherFront] {
+        &self.fronts
+    }
+
+    // Moves the fronts by one tick, half_width is how far the world reaches
+    // either way from 0 in blocks. Fronts that leave it clear.
+    pub fn tick(&mut self, half_width: f32) {
+        let Some(schema) = &self.schema else {
+            return;
+        };
+
+        let count = self.fronts.len();
+        self.fronts.retain_mut(|front| {
+            front.x += front.velocity;
+            front.remaining = front.remaining.saturating_sub(1);
+            front.remaining > 0 && front.x.abs() - front.width / 2.0 < half_width
+        });
+        self.changed |= self.fronts.len() != count;
+
+        if self.fronts.len() < schema.max_fronts as usize && self.rng.f32() < schema.front_chance {
+            let x = (self.rng.f32() * 2.0 - 1.0) * half_width;
+            let front = new_front(schema, &mut self.rng, x);
+            self.fronts.push(front);
+            self.changed = true;
+        }
+    }
+
+    // Forms a front at once, centred on x. Returns false if the dimension has no weather.
+    pub fn start_front(&mut self, x: f32) -> bool {
+        let Some(schema) = &self.schema else {
+            return false;
+        };
+
+        let front = new_front(schema, &mut self.rng, x);
+        self.fronts.push(front);
+        self.changed = true;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.changed |= !self.fronts.is_empty();
+        self.fronts.clear();
+    }
+
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+fn new_front(schema: &WeatherSchema, rng: &mut Rng, x: f32) -> WeatherFront {
+    WeatherFront {
+        x,
+        width: rng.u32(schema.min_width..=schema.max_width.max(schema.min_width)) as f32,
+        velocity: (rng.f32() * 2.0 - 1.0) * schema.max_speed,
+        intensity: 0.25 + rng.f32() * 0.75,
+        remaining: rng.u64(schema.min_duration..=schema.max_duration.max(schema.min_duration)),
+    }
+}
+
+// Settles snow and tops up liquid under the fronts, returns the blocks that changed
+pub fn apply_weather(dimension: &mut Dimension) -> Vec<CellChange> {
+    let weather = dimension.get_weather();
+    let Some(schema) = weather.schema.clone() else {
+        return Vec::new();
+    };
+    if weather.fronts.is_empty() || (schema.snow_block.is_none() && schema.rain_fills.is_none()) {
+        return Vec::new();
+    }
+
+    let fronts = weather.fronts.clone();
+    let mut rng = std::mem::take(&mut dimension.get_weather_mut().rng);
+    let mut changes = Vec::new();
+
+    for front in fronts {
+        for _ in 0..WEATHER_SAMPLES_PER_FRONT {
+            if rng.f32() >= schema.effect_chance * front.intensity {
+                continue;
+            }
+
+            let x = (front.x + (rng.f32() - 0.5) * front.width).floor() as i32;
+            let Some((surface, precipitation)) = get_precipitation(dimension, &schema, x) else {
+                continue;
+            };
+
+            match (precipitation, schema.snow_block, schema.rain_fills) {
+                (Precipitation::Snow, Some(snow_block), _) => changes.extend(settle_snow(dimension, surface, snow_block)),
+                (Precipitation::Rain, _, Some(liquid_block)) => {
+                    dimension.top_up_liquid(surface, liquid_block);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    dimension.get_weather_mut().rng = rng;
+    changes
+}
+
+// The fronts, and what falls under them until the next update
+pub fn get_state(dimension: &Dimension) -> WeatherState {
+    let weather = dimension.get_weather();
+    let Some(schema) = &weather.schema else {
+        return WeatherState::default();
+    };
+
+    // Everything a front covers now or will have reached by the next update
+    let lookahead = KEEPALIVE_INTERVAL_TICKS as f32;
+    let mut columns: Vec<i32> = weather.fronts.iter()
+        .flat_map(|front| {
+            let travelled = front.velocity * lookahead;
+            let start = (front.x - front.width / 2.0 + travelled.min(0.0)).floor() as i32;
+            let end = (front.x + front.width / 2.0 + travelled.max(0.0)).ceil() as i32;
+            start..end
+        })
+        .collect();
+    columns.sort_unstable();
+    columns.dedup();
+
+    let mut spans: Vec<PrecipitationSpan> = Vec::new();
+    for x in columns {
+        let Some((_, precipitation)) = get_precipitation(dimension, schema, x) else {
+            continue;
+        };
+
+        match spans.last_mut() {
+            Some(span) if span.end == x - 1 && span.precipitation == precipitation => span.end = x,
+            _ => spans.push(PrecipitationSpan { start: x, end: x, precipitation }),
+        }
+    }
+
+    WeatherState { fronts: weather.fronts.clone(), spans }
+}
+
+// The surface of the column and what would fall on it under a front, None if it stays dry
+fn get_precipitation(dimension: &Dimension, schema: &WeatherSchema, x: i32) -> Option<(IVec2, Precipitation)> {
+    let surface = dimension.get_surface(x)?;
+    if dimension.get_humidity(surface)? < schema.min_humidity {
+        return None;
+    }
+
+    if dimension.get_temperature(surface)? < schema.snow_temperature {
+        Some((surface, Precipitation::Snow))
+    } else {
+        Some((surface, Precipitation::Rain))
+    }
+}
+
+// Snow only settles on solid tiles, and only one block deep
+fn settle_snow(dimension: &mut Dimension, surface: IVec2, snow_block: u32) -> Option<CellChange> {
+    let ground = dimension.get_block(surface, LayerType::Foreground)?;
+    if ground.block_type != BlockType::Tile || ground.block_id == snow_block || dimension.get_liquids().is_liquid(ground) {
+        return None;
+    }
+
+    let world_pos = surface + IVec2::Y;
+    let before = dimension.get_block(world_pos, LayerType::Foreground)?;
+    if before.block_type != BlockType::Air {
+        return None;
+    }
+
+    let after = BlockState::new(BlockType::Tile, snow_block);
+    dimension.set_block(world_pos, LayerType::Foreground, after);
+    Some(CellChange { position: world_pos, layer: LayerType::Foreground, before, after })
+}
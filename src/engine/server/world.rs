@@ -1,18 +1,35 @@
-use std::{collections::HashMap, path::Path, sync::mpsc::Receiver, time::{Duration, Instant}};
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::mpsc::Receiver, time::{Duration, Instant}};
 use dashmap::DashMap;
 use glam::{IVec2, UVec2};
 use hecs::World;
 
-use crate::engine::{common::get_data_path, components::alive::{AliveTask, AliveTaskKey, EntityID, PlayerID}, server::{biome::BiomeRegistry, chunk::Chunk, chunk_generator::ChunkGenerator, data::schema_definitions::{BiomeMapAdjustments, BiomeSchema, DimensionSchema}}};
+use crate::engine::{common::{get_data_path, ChunkRelativePos, WorldTime}, components::alive::{AliveTask, AliveTaskKey, EntityID, PlayerID}, server::{biome::BiomeRegistry, block_registry::BlockRegistry, chunk::Chunk, block_update, gravity, sprite, lighting::Lighting, weather::{self, Weather}, constants::{CHUNK_EXTENSION, CHUNK_SIZE, GRAVITY_UPDATE_DELAY, PENDING_FEATURES_FILE, RANDOM_TICKS_PER_CHUNK, RANDOM_TICK_SEED_SALT, WEATHER_SEED_SALT, WORLD_DIRECTORY}, common::{world_to_chunk_pos_2d, world_to_local_pos_2d, BlockState, BlockType, LayerType}, chunk_generator::{ChunkGenerator, GeneratedChunk}, feature::{self, FeaturePlacement}, liquid::LiquidSimulation, tile_entity::TileEntity, world_edit::CellChange, data::schema_definitions::{BiomeMapAdjustments, BiomeSchema, DimensionSchema, OreSchema}}};
 
 pub struct Dimension {
     pub name: String,
     pub size: UVec2,
     pub dimension_schema: DimensionSchema,
+    ores: Vec<OreSchema>,
+    blocks: BlockRegistry,
+    // Ticks since the dimension was created, scheduled updates are due at a tick
+    tick: u64,
+    // The clock players see. It starts out with the tick but can be changed
+    // by commands, so nothing that is scheduled may depend on it.
+    time: u64,
+    random_tick_rng: fastrand::Rng,
+    weather: Weather,
     ecs_world: hecs::World,
     chunks: HashMap<IVec2, Chunk>,
     chunk_generator: ChunkGenerator,
-    chunk_receiver: Receiver<(Chunk, IVec2)>,
+    chunk_receiver: Receiver<GeneratedChunk>,
+    // Feature blocks waiting for their chunk to generate, stored with the chunks
+    pending_features: HashMap<IVec2, Vec<FeaturePlacement>>,
+    // Chunks read back from storage that still need their light
+    unlit_chunks: Vec<IVec2>,
+    liquids: LiquidSimulation,
+    // Cells to relight at the end of the tick, and chunks whose light clients need again
+    pending_relight: Vec<IVec2>,
+    light_changes: HashSet<IVec2>,
     pub players: HashMap<PlayerID, hecs::Entity>,
     player_tasks: DashMap<AliveTaskKey, AliveTask>,
     entities: HashMap<EntityID, hecs::Entity>,
@@ -30,16 +47,37 @@ impl Dimension {
 
         let biome_schemas = biomes_result.unwrap();
         let biome_registry = BiomeRegistry::new(biome_schemas);
-        let (chunk_generator, chunk_receiver) = ChunkGenerator::new(biome_registry, schema.clone(), seed);
+
+        // Ores are optional, a dimension without them is still playable
+        let ores = Self::load_ores(&schema.name, &get_data_path()).unwrap_or_else(|error| {
+            println!("Failed to load ores for dimension {}: {error}", &schema.name);
+            Vec::new()
+        });
+        let blocks = BlockRegistry::load(&get_data_path()).unwrap_or_else(|error| {
+            println!("Failed to load block properties: {error}");
+            BlockRegistry::empty()
+        });
+        let (chunk_generator, chunk_receiver) = ChunkGenerator::new(biome_registry, ores.clone(), schema.clone(), seed);
 
         Dimension { 
             name: schema.name.clone(),
             size: schema.size,
             dimension_schema: schema.clone(),
+            ores,
+            blocks,
+            tick: 0,
+            time: 0,
+            random_tick_rng: fastrand::Rng::with_seed(seed as u32 as u64 ^ RANDOM_TICK_SEED_SALT),
+            weather: Weather::new(schema.weather.clone(), seed as u32 as u64 ^ WEATHER_SEED_SALT),
             ecs_world: World::new(),
             chunks: HashMap::new(),
             chunk_generator,
             chunk_receiver,
+            pending_features: load_pending_features(&schema.name),
+            unlit_chunks: Vec::new(),
+            liquids: LiquidSimulation::new(schema.liquids.clone()),
+            pending_relight: Vec::new(),
+            light_changes: HashSet::new(),
             players: HashMap::new(),
             player_tasks: DashMap::new(),
             entities: HashMap::new(),
@@ -66,11 +104,260 @@ impl Dimension {
         self.chunks.iter().collect::<Vec<(&IVec2, &Chunk)>>()
     }
 
+    pub fn get_chunk(&self, pos: &IVec2) -> Option<&Chunk> {
+        self.chunks.get(pos)
+    }
+
+    // Schedules generation if needed. Returns false if the chunk lies outside the world
+    pub fn request_chunk(&mut self, chunk_pos: IVec2) -> bool {
+        if !self.chunk_within_world_bounds(&chunk_pos) {
+            return false;
+        }
+
+        self.try_load_chunk(chunk_pos);
+        true
+    }
+
+    // Returns false if the chunk containing the block is not loaded
+    pub fn set_block(&mut self, world_pos: IVec2, layer: LayerType, block: BlockState) -> bool {
+        let Some(chunk) = self.chunks.get_mut(&world_to_chunk_pos_2d(world_pos)) else {
+            return false;
+        };
+
+        let local_pos = world_to_local_pos_2d(world_pos);
+        let chunk_relative_pos = ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8);
+        chunk.set_block(chunk_relative_pos, layer, block.block_type, block.block_id, block.texture_index);
+
+        // Placing the same tile entity block again keeps its state
+        if layer == LayerType::Foreground
+            && chunk.get_tile_entity(chunk_relative_pos).is_none()
+            && let Some(schema) = self.blocks.get_tile_entity(block) {
+            chunk.insert_tile_entity(chunk_relative_pos, TileEntity::new(schema));
+        }
+
+        if layer != LayerType::Background {
+            self.pending_relight.push(world_pos);
+        }
+
+        // Liquid next to the block may have somewhere to go now, and the
+        // block itself or the one resting on it may have lost its support
+        if layer == LayerType::Foreground {
+            self.liquids.activate_around(world_pos);
+
+            for position in [world_pos, world_pos + IVec2::Y] {
+                if self.get_block(position, layer).is_some_and(|block| self.blocks.falls(block)) {
+                    self.schedule_update(position, GRAVITY_UPDATE_DELAY);
+                }
+            }
+        }
+
+        // Sprites standing on the block, hanging from it or hung on the wall behind them
+        let supported = match layer {
+            LayerType::Foreground => vec![world_pos + IVec2::Y, world_pos - IVec2::Y],
+            LayerType::Middleground => Vec::new(),
+            LayerType::Background => vec![world_pos],
+        };
+        for position in supported {
+            if sprite::needs_support_check(self, position) {
+                self.schedule_update(position, GRAVITY_UPDATE_DELAY);
+            }
+        }
+        true
+    }
+
+    // Looks at the block again after the delay, unless its chunk is not loaded
+    pub fn schedule_update(&mut self, world_pos: IVec2, delay: u64) {
+        let due_tick = self.tick + delay.max(1);
+        let Some(chunk) = self.chunks.get_mut(&world_to_chunk_pos_2d(world_pos)) else {
+            return;
+        };
+
+        let local_pos = world_to_local_pos_2d(world_pos);
+        chunk.schedule_update(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8), due_tick);
+    }
+
+    // Moves everything that moves on its own by one tick and returns the blocks that changed
+    pub fn tick(&mut self) -> Vec<CellChange> {
+        self.tick += 1;
+        // Only reachable through the time command, the clock starts over from day one
+        self.time = self.time.wrapping_add(1);
+        let mut changes = self.liquids.tick(&mut self.chunks);
+        self.pending_relight.extend(changes.iter().map(|change| change.position));
+
+        for world_pos in self.take_due_updates() {
+            changes.extend(block_update::run_scheduled_update(self, world_pos));
+        }
+        changes.extend(gravity::move_falling_blocks(self));
+
+        for chunk in self.chunks.values_mut() {
+            chunk.tick_tile_entities(&self.blocks);
+        }
+
+        let mut rng = std::mem::take(&mut self.random_tick_rng);
+        let chunk_positions: Vec<IVec2> = self.chunks.keys().copied().collect();
+        for chunk_pos in chunk_positions {
+            let origin = chunk_pos * CHUNK_SIZE as i32;
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let world_pos = origin + IVec2::new(rng.i32(0..CHUNK_SIZE as i32), rng.i32(0..CHUNK_SIZE as i32));
+                changes.extend(block_update::run_random_tick(self, world_pos, &mut rng));
+            }
+        }
+        self.random_tick_rng = rng;
+
+        self.weather.tick(self.size.x as f32 * CHUNK_SIZE as f32 / 2.0);
+        changes.extend(weather::apply_weather(self));
+
+        if !self.pending_relight.is_empty() {
+            let positions = std::mem::take(&mut self.pending_relight);
+            self.lighting().relight(&positions);
+        }
+
+        changes
+    }
+
+    pub fn get_world_time(&self) -> WorldTime {
+        WorldTime { time: self.time, day_length: self.dimension_schema.day_length }
+    }
+
+    pub fn set_time(&mut self, time: u64) {
+        self.time = time;
+    }
+
+    pub fn get_weather(&self) -> &Weather {
+        &self.weather
+    }
+
+    pub fn get_weather_mut(&mut self) -> &mut Weather {
+        &mut self.weather
+    }
+
+    // Chunks whose light changed since the last call
+    pub fn take_light_changes(&mut self) -> Vec<IVec2> {
+        self.light_changes.drain().collect()
+    }
+
+    // Chunks whose liquid levels changed since the last call
+    pub fn take_liquid_changes(&mut self) -> Vec<IVec2> {
+        self.liquids.take_changed_chunks()
+    }
+
+    fn lighting(&mut self) -> Lighting<'_> {
+        Lighting {
+            chunks: &mut self.chunks,
+            blocks: &self.blocks,
+            changed_chunks: &mut self.light_changes,
+        }
+    }
+
+    // Lowest first, so a stack of blocks starts falling from the bottom
+    fn take_due_updates(&mut self) -> Vec<IVec2> {
+        let tick = self.tick;
+        let mut due = Vec::new();
+
+        for (chunk_pos, chunk) in self.chunks.iter_mut() {
+            let origin = chunk_pos * CHUNK_SIZE as i32;
+            for chunk_relative_pos in chunk.take_due_updates(tick) {
+                due.push(origin + IVec2::new(chunk_relative_pos.x as i32, chunk_relative_pos.y as i32));
+            }
+        }
+
+        due.sort_unstable_by_key(|world_pos| (world_pos.y, world_pos.x));
+        due
+    }
+
+    // Returns None if the chunk containing the block is not loaded
+    pub fn get_block(&self, world_pos: IVec2, layer: LayerType) -> Option<BlockState> {
+        let chunk = self.chunks.get(&world_to_chunk_pos_2d(world_pos))?;
+        let local_pos = world_to_local_pos_2d(world_pos);
+        Some(chunk.get_block(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8), layer))
+    }
+
+    // None if there is no tile entity in the foreground there or its chunk is not loaded
+    pub fn get_tile_entity(&self, world_pos: IVec2) -> Option<&TileEntity> {
+        let chunk = self.chunks.get(&world_to_chunk_pos_2d(world_pos))?;
+        let local_pos = world_to_local_pos_2d(world_pos);
+        chunk.get_tile_entity(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8))
+    }
+
+    pub fn get_tile_entity_mut(&mut self, world_pos: IVec2) -> Option<&mut TileEntity> {
+        let chunk = self.chunks.get_mut(&world_to_chunk_pos_2d(world_pos))?;
+        let local_pos = world_to_local_pos_2d(world_pos);
+        chunk.get_tile_entity_mut(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8))
+    }
+
+    // Temperature noise at the block, None if its chunk is not loaded
+    pub fn get_temperature(&self, world_pos: IVec2) -> Option<u8> {
+        let chunk = self.chunks.get(&world_to_chunk_pos_2d(world_pos))?;
+        let local_pos = world_to_local_pos_2d(world_pos);
+        Some(chunk.get_temperature(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8)))
+    }
+
+    // Humidity noise at the block, None if its chunk is not loaded
+    pub fn get_humidity(&self, world_pos: IVec2) -> Option<u8> {
+        let chunk = self.chunks.get(&world_to_chunk_pos_2d(world_pos))?;
+        let local_pos = world_to_local_pos_2d(world_pos);
+        Some(chunk.get_humidity(ChunkRelativePos::new(local_pos.x as u8, local_pos.y as u8)))
+    }
+
+    // Topmost foreground block of the column that is not air. None if there
+    // is none, or an unloaded chunk is in the way before one is found.
+    pub fn get_surface(&self, x: i32) -> Option<IVec2> {
+        let chunk_x = world_to_chunk_pos_2d(IVec2::new(x, 0)).x;
+        let half_y = self.size.y as i32 / 2;
+        let top = (-half_y..half_y).rev().find(|chunk_y| self.chunks.contains_key(&IVec2::new(chunk_x, *chunk_y)))?;
+
+        for chunk_y in (-half_y..=top).rev() {
+            let chunk = self.chunks.get(&IVec2::new(chunk_x, chunk_y))?;
+            if chunk.get_total_block_count() == 0 {
+                continue;
+            }
+
+            let origin_y = chunk_y * CHUNK_SIZE as i32;
+            for y in (origin_y..origin_y + CHUNK_SIZE as i32).rev() {
+                let world_pos = IVec2::new(x, y);
+                if self.get_block(world_pos, LayerType::Foreground)?.block_type != BlockType::Air {
+                    return Some(world_pos);
+                }
+            }
+        }
+        None
+    }
+
+    // See LiquidSimulation::top_up
+    pub fn top_up_liquid(&mut self, world_pos: IVec2, block_id: u32) -> bool {
+        self.liquids.top_up(&mut self.chunks, world_pos, block_id)
+    }
+
+    pub fn get_ores(&self) -> &[OreSchema] {
+        &self.ores
+    }
+
+    pub fn get_liquids(&self) -> &LiquidSimulation {
+        &self.liquids
+    }
+
+    pub fn get_blocks(&self) -> &BlockRegistry {
+        &self.blocks
+    }
+
+    pub fn get_ecs_world(&self) -> &hecs::World {
+        &self.ecs_world
+    }
+
+    pub fn get_ecs_world_mut(&mut self) -> &mut hecs::World {
+        &mut self.ecs_world
+    }
+
+    // False for blocks below, above or beside the world, loaded or not
+    pub fn block_within_world_bounds(&self, world_pos: IVec2) -> bool {
+        self.chunk_within_world_bounds(&world_to_chunk_pos_2d(world_pos))
+    }
+
     fn chunk_at(&self, pos: &IVec2) -> bool {
         return self.chunks.contains_key(&pos);
     }
 
-    fn chunk_within_world_bounds(&self, pos: &IVec2) -> bool {
+    pub fn chunk_within_world_bounds(&self, pos: &IVec2) -> bool {
         let half_x = self.size.x as i32 / 2;
         let half_y = self.size.y as i32 / 2;
 
@@ -85,14 +372,105 @@ impl Dimension {
             // chunk out of bounds
         } else if self.chunk_at(&chunk_pos) {
             // chunk already exists
+        } else if let Some(mut chunk) = self.read_stored_chunk(chunk_pos) {
+            // Neighbours generated after it was stored may reach into it
+            for placement in self.pending_features.remove(&chunk_pos).unwrap_or_default() {
+                chunk.place_feature_block(&placement);
+            }
+            self.chunks.insert(chunk_pos, chunk);
+            self.liquids.activate_chunk_border(&self.chunks, chunk_pos);
+            self.unlit_chunks.push(chunk_pos);
         } else {
             self.chunk_generator.load_chunk(&chunk_pos);
         }
     }
 
+    fn chunk_path(&self, chunk_pos: IVec2) -> PathBuf {
+        storage_directory(&self.name).join(format!("{}_{}.{CHUNK_EXTENSION}", chunk_pos.x, chunk_pos.y))
+    }
+
+    // None if the chunk was never stored. A chunk that fails to read is
+    // generated again rather than stopping the server.
+    fn read_stored_chunk(&self, chunk_pos: IVec2) -> Option<Chunk> {
+        let path = self.chunk_path(chunk_pos);
+        let bytes = std::fs::read(&path).ok()?;
+
+        Chunk::from_bytes(&bytes, self.tick).inspect_err(|error| {
+            println!("Failed to load {}: {error}", path.display());
+        }).ok()
+    }
+
+    // Returns how many chunks were written. The features waiting for chunks
+    // that were never generated go along, so they still land once they are.
+    pub fn save_chunks(&self) -> Result<usize, String> {
+        let directory = storage_directory(&self.name);
+        std::fs::create_dir_all(&directory).map_err(|error| format!("Failed to create {}: {error}", directory.display()))?;
+
+        for (chunk_pos, chunk) in &self.chunks {
+            let path = self.chunk_path(*chunk_pos);
+            std::fs::write(&path, chunk.to_bytes(self.tick)).map_err(|error| format!("Failed to write {}: {error}", path.display()))?;
+        }
+
+        let placements: Vec<FeaturePlacement> = self.pending_features.values().flatten().copied().collect();
+        let path = directory.join(PENDING_FEATURES_FILE);
+        std::fs::write(&path, feature::pending_features_to_bytes(&placements)).map_err(|error| format!("Failed to write {}: {error}", path.display()))?;
+
+        Ok(self.chunks.len())
+    }
+
+    // The world is regenerated from scratch the next time it loads
+    pub fn delete_stored_chunks(&self) -> Result<(), String> {
+        let directory = storage_directory(&self.name);
+        match std::fs::remove_dir_all(&directory) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to delete {}: {error}", directory.display())),
+            _ => Ok(()),
+        }
+    }
+
     pub fn receive_chunks(&mut self) {
-        while let Ok((chunk, pos)) = self.chunk_receiver.try_recv() {
+        let mut received = std::mem::take(&mut self.unlit_chunks);
+
+        while let Ok((mut chunk, pos, overflow)) = self.chunk_receiver.try_recv() {
+            for placement in self.pending_features.remove(&pos).unwrap_or_default() {
+                chunk.place_feature_block(&placement);
+            }
             self.chunks.insert(pos, chunk);
+            self.liquids.activate_chunk_border(&self.chunks, pos);
+            received.push(pos);
+
+            for placement in overflow {
+                self.place_feature_block(placement);
+            }
+        }
+
+        // Lit once the whole batch is in and from the top down, so sky light
+        // falls through every chunk once instead of once per chunk above it
+        received.sort_unstable_by_key(|pos| std::cmp::Reverse(pos.y));
+        for pos in received {
+            self.lighting().light_chunk(pos);
+        }
+    }
+
+    // Adds a chunk without generating it, for tests that build the world by hand
+    #[cfg(test)]
+    pub fn insert_chunk(&mut self, chunk_pos: IVec2, chunk: Chunk) {
+        self.chunks.insert(chunk_pos, chunk);
+        self.lighting().light_chunk(chunk_pos);
+    }
+
+    // Neighbours that already exist get the block right away, the others once they generate
+    fn place_feature_block(&mut self, placement: FeaturePlacement) {
+        let chunk_pos = world_to_chunk_pos_2d(placement.world_pos);
+        if !self.chunk_within_world_bounds(&chunk_pos) {
+            return;
+        }
+
+        match self.chunks.get_mut(&chunk_pos) {
+            Some(chunk) => {
+                chunk.place_feature_block(&placement);
+                self.pending_relight.push(placement.world_pos);
+            },
+            None => self.pending_features.entry(chunk_pos).or_default().push(placement),
         }
     }
 
@@ -133,9 +511,12 @@ impl Dimension {
             let path = entry.path();
 
             if path.is_file() {
-                let file = std::fs::File::open(path)?;
+                let file = std::fs::File::open(&path)?;
 
-                let biome: BiomeSchema = serde_json::from_reader(file)?;
+                let mut biome: BiomeSchema = serde_json::from_reader(file)?;
+                if biome.name.is_empty() {
+                    biome.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                }
 
                 biomes.push(biome);
             }
@@ -143,4 +524,51 @@ impl Dimension {
 
         Ok(biomes)
     }
-}
\ No newline at end of file
+
+    // One file per ore in the ores folder of the dimension, which may be missing
+    fn load_ores(dimension_name: &str, data_dir: &Path) -> Result<Vec<OreSchema>, Box<dyn std::error::Error>> {
+        let mut ores = Vec::new();
+
+        let ores_path = data_dir.join("dimensions").join(dimension_name).join("ores");
+        if !ores_path.is_dir() {
+            return Ok(ores);
+        }
+
+        for entry in std::fs::read_dir(ores_path)? {
+            let path = entry?.path();
+
+            if path.is_file() {
+                let file = std::fs::File::open(path)?;
+                let ore: OreSchema = serde_json::from_reader(file)?;
+                ores.push(ore);
+            }
+        }
+
+        // Directory order is not stable, and the order decides how the random numbers are used
+        ores.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ores)
+    }
+}
+// Where the chunks of a dimension are stored, relative to the working directory
+fn storage_directory(dimension_name: &str) -> PathBuf {
+    PathBuf::from(WORLD_DIRECTORY).join(dimension_name)
+}
+
+// Empty for a world that was never stored
+fn load_pending_features(dimension_name: &str) -> HashMap<IVec2, Vec<FeaturePlacement>> {
+    let path = storage_directory(dimension_name).join(PENDING_FEATURES_FILE);
+    let Ok(bytes) = std::fs::read(&path) else {
+        return HashMap::new();
+    };
+
+    let placements = feature::pending_features_from_bytes(&bytes).unwrap_or_else(|error| {
+        println!("Failed to load {}: {error}", path.display());
+        Vec::new()
+    });
+
+    let mut pending_features: HashMap<IVec2, Vec<FeaturePlacement>> = HashMap::new();
+    for placement in placements {
+        pending_features.entry(world_to_chunk_pos_2d(placement.world_pos)).or_default().push(placement);
+    }
+    pending_features
+}
@@ -0,0 +1,150 @@
+use glam::IVec2;
+
+use crate::engine::{common::{BlockChange, ServerPacket}, server::{common::{BlockPattern, BlockState, LayerType}, constants::WORLD_EDIT_MAX_BLOCKS, sprite, world::Dimension}};
+
+// Inclusive box of world positions, given by any two opposite corners
+#[derive(Debug, Clone, Copy)]
+pub struct EditRegion {
+    pub min: IVec2,
+    pub max: IVec2,
+}
+
+impl EditRegion {
+    pub fn from_corners(a: IVec2, b: IVec2) -> EditRegion {
+        EditRegion { min: a.min(b), max: a.max(b) }
+    }
+
+    pub fn block_count(&self) -> u64 {
+        // In i64, corners far apart would overflow i32. The product of the
+        // widest possible box still overflows u64, so it saturates.
+        let size = self.max.as_i64vec2() - self.min.as_i64vec2() + 1;
+        (size.x as u64).saturating_mul(size.y as u64)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = IVec2> {
+        let (min, max) = (self.min, self.max);
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    pub fn check_size(&self) -> Result<(), String> {
+        self.check_limit(WORLD_EDIT_MAX_BLOCKS)
+    }
+
+    pub fn check_limit(&self, limit: u64) -> Result<(), String> {
+        if self.block_count() > limit {
+            return Err(format!("Region of {} blocks is too large, the limit is {limit}", self.block_count()));
+        }
+        Ok(())
+    }
+}
+
+// One block that was changed, with what was there before
+#[derive(Debug, Clone, Copy)]
+pub struct CellChange {
+    pub position: IVec2,
+    pub layer: LayerType,
+    pub before: BlockState,
+    pub after: BlockState,
+}
+
+impl CellChange {
+    pub fn to_packet(self) -> ServerPacket {
+        ServerPacket::BlockChange(((self.position.x as i64, self.position.y as i64), BlockChange {
+            layer: self.layer,
+            block_type: self.after.block_type,
+            block_id: self.after.block_id,
+            texture_index: self.after.texture_index,
+        }))
+    }
+}
+
+// Blocks that actually changed, ready to be sent to clients and journaled
+#[derive(Default)]
+pub struct EditOutcome {
+    pub changes: Vec<CellChange>,
+    // Positions skipped because their chunk is not loaded
+    pub unloaded: u64,
+    // Positions skipped because they no longer held the expected block
+    pub conflicts: u64,
+    // Sprites that were not placed because something was in the way or they had nothing to rest on
+    pub obstructed: u64,
+}
+
+impl EditOutcome {
+    pub fn set(&mut self, dimension: &mut Dimension, world_pos: IVec2, layer: LayerType, block: BlockState) {
+        match dimension.get_block(world_pos, layer) {
+            None => self.unloaded += 1,
+            Some(current) if current == block => {},
+            Some(current) => {
+                dimension.set_block(world_pos, layer, block);
+                self.changes.push(CellChange { position: world_pos, layer, before: current, after: block });
+            }
+        }
+    }
+
+    // Like set, but sprites covering several cells are placed and broken as a whole
+    pub fn place(&mut self, dimension: &mut Dimension, world_pos: IVec2, layer: LayerType, block: BlockState) {
+        if dimension.get_blocks().get_sprite(block).is_some() {
+            sprite::place_object(dimension, self, world_pos, layer, block);
+            return;
+        }
+
+        sprite::break_object(dimension, self, world_pos, layer);
+        self.set(dimension, world_pos, layer, block);
+    }
+
+    // Only changes the block if it still is what the caller expects, used to undo and redo
+    pub fn set_if(&mut self, dimension: &mut Dimension, world_pos: IVec2, layer: LayerType, expected: BlockState, block: BlockState) {
+        match dimension.get_block(world_pos, layer) {
+            None => self.unloaded += 1,
+            Some(current) if current != expected => self.conflicts += 1,
+            Some(_) => self.set(dimension, world_pos, layer, block),
+        }
+    }
+}
+
+// Sets every block in the region, or only those matching the filter
+pub fn fill(dimension: &mut Dimension, region: EditRegion, layer: LayerType, block: BlockPattern, filter: Option<BlockPattern>) -> EditOutcome {
+    let mut outcome = EditOutcome::default();
+
+    for world_pos in region.positions() {
+        if let Some(filter) = filter {
+            match dimension.get_block(world_pos, layer) {
+                Some(current) if !filter.matches(&current) => continue,
+                _ => {}
+            }
+        }
+
+        outcome.place(dimension, world_pos, layer, block.to_state());
+    }
+
+    outcome
+}
+
+// Copies the region so its minimum corner lands on the destination. The
+// source is read completely first, so overlapping regions copy correctly.
+pub fn clone(dimension: &mut Dimension, region: EditRegion, destination: IVec2, layers: &[LayerType]) -> Result<EditOutcome, String> {
+    // Both corners of the copy have to fit, everything between them does then too
+    let offset = destination.checked_sub(region.min);
+    let far_corner = region.max.checked_sub(region.min).and_then(|size| destination.checked_add(size));
+    let (Some(offset), Some(_)) = (offset, far_corner) else {
+        return Err(format!("Destination {destination} is too far from the region to copy it there"));
+    };
+
+    let mut outcome = EditOutcome::default();
+
+    for layer in layers {
+        let source: Vec<(IVec2, Option<BlockState>)> = region.positions()
+            .map(|world_pos| (world_pos, dimension.get_block(world_pos, *layer)))
+            .collect();
+
+        for (world_pos, block) in source {
+            match block {
+                Some(block) => outcome.set(dimension, world_pos + offset, *layer, block),
+                None => outcome.unloaded += 1,
+            }
+        }
+    }
+
+    Ok(outcome)
+}
@@ -3,12 +3,19 @@ struct VertexOutput {
     @location(0) @interpolate(flat) block_id: u32,
     @location(1) @interpolate(flat) block_type: u32,
     @location(2) @interpolate(flat) texture_index: u32,
+    // Sky light, then block light
+    @location(3) @interpolate(flat) light: vec2<u32>,
+    // Position inside the tile, from the bottom left
+    @location(4) uv: vec2<f32>,
+    @location(5) @interpolate(flat) daylight: f32,
 };
 
 struct PushConstants {
     chunk_pos: vec2<i32>,
     window_size: vec2<f32>,
     zoom_factor: f32,
+    // Share of the sky light that reaches the world at this time of day
+    daylight: f32,
 };
 
 var<push_constant> pc: PushConstants;
@@ -21,6 +28,8 @@ fn vs_main(
     @location(1) position: vec2<u32>,
     @location(2) block_type: u32,
     @location(3) texture_index: u32,
+    @location(4) light: vec2<u32>,
+    @location(5) liquid_level: u32,
 ) -> VertexOutput {
     var out: VertexOutput;
     var local_pos: vec2<f32>;
@@ -37,6 +46,13 @@ fn vs_main(
         default: { /* Should not happen, but required for switch completeness */ }
     }
 
+    // A partly filled liquid cell is drawn from the bottom up to its level,
+    // 0 means full
+    let LIQUID_MAX_LEVEL: f32 = 16.0;
+    if (liquid_level > 0u) {
+        local_pos.y = local_pos.y * f32(liquid_level) / LIQUID_MAX_LEVEL;
+    }
+
     var world_pos: vec2<f32>;
     let tile_pos = vec2<f32>(f32(position.x), f32(position.y));
     let chunk_offset = vec2<f32>(f32(pc.chunk_pos.x), f32(pc.chunk_pos.y));
@@ -56,6 +72,9 @@ fn vs_main(
     out.block_id = block_id;
     out.block_type = block_type;
     out.texture_index = texture_index;
+    out.light = light;
+    out.uv = local_pos;
+    out.daylight = pc.daylight;
     return out;
 }
 
@@ -77,8 +96,55 @@ fn fs_main(
         case 4u: { color = vec3(0.89, 0.82, 0.34); } // Sand
         case 5u: { color = vec3(0.88, 0.91, 0.94); } // Snow
         case 6u: { color = vec3(0.67, 0.85, 0.94); } // Ice
+        case 7u: { color = vec3(0.42, 0.29, 0.16); } // Wood
+        case 8u: { color = vec3(0.20, 0.47, 0.14); } // Leaves
+        case 9u: { color = vec3(0.36, 0.58, 0.22); } // Cactus
+        case 10u: { color = vec3(0.15, 0.15, 0.16); } // Coal
+        case 11u: { color = vec3(0.72, 0.58, 0.50); } // Iron
+        case 12u: { color = vec3(0.78, 0.45, 0.25); } // Copper
+        case 13u: { color = vec3(0.95, 0.80, 0.25); } // Gold
+        case 14u: { color = vec3(0.38, 0.70, 0.20); } // Tall grass
+        case 15u: { color = vec3(0.90, 0.35, 0.45); } // Flower
+        case 16u: { color = vec3(0.16, 0.40, 0.12); } // Vine
+        case 17u: { color = vec3(0.55, 0.42, 0.25); } // Dead bush
+        case 18u: { color = vec3(0.95, 0.40, 0.10); } // Lava
+        case 19u: { color = vec3(0.55, 0.50, 0.30); } // Wheat seeds
+        case 20u: { color = vec3(0.45, 0.65, 0.25); } // Wheat sprout
+        case 21u: { color = vec3(0.85, 0.72, 0.30); } // Wheat
+        case 22u: { color = vec3(1.00, 0.85, 0.45); } // Torch
+        case 23u: { color = vec3(0.60, 0.42, 0.20); } // Chest
+        case 24u: { color = vec3(0.70, 0.56, 0.34); } // Sign
+        case 25u: { color = vec3(0.35, 0.33, 0.32); } // Furnace
+        case 26u: { color = vec3(0.80, 0.90, 0.95); } // Glass
+        case 27u: { color = vec3(0.50, 0.34, 0.18); } // Door
+        case 28u: { color = vec3(0.58, 0.40, 0.22); } // Table
+        case 29u: { color = vec3(0.75, 0.55, 0.35); } // Painting
+        case 30u: { color = vec3(0.95, 0.82, 0.40); } // Chandelier
+        case 31u: { color = vec3(0.25, 0.52, 0.18); } // Bush
         default: { color = vec3(0.00, 0.00, 0.00); } // None
     }
 
+    // Sprites covering several tiles are shaded as one object, the texture
+    // index says which tile of it this is (x in the low four bits, y in
+    // the high four) so every tile draws its own part of the gradient
+    if (in.block_type == 3u) {
+        let cell = vec2<f32>(f32(in.texture_index & 15u), f32(in.texture_index >> 4u));
+        let sprite_pos = cell + in.uv;
+        color = color * (0.75 + 0.25 * min(sprite_pos.y / 4.0, 1.0));
+    }
+
+    // Walls sit behind everything else, darken them so the foreground stands out
+    if (in.block_type == 2u) {
+        color = color * 0.5;
+    }
+
+    // Per tile lighting, whichever of the two lights is brighter wins. Sky
+    // light dims at night, block light does not. Nothing goes fully black
+    // so unlit caves can still be made out.
+    let MAX_LIGHT_LEVEL: f32 = 15.0;
+    let MIN_BRIGHTNESS: f32 = 0.08;
+    let light = max(f32(in.light.x) * in.daylight, f32(in.light.y)) / MAX_LIGHT_LEVEL;
+    color = color * max(light, MIN_BRIGHTNESS);
+
     return vec4<f32>(color, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // In blocks, so the drops stay put in the world while zooming
+    @location(0) world_pos: vec2<f32>,
+    @location(1) @interpolate(flat) precipitation: u32,
+    @location(2) @interpolate(flat) intensity: f32,
+    @location(3) @interpolate(flat) time: f32,
+    @location(4) @interpolate(flat) daylight: f32,
+};
+
+struct PushConstants {
+    window_size: vec2<f32>,
+    zoom_factor: f32,
+    // Seconds, wraps around every WEATHER_ANIMATION_PERIOD
+    time: f32,
+    daylight: f32,
+};
+
+var<push_constant> pc: PushConstants;
+
+// Every column is one instance, a quad from the ground to the top of the screen
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_id: u32,
+    @location(0) column: i32,
+    @location(1) bottom: f32,
+    @location(2) top: f32,
+    @location(3) precipitation: u32,
+    @location(4) intensity: f32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    var local_pos: vec2<f32>;
+
+    switch vertex_id {
+        case 0u: { local_pos = vec2<f32>(0.0, 1.0); }
+        case 1u: { local_pos = vec2<f32>(0.0, 0.0); }
+        case 2u: { local_pos = vec2<f32>(1.0, 1.0); }
+
+        case 3u: { local_pos = vec2<f32>(1.0, 1.0); }
+        case 4u: { local_pos = vec2<f32>(0.0, 0.0); }
+        case 5u: { local_pos = vec2<f32>(1.0, 0.0); }
+
+        default: { /* Should not happen, but required for switch completeness */ }
+    }
+
+    let world_pos = vec2<f32>(f32(column) + local_pos.x, mix(bottom, top, local_pos.y));
+
+    let TILE_PIXEL_SIZE: f32 = 16.0;
+    let final_pos = world_pos * TILE_PIXEL_SIZE / pc.window_size * pc.zoom_factor;
+
+    out.clip_position = vec4<f32>(final_pos.x, final_pos.y, 0.0, 1.0);
+    out.world_pos = world_pos;
+    out.precipitation = precipitation;
+    out.intensity = intensity;
+    out.time = pc.time;
+    out.daylight = pc.daylight;
+    return out;
+}
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+}
+
+@fragment
+fn fs_main(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    // Rain falls fast and straight, snow slowly and swaying
+    let is_snow = in.precipitation == 1u;
+    let speed = select(20.0, 3.0, is_snow);
+
+    // The column is cut into cells one block high that fall with the
+    // weather, some of them hold a drop or a flake
+    let falling = vec2<f32>(in.world_pos.x, in.world_pos.y + in.time * speed);
+    let cell = floor(falling);
+    let seed = hash(cell);
+
+    // Up to half of the cells are filled at full intensity
+    if (seed > in.intensity * 0.5) {
+        discard;
+    }
+
+    let inside = fract(falling);
+    let offset = 0.2 + 0.6 * hash(cell + vec2<f32>(0.5, 0.5));
+
+    var color: vec4<f32>;
+    if (is_snow) {
+        let sway = sin(in.time * 1.5 + seed * 6.28) * 0.15;
+        if (distance(inside, vec2<f32>(offset + sway, 0.5)) > 0.1) {
+            discard;
+        }
+        color = vec4<f32>(0.95, 0.97, 1.0, 0.9);
+    } else {
+        if (abs(inside.x - offset) > 0.03 || inside.y < 0.4) {
+            discard;
+        }
+        color = vec4<f32>(0.55, 0.65, 0.85, 0.6);
+    }
+
+    // Dimmer at night, but never gone
+    let MIN_BRIGHTNESS: f32 = 0.25;
+    return vec4<f32>(color.rgb * max(in.daylight, MIN_BRIGHTNESS), color.a);
+}
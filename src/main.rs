@@ -4,7 +4,7 @@ use std::{collections::HashMap, sync::{mpsc::{Receiver, Sender}, LazyLock}, thre
 
 use winit::{event_loop::{EventLoop, ControlFlow}};
 
-use crate::engine::{client::client::Client, command_registry::{self, CommandEnvironment, CommandRegistry, DebugCommand, DebugCommandWithArgs}, common::{BlockChange, ChunkMesh, PacketChunk, ServerPacket}, server::{common::{BlockType, LayerType}, constants::TICK_RATE, server::Server}};
+use crate::engine::{client::client::Client, command_registry::{self, CommandEnvironment, CommandRegistry, CommandSource, ConsoleOutput, DebugCommand, DebugCommandWithArgs}, console::Console, common::{BlockChange, ChunkMesh, PacketChunk, ServerPacket}, network::Connection, server::{common::{BlockType, LayerType}, constants::{AUTOSAVE_INTERVAL_TICKS, KEEPALIVE_INTERVAL_TICKS, TICK_RATE}, server::Server}};
 
 
 fn main() {
@@ -12,64 +12,88 @@ fn main() {
 
     let (tx_console_to_client, rx_console_to_client) = std::sync::mpsc::channel::<DebugCommandWithArgs>();
     let (tx_console_to_server, rx_console_to_server) = std::sync::mpsc::channel::<DebugCommandWithArgs>();
-    let (tx_server_to_client, rx_server_to_client) = std::sync::mpsc::channel::<Vec<u8>>();
+    let (tx_connection_to_server, rx_connection_to_server) = std::sync::mpsc::channel::<Connection>();
+
+    // Kept to stop the server once the window closes
+    #[cfg(all(feature = "client", feature = "server"))]
+    let tx_stop_server = tx_console_to_server.clone();
 
     // Spawn a thread that reads terminal input
     spawn_console_thread(tx_console_to_client, tx_console_to_server);
 
-    // Spawn the server thread
-    #[cfg(feature = "server")]
+    // Start server on a separate thread if also launching client
+    #[cfg(all(feature = "client", feature = "server"))]
+    let server_thread = spawn_server_thread(rx_connection_to_server, rx_console_to_server);
+
+    // Start server in main if not launching client
+    #[cfg(all(feature = "server", not(feature = "client")))]
     {
-        // Start server on a separate thread if also launching client
-        #[cfg(feature = "client")]
-        spawn_server_thread(tx_server_to_client, rx_console_to_server);
-
-        // Start server in main if not launching client
-        #[cfg(not(feature = "client"))]
-        {
-            println!("No client - initializing server on main thread");
-            initialize_server(tx_server_to_client, rx_console_to_server);
-        }
+        println!("No client - initializing server on main thread");
+        initialize_server(rx_connection_to_server, rx_console_to_server);
     }
 
     // Initialize client and start event loop
     #[cfg(feature = "client")]
-    initialize_client(rx_console_to_client, rx_server_to_client);
+    initialize_client(rx_console_to_client, tx_connection_to_server);
+
+    // The server saves the world on the way out, it may have stopped already
+    #[cfg(all(feature = "client", feature = "server"))]
+    {
+        if let Ok(cmd) = command_registry::parse_command_line("stopserver") {
+            let _ = tx_stop_server.send(cmd);
+        }
+        let _ = server_thread.join();
+    }
 }
 
-fn initialize_client(rx_console_to_client: Receiver<DebugCommandWithArgs>, rx_server_to_client: Receiver<Vec<u8>>) {
+fn initialize_client(rx_console_to_client: Receiver<DebugCommandWithArgs>, tx_connection_to_server: Sender<Connection>) {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut client: Client = Client::new(rx_console_to_client, rx_server_to_client);
+    // Hand the server its end of the link, it picks it up on the next tick
+    let (client_connection, server_connection) = Connection::pair();
+    let _ = tx_connection_to_server.send(server_connection);
+
+    let mut client: Client = Client::new(rx_console_to_client, client_connection);
 
     println!("Started client with player UUID [{}] and nickname \"{}\"", client.get_uuid_string(), client.get_nickname());
 
     event_loop.run_app(&mut client).unwrap();
 }
 
-fn spawn_server_thread(tx_server_to_client: Sender<Vec<u8>>, rx_console_to_server: Receiver<DebugCommandWithArgs>) -> JoinHandle<()> {
+fn spawn_server_thread(rx_connection_to_server: Receiver<Connection>, rx_console_to_server: Receiver<DebugCommandWithArgs>) -> JoinHandle<()> {
     return std::thread::spawn(move || {
         println!("Server thread spawned");
 
-        initialize_server(tx_server_to_client, rx_console_to_server);
+        initialize_server(rx_connection_to_server, rx_console_to_server);
     });
 }
 
-fn initialize_server(tx_server_to_client: Sender<Vec<u8>>, rx_console_to_server: Receiver<DebugCommandWithArgs>) {
+fn initialize_server(rx_connection_to_server: Receiver<Connection>, rx_console_to_server: Receiver<DebugCommandWithArgs>) {
     let tick_duration = Duration::from_micros(1_000_000 / TICK_RATE);
     println!("Game tick loop started at {} TPS.", TICK_RATE);
 
-    let mut server = Server::start_server(rx_console_to_server, tx_server_to_client);
+    let mut server = Server::start_server(rx_console_to_server, rx_connection_to_server);
     let mut next_tick = Instant::now();
     let mut _ticks: u128 = 0;
 
     while server.is_running() {
         server.process_commands();
+        server.process_packets();
         server.on_tick();
+
+        if _ticks.is_multiple_of(KEEPALIVE_INTERVAL_TICKS) {
+            server.send_keepalive();
+            server.send_status();
+            server.send_world_time();
+            server.send_weather();
+        }
+
+        if _ticks != 0 && _ticks.is_multiple_of(AUTOSAVE_INTERVAL_TICKS) {
+            server.save();
+        }
         
         if _ticks % 60 == 0 {
-            server.send_packet(ServerPacket::Ping);
 
             for dimension in server.dimensions.values_mut() {
                 dimension.receive_chunks();
@@ -101,31 +125,27 @@ fn initialize_server(tx_server_to_client: Sender<Vec<u8>>, rx_console_to_server:
 fn spawn_console_thread(tx_to_client: Sender<DebugCommandWithArgs>, tx_to_server: Sender<DebugCommandWithArgs>) {
     std::thread::spawn(move || {
         println!("Terminal input thread spawned");
-        use std::io::{self, BufRead};
-        let stdin = io::stdin();
-        for line in stdin.lock().lines() {
-            let cmd = line.unwrap().to_lowercase();
+        let mut console = match Console::new() {
+            Ok(console) => console,
+            Err(error) => {
+                println!("Failed to start console: {error}");
+                return;
+            }
+        };
+
+        while let Some(line) = console.read_line() {
 
-            let parts: Vec<&str> = cmd.trim().split_whitespace().collect();
-            if parts.is_empty() {
+            if line.trim().is_empty() {
                 continue;
             }
 
-            let cmd_name = parts[0];
-            let args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
-
-            if let Some(command) = get_global_command_registry().get(cmd_name).copied() {
-                let cmd = DebugCommandWithArgs {
-                    debug_command: command,
-                    command_args: args,
-                };
-                match command.command_environment {
+            match command_registry::parse_command_line(&line) {
+                Ok(cmd) => match cmd.debug_command.command_environment {
                     CommandEnvironment::Client => {tx_to_client.send(cmd).unwrap()},
                     CommandEnvironment::Server => {tx_to_server.send(cmd).unwrap()},
-                    CommandEnvironment::Main => {command_registry::handle_main_command(&cmd)},
-                }
-            } else {
-                println!("Unknown command. Type 'help' for a list.");
+                    CommandEnvironment::Main => {command_registry::handle_main_command(&cmd, CommandSource::console(), &mut ConsoleOutput).write_to(&mut ConsoleOutput)},
+                },
+                Err(error) => println!("{error}"),
             }
         }
         println!("Terminal input thread shut down");